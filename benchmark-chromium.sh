@@ -18,6 +18,10 @@ if [ -e "$results/done" ]; then
 fi
 rm -f "$results/*"
 
+# On SIGINT, finish the current run, then stop without marking the results as done.
+interrupted=
+trap 'interrupted=1' INT
+
 for i in {01..$run_count}; do
     echo ">>> $i"
 
@@ -60,6 +64,10 @@ for i in {01..$run_count}; do
     done
     echo
     echo
+    if [ -n "$interrupted" ]; then
+        echo ">>> Interrupted; stopping after run $i"
+        exit 130
+    fi
 done
 
 touch "$results/done"
@@ -10,6 +10,8 @@ if [ $# -gt 0 ]; then
     shift
 fi
 browser_open_time=${SERVO_PERF_BROWSER_OPEN_TIME-10}
+# If set, write each run’s files here first, then move them into the results once the run succeeds.
+staging_dir=${SERVO_PERF_STAGING_DIR-}
 
 mkdir -p "$results"
 if [ -e "$results/done" ]; then
@@ -18,24 +20,39 @@ if [ -e "$results/done" ]; then
 fi
 rm -f "$results/*"
 
+# On SIGINT, finish the current run, then stop without marking the results as done.
+interrupted=
+trap 'interrupted=1' INT
+
 export SERVO_TRACING='info'
 for i in {01..$run_count}; do
     echo ">>> $i"
 
-    # Write a manifest that pairs the HTML and Perfetto traces of each run,
-    # both as paths relative to the directory containing the manifest file.
     html_trace=trace$i.html
     perfetto_trace=servo$i.pftrace
-    jq -en \
-        --arg html "$html_trace" \
-        --arg perfetto "$perfetto_trace" \
-        '{$html, $perfetto}' > "$results/manifest$i.json"
+    manifest=manifest$i.json
+    run_dir=${staging_dir:-$results}
+    # The manifest is only written once the run succeeds, so we can skip runs that were done
+    # before the previous attempt at these results was interrupted.
+    if [ -e "$results/$manifest" ]; then
+        echo ">>> $i is done; skipping"
+        continue
+    fi
 
-    "$servo" \
-        --profiler-trace-path="$results/$html_trace" --print-pwm \
-        --ignore-certificate-errors \
-        "$@" \
-        "$url" &
+    # Servo writes its Perfetto trace to `servo.pftrace` in the working directory, so run it in a
+    # directory of its own, in case other samples are being collected at the same time.
+    work_dir=$(mktemp -d)
+    if [[ $servo == */* ]]; then
+        servo=${servo:a}
+    fi
+    (
+        cd "$work_dir"
+        exec "$servo" \
+            --profiler-trace-path="${run_dir:a}/$html_trace" --print-pwm \
+            --ignore-certificate-errors \
+            "$@" \
+            "$url"
+    ) &
     pid=$!
 
     sleep "$browser_open_time"
@@ -46,9 +63,30 @@ for i in {01..$run_count}; do
         sleep 1
     done
     echo
-    mv servo.pftrace "$results/$perfetto_trace"
+    mv "$work_dir/servo.pftrace" "$run_dir/$perfetto_trace"
+    rm -rf "$work_dir"
+
+    # Only now that the run has succeeded, write a manifest that pairs the HTML and Perfetto traces
+    # of the run, both as paths relative to the directory containing the manifest file.
+    jq -en \
+        --arg html "$html_trace" \
+        --arg perfetto "$perfetto_trace" \
+        '{$html, $perfetto}' > "$run_dir/$manifest"
+    if [ -n "$staging_dir" ]; then
+        # Move the manifest last, so analysis never sees a manifest without its traces.
+        # Copy to a temporary name first, so the final rename is atomic even across filesystems.
+        for file in "$html_trace" "$perfetto_trace" "$manifest"; do
+            cp "$staging_dir/$file" "$results/.$file.partial"
+            mv "$results/.$file.partial" "$results/$file"
+            rm "$staging_dir/$file"
+        done
+    fi
     echo
     echo
+    if [ -n "$interrupted" ]; then
+        echo ">>> Interrupted; stopping after run $i"
+        exit 130
+    fi
 done
 
 touch "$results/done"
@@ -0,0 +1,96 @@
+#!/usr/bin/env zsh
+# Usage: benchmark-webkit.sh <path/to/MiniBrowser> <url> <run count> [path/to/results] [extra MiniBrowser arguments ...]
+# Requires `curl`, `jq`, and `websocat`.
+set -euo pipefail
+script_dir=${0:a:h}
+browser=$1; shift
+url=$1; shift
+run_count=$1; shift
+results=${1-$(mktemp -d)}
+if [ $# -gt 0 ]; then
+    shift
+fi
+browser_open_time=${SERVO_PERF_BROWSER_OPEN_TIME-10}
+inspector_port=${SERVO_PERF_WEBKIT_INSPECTOR_PORT-9226}
+
+mkdir -p "$results"
+if [ -e "$results/done" ]; then
+    echo ">>> $results is done; skipping"
+    exit
+fi
+rm -f "$results/*"
+
+# On SIGINT, finish the current run, then stop without marking the results as done.
+interrupted=
+trap 'interrupted=1' INT
+
+for i in {01..$run_count}; do
+    echo ">>> $i"
+    messages=$(mktemp)
+
+    # Start on a blank page, so we can start recording the timeline before navigating. The
+    # remote inspector serves each page at `/socket/<connection>/<target>/WebPage`.
+    # <https://docs.webkit.org/Ports/WebKitGTK%20and%20WPE%20WebKit/Environment%20Variables.html>
+    WEBKIT_INSPECTOR_HTTP_SERVER=127.0.0.1:$inspector_port "$browser" --private "$@" about:blank &
+    pid=$!
+
+    printf 'Waiting for inspector'
+    socket=
+    while [ -z "$socket" ]; do
+        socket=$(curl -fsS "http://127.0.0.1:$inspector_port/" 2> /dev/null | grep -o '/socket/[0-9]*/[0-9]*/WebPage' | head -1) || :
+        printf .
+        sleep 1
+    done
+    echo
+
+    # Talk to the page over the Web Inspector protocol, with `websocat` as a coprocess. Commands
+    # for the page go through `Target.sendMessageToTarget`, to the target it announces first.
+    coproc websocat -t "ws://127.0.0.1:$inspector_port$socket"
+    target=
+    while [ -z "$target" ]; do
+        read -p line
+        target=$(jq -r 'select(.method == "Target.targetCreated") | .params.targetInfo.targetId' <<< "$line")
+    done
+    cat <&p > "$messages" &
+    reader_pid=$!
+    next_id=0
+    send() {
+        next_id=$((next_id + 1))
+        params=${2-}
+        if [ -z "$params" ]; then
+            params='{}'
+        fi
+        jq -cn --arg target "$target" --argjson id $next_id --arg method "$1" --argjson params "$params" \
+            '{id: $id, method: "Target.sendMessageToTarget", params: {targetId: $target, message: ({id: $id, method: $method, params: $params} | tojson)}}' >&p
+    }
+
+    send Timeline.start
+    send Page.navigate "$(jq -cn --arg url "$url" '{url: $url}')"
+    sleep "$browser_open_time"
+    send Runtime.evaluate '{"expression": "JSON.stringify(performance.getEntriesByType(\"paint\"))", "returnByValue": true}'
+    paint_timings_id=$next_id
+    send Timeline.stop
+    sleep 1
+
+    kill $reader_pid $pid || :
+    wait $pid || :
+    coproc :
+
+    # Keep the timeline records, and the paint timings from our `Runtime.evaluate`.
+    jq -s --argjson id $paint_timings_id '
+        [.[] | select(.method == "Target.dispatchMessageFromTarget") | .params.message | fromjson] as $messages
+        | {
+            records: [$messages[] | select(.method == "Timeline.eventRecorded") | .params.record],
+            paintTimings: ([$messages[] | select(.id == $id) | .result.result.value | fromjson][0] // [])
+        }
+    ' "$messages" > "$results/webkit$i.json"
+    rm "$messages"
+    echo
+    if [ -n "$interrupted" ]; then
+        echo ">>> Interrupted; stopping after run $i"
+        exit 130
+    fi
+done
+
+touch "$results/done"
+echo "Results: $results"
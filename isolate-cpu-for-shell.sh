@@ -1,10 +1,16 @@
 #!/usr/bin/env zsh
 # Based on: <https://testbit.eu/2023/cgroup-cpuset>
-# To enable: isolate-cpu-for-shell.sh <pid> <cpu ids ...>
+# To enable: isolate-cpu-for-shell.sh [--mems=<numa node ids>] <pid> <cpu ids ...>
 #       e.g. isolate-cpu-for-shell.sh $$ 10 {13..15}
+#       e.g. isolate-cpu-for-shell.sh --mems=1 $$ 10 {13..15}
 # To disable: undo-cpu-isolation.sh
 set -euo pipefail
 
+selected_mems=
+case "${1-}" in
+(--mems=*) selected_mems=${1#--mems=}; shift ;;
+esac
+
 all_cpu_and_core_ids() {
     lscpu --parse=cpu,core | rg -v '^#' | sed $'s/,/\t/g'
 }
@@ -18,7 +24,7 @@ cpu_ids_for_core_id() {
 }
 
 usage() {
-    >&2 echo 'Usage: isolate-cpu-for-shell <pid> <cpu ids ...>'
+    >&2 echo 'Usage: isolate-cpu-for-shell [--mems=<numa node ids>] <pid> <cpu ids ...>'
     >&2 printf 'Available cpu ids:'
     all_cpu_and_core_ids | while read -r cpu core; do
         # If this is the first cpu id with the given core id...
@@ -94,6 +100,10 @@ if test -r /proc/$shell_pid/exe ; then
     echo "+cpu" >> /sys/fs/cgroup/shield/cgroup.subtree_control
     echo "+cpuset" >> /sys/fs/cgroup/shield/cgroup.subtree_control
     echo "$selected_cpus"   > /sys/fs/cgroup/shield/cpuset.cpus
+    if [ -n "$selected_mems" ]; then
+        >&2 echo "Selected memory node ids: $selected_mems"
+        echo "$selected_mems" > /sys/fs/cgroup/shield/cpuset.mems
+    fi
 
     # Move all other cgroups to the remaining CPUs.
     for cpscpus in /sys/fs/cgroup/**/cpuset.cpus ; do
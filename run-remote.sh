@@ -0,0 +1,33 @@
+#!/usr/bin/env zsh
+# Usage: run-remote.sh <host> <path/to/runner> <path/to/browser> <url> <run count> <path/to/results> [extra browser arguments ...]
+# Copies a benchmark runner script to <host> over SSH, runs it there with a remote results directory,
+# then copies the results back to the local results directory.
+set -euo pipefail
+host=$1; shift
+runner=$1; shift
+browser=$1; shift
+url=$1; shift
+run_count=$1; shift
+results=$1; shift
+browser_open_time=${SERVO_PERF_BROWSER_OPEN_TIME-10}
+
+remote_dir=$(ssh "$host" mktemp -d)
+trap 'ssh "$host" rm -rf ${(qq)remote_dir}' EXIT
+
+# On SIGINT, let the remote runner finish, since we can’t easily tell it to stop after its current run.
+trap 'echo ">>> Interrupted; waiting for remote runner to finish"' INT
+
+# On SIGTERM, which `collect` sends when the runner times out, kill the remote runner and browser,
+# since killing our `ssh` does not kill them. The runner is the leader of its own SSH session.
+trap 'echo ">>> Timed out; killing remote runner and browser"; ssh "$host" "pkill -KILL -s \$(cat ${(qq)remote_dir}/runner.pid)"; exit 1' TERM
+
+scp -q "$runner" "$host:$remote_dir/runner"
+ssh "$host" "cd ${(qq)remote_dir} && chmod +x runner && echo \$\$ > runner.pid && export SERVO_PERF_BROWSER_OPEN_TIME=${(qq)browser_open_time} && exec ./runner ${(qq)browser} ${(qq)url} ${(qq)run_count} results ${(qq)@}"
+
+# Copy the results back without the remote `done` marker, then mark the local results as done,
+# so an interrupted copy never leaves a sample that looks done but is missing traces.
+ssh "$host" "rm -f ${(qq)remote_dir}/results/done"
+mkdir -p "$results"
+scp -qr "$host:$remote_dir/results/*" "$results"
+touch "$results/done"
+echo "Results: $results"
@@ -1,59 +1,151 @@
-use std::{ffi::OsStr, fs::File, io::Write, path::Path, process::Command};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    ffi::OsStr,
+    fs::File,
+    hash::Hasher,
+    io::{BufRead, BufReader, Read, Write},
+    path::{Path, PathBuf},
+    process::Command,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        LazyLock, Mutex,
+    },
+};
 
-use jane_eyre::eyre::{self, bail, OptionExt};
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
-use tracing::info;
+use flate2::read::GzDecoder;
+use jane_eyre::eyre::{self, bail, Context, OptionExt};
+use rayon::{
+    iter::{IntoParallelRefIterator, ParallelIterator},
+    ThreadPoolBuilder,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
 
-use crate::study::{Engine, KeyedCpuConfig, KeyedEngine, KeyedSite, Study};
+use crate::{
+    profile::print_profile,
+    study::{Engine, KeyedCpuConfig, KeyedEngine, KeyedSite, Study},
+};
+
+/// Input files that failed to analyse, for the summary at the end of `analyse`.
+static FAILED_PATHS: LazyLock<Mutex<BTreeSet<String>>> =
+    LazyLock::new(|| Mutex::new(BTreeSet::default()));
 
 pub fn main(args: Vec<String>) -> eyre::Result<()> {
     let study_dir = Path::new(
         args.iter()
             .nth(0)
-            .expect("Usage: analyse <studies/example>"),
+            .expect("Usage: analyse <studies/example> [--profile] [--allow-failures]"),
     );
     let study = Study::load(study_dir.join("study.toml"))?;
+    let mut profile = false;
+    let mut allow_failures = false;
+    for option in args[1..].iter() {
+        match &**option {
+            "--profile" => profile = true,
+            "--allow-failures" => allow_failures = true,
+            other => bail!("Unknown option: {other}"),
+        }
+    }
 
     // Change working directory to the study directory.
     // We need this for `traceconv_command` and `isolate_cpu_command`.
     std::env::set_current_dir(study_dir)?;
 
+    for engine in study.engines().filter(|engine| !engine.has_summaries()) {
+        warn!(
+            engine = engine.key,
+            engine_type = engine.type_name(),
+            "Samples of this engine type can’t be analysed yet; skipping"
+        );
+    }
+    let sample_count =
+        study.cpu_configs().count() * study.sites().count() * study.analysed_engines().count();
+    let mut processed_count = 0;
+    let mut succeeded_count = 0;
+    let mut empty_samples = vec![];
+    let mut failed_samples = vec![];
     for cpu_config in study.cpu_configs() {
         for site in study.sites() {
-            for engine in study.engines() {
-                analyse_sample(&study, cpu_config, site, engine)?;
+            for engine in study.analysed_engines() {
+                let sample_dir = Path::new(cpu_config.key).join(site.key).join(engine.key);
+                match analyse_sample(&study, cpu_config, site, engine) {
+                    Ok(0) => empty_samples.push(sample_dir),
+                    Ok(_) => succeeded_count += 1,
+                    Err(error) => {
+                        warn!(?sample_dir, "Failed to analyse sample: {error}");
+                        failed_samples.push((sample_dir, error));
+                    }
+                }
+                processed_count += 1;
+                info!("Processed {processed_count}/{sample_count} samples");
             }
         }
     }
 
+    if profile {
+        print_profile();
+    }
+
+    // Print the paths that need attention, so they can be fixed or collected again.
+    let failed_paths = FAILED_PATHS
+        .lock()
+        .map(|paths| paths.clone())
+        .unwrap_or_default();
+    println!(">>> Analysis summary");
+    println!(
+        "{succeeded_count} samples succeeded, {} had no valid individuals, {} failed",
+        empty_samples.len(),
+        failed_samples.len(),
+    );
+    println!("{} files failed to analyse", failed_paths.len());
+    for sample_dir in empty_samples.iter() {
+        println!("No valid individuals: {}", sample_dir.display());
+    }
+    for (sample_dir, error) in failed_samples.iter() {
+        println!("Failed sample: {} ({error})", sample_dir.display());
+    }
+    for path in failed_paths.iter() {
+        println!("Failed file: {path}");
+    }
+    if !failed_samples.is_empty() {
+        bail!(
+            "Failed to analyse {} samples (see above)",
+            failed_samples.len()
+        );
+    }
+    // Fail if any file failed, so that CI notices, even though the other files were analysed.
+    if !failed_paths.is_empty() && !allow_failures {
+        bail!(
+            "Failed to analyse {} files (see above); to allow this, add --allow-failures",
+            failed_paths.len()
+        );
+    }
+
     Ok(())
 }
 
+/// Records an input file that failed to analyse, so it can be listed at the end of `analyse`.
+pub fn record_failed_path(path: &str) {
+    if let Ok(mut failed_paths) = FAILED_PATHS.lock() {
+        failed_paths.insert(path.to_owned());
+    }
+}
+
+/// Analyses the given sample, returning the number of valid individuals.
 #[tracing::instrument(level = "error", skip(study, cpu_config, site, engine), fields(cpu_config = cpu_config.key, site = site.key, engine = engine.key))]
 fn analyse_sample(
     study: &Study,
     cpu_config: KeyedCpuConfig<'_>,
     site: KeyedSite<'_>,
     engine: KeyedEngine<'_>,
-) -> eyre::Result<()> {
+) -> eyre::Result<usize> {
     let sample_dir = Path::new(cpu_config.key).join(site.key).join(engine.key);
-    let mut args = vec![site.url.to_owned()];
+    let mut args = vec![site.analysis_url().to_owned()];
 
     info!(?sample_dir, "Analysing sample");
     match engine.engine {
-        Engine::Servo { .. } => {
-            for entry in std::fs::read_dir(&sample_dir)? {
-                let path = entry?.path();
-                // Skip our own output files `summaries.*`.
-                if path.file_stem() == Some(OsStr::new("summaries")) {
-                    continue;
-                }
-                // Filter to `manifest*.json`.
-                if path.extension() == Some(OsStr::new("json")) {
-                    args.push(path.to_str().ok_or_eyre("Unsupported path")?.to_owned());
-                }
-            }
-        }
+        // Servo and WebKit samples need no conversion.
+        Engine::Servo { .. } | Engine::WebKit { .. } => {}
         Engine::Chromium { .. } | Engine::ChromeDriver { .. } => {
             let mut json_paths = vec![];
             let mut convert_jobs = vec![];
@@ -68,50 +160,347 @@ fn analyse_sample(
                             .strip_suffix(".pftrace")
                             .expect("Guaranteed by extension check")
                     );
-                    if !std::fs::exists(&json_path)? {
-                        convert_jobs.push((pftrace_path.to_owned(), json_path.clone()));
-                    }
+                    // Conversion is skipped if the JSON trace is up to date (see below).
+                    convert_jobs.push((pftrace_path.to_owned(), json_path.clone()));
                     json_paths.push(json_path);
                 }
             }
-            let traceconv_results = convert_jobs
-                .par_iter()
-                .map(|(pftrace_path, json_path)| -> eyre::Result<()> {
-                    convert_pftrace_to_json(study, pftrace_path, json_path)
-                })
-                .collect::<Vec<_>>();
+            // Run traceconv in a pool of its own, to limit how many of them run at once.
+            let pool = ThreadPoolBuilder::new()
+                .num_threads(study.traceconv_jobs())
+                .build()?;
+            let started_count = AtomicUsize::new(0);
+            let active_count = AtomicUsize::new(0);
+            let traceconv_results = pool.install(|| {
+                convert_jobs
+                    .par_iter()
+                    .map(|(pftrace_path, json_path)| -> eyre::Result<()> {
+                        let started = started_count.fetch_add(1, Ordering::SeqCst) + 1;
+                        let active = active_count.fetch_add(1, Ordering::SeqCst) + 1;
+                        info!(
+                            active,
+                            queued = convert_jobs.len() - started,
+                            "Starting conversion"
+                        );
+                        let result = convert_pftrace_to_json(study, pftrace_path, json_path);
+                        active_count.fetch_sub(1, Ordering::SeqCst);
+                        result
+                    })
+                    .collect::<Vec<_>>()
+            });
             for result in traceconv_results {
                 result?;
             }
-            for entry in std::fs::read_dir(&sample_dir)? {
-                let path = entry?.path();
-                // Skip our own output files `summaries.*`.
-                if path.file_stem() == Some(OsStr::new("summaries")) {
-                    continue;
-                }
-                // Filter to `chrome*.json`.
-                if path.extension() == Some(OsStr::new("json")) {
-                    args.push(path.to_str().ok_or_eyre("Unsupported path")?.to_owned());
-                }
-            }
+        }
+        // Firefox writes profiles in the Gecko profiler JSON format, which is neither the Chrome
+        // JSON trace format nor anything traceconv can convert, so it needs an analyser of its own.
+        Engine::GeckoDriver { .. } => {
+            unreachable!("Guaranteed by KeyedEngine::has_summaries")
+        }
+        // The browser keeps its traces on its own machine (see collect.rs).
+        Engine::RemoteWebDriver { .. } => {
+            unreachable!("Guaranteed by KeyedEngine::has_summaries")
         }
     }
+    args.extend(sample_input_paths(&sample_dir)?);
 
-    let summaries = match engine.engine {
-        Engine::Servo { .. } => crate::servo::compute_summaries(args)?,
+    let mut summaries = match engine.engine {
+        Engine::Servo { .. } => crate::servo::compute_summaries(
+            args.clone(),
+            study.long_task_threshold(),
+            &study.metric_anchor(),
+            &study.annotation_filters,
+            study.max_plausible_event_duration(),
+            study.summary_options(),
+            &study.category_names(engine.key, crate::servo::default_category_names()),
+        )?,
         Engine::Chromium { .. } | Engine::ChromeDriver { .. } => {
-            crate::chromium::compute_summaries(args)?
+            crate::chromium::compute_summaries(
+                args,
+                study.long_task_threshold(),
+                study.frame_lifetime_only,
+                study.metric_basis,
+                study.max_plausible_event_duration(),
+                study.summary_options(),
+                &study.category_names(engine.key, crate::chromium::default_category_names()),
+            )?
+        }
+        Engine::WebKit { .. } => crate::webkit::compute_summaries(
+            args,
+            study.long_task_threshold(),
+            study.max_plausible_event_duration(),
+            study.summary_options(),
+        )?,
+        Engine::GeckoDriver { .. } | Engine::RemoteWebDriver { .. } => {
+            unreachable!("Guaranteed by the match above")
         }
     };
 
+    if let Some(min_event_coverage) = study.min_event_coverage {
+        summaries.drop_low_coverage_real_events(min_event_coverage);
+    }
+
     File::create(sample_dir.join("summaries.json"))?.write_all(summaries.json().as_bytes())?;
     File::create(sample_dir.join("summaries.txt"))?.write_all(summaries.text()?.as_bytes())?;
 
+    // If requested, also summarise each frame that matches the site’s URL (pattern) separately.
+    if site.all_frames {
+        let Engine::Servo { .. } = engine.engine else {
+            warn!("all_frames is only supported for Servo-type engines; ignoring");
+            return Ok(summaries.individuals.len());
+        };
+        let mut summaries_by_frame = crate::servo::compute_summaries_by_frame(
+            args,
+            study.long_task_threshold(),
+            &study.metric_anchor(),
+            &study.annotation_filters,
+            study.max_plausible_event_duration(),
+            study.summary_options(),
+            &study.category_names(engine.key, crate::servo::default_category_names()),
+        )?;
+        let mut text = String::default();
+        for (url, summaries) in summaries_by_frame.iter_mut() {
+            if let Some(min_event_coverage) = study.min_event_coverage {
+                summaries.drop_low_coverage_real_events(min_event_coverage);
+            }
+            text += &format!(">>>>>> Frame {url}\n{}\n", summaries.text()?);
+        }
+        File::create(sample_dir.join("frames.json"))?
+            .write_all(serde_json::to_string(&summaries_by_frame)?.as_bytes())?;
+        File::create(sample_dir.join("frames.txt"))?.write_all(text.as_bytes())?;
+    }
+
+    Ok(summaries.individuals.len())
+}
+
+/// Lists the inputs to the `servo`, `chromium`, or `webkit` analysis in a sample directory, that
+/// is, `manifest*.json` for Servo samples, `chrome*.json` for Chromium samples, or `webkit*.json`
+/// for WebKit samples.
+///
+/// For Chromium samples, this assumes that `chrome*.pftrace` have already been converted to JSON.
+pub fn sample_input_paths(sample_dir: &Path) -> eyre::Result<Vec<String>> {
+    let mut result = vec![];
+    for entry in std::fs::read_dir(sample_dir)? {
+        let path = entry?.path();
+        // Skip our own output files `summaries.*` and `frames.*`.
+        if path.file_stem() == Some(OsStr::new("summaries"))
+            || path.file_stem() == Some(OsStr::new("frames"))
+        {
+            continue;
+        }
+        // Filter to `manifest*.json`, `chrome*.json`, or `webkit*.json`, or any of those as
+        // `*.json.gz` (see [open_trace]).
+        let is_json_gz = path.extension() == Some(OsStr::new("gz"))
+            && path.file_stem().map(Path::new).and_then(Path::extension)
+                == Some(OsStr::new("json"));
+        if path.extension() == Some(OsStr::new("json")) || is_json_gz {
+            result.push(path.to_str().ok_or_eyre("Unsupported path")?.to_owned());
+        }
+    }
+
+    Ok(result)
+}
+
+/// Opens a trace file for reading, transparently decompressing it if it starts with the gzip magic
+/// bytes, so traces can be archived as `.gz` files.
+pub fn open_trace(path: impl AsRef<Path>) -> eyre::Result<Box<dyn Read>> {
+    let mut file = BufReader::new(File::open(path)?);
+    if file.fill_buf()?.starts_with(&[0x1f, 0x8b]) {
+        return Ok(Box::new(GzDecoder::new(file)));
+    }
+
+    Ok(Box::new(file))
+}
+
+/// Expands shell-style globs like `trace*.html` in the paths of `<url> <path ...>` arguments, for
+/// commands that take input paths directly. Arguments that are existing paths are kept as is.
+pub fn expand_input_globs(args: Vec<String>) -> eyre::Result<Vec<String>> {
+    let mut args = args.into_iter();
+    let mut result = args.next().into_iter().collect::<Vec<_>>();
+    for arg in args {
+        if std::fs::exists(&arg)? {
+            result.push(arg);
+            continue;
+        }
+        let paths = glob::glob(&arg)
+            .wrap_err_with(|| format!("Bad glob: {arg:?}"))?
+            .collect::<Result<Vec<_>, _>>()?;
+        if paths.is_empty() {
+            bail!("No such file, and no files match glob: {arg:?}");
+        }
+        for path in paths {
+            result.push(path.to_str().ok_or_eyre("Unsupported path")?.to_owned());
+        }
+    }
+
+    Ok(result)
+}
+
+#[test]
+fn test_expand_input_globs() -> eyre::Result<()> {
+    let dir = mktemp::Temp::new_dir()?;
+    let dir = dir.to_str().ok_or_eyre("Unsupported path")?;
+    for name in ["trace1.html", "trace2.html", "servo1.pftrace"] {
+        std::fs::write(format!("{dir}/{name}"), "")?;
+    }
+
+    assert_eq!(
+        expand_input_globs(vec![
+            "https://servo.org/".to_owned(),
+            format!("{dir}/servo1.pftrace"),
+            format!("{dir}/trace*.html"),
+        ])?,
+        [
+            "https://servo.org/".to_owned(),
+            format!("{dir}/servo1.pftrace"),
+            format!("{dir}/trace1.html"),
+            format!("{dir}/trace2.html"),
+        ],
+    );
+    assert!(expand_input_globs(vec![
+        "https://servo.org/".to_owned(),
+        format!("{dir}/chrome*.json"),
+    ])
+    .is_err());
+
+    Ok(())
+}
+
+/// Removes any paths that refer to the same file as an earlier path, or to a file with the same
+/// (decompressed) contents, such as a leftover copy. Otherwise these would be analysed more than
+/// once. Paths we can’t read are kept, so that analysing them reports the failure as usual.
+pub fn dedupe_input_paths(paths: &[String]) -> eyre::Result<Vec<String>> {
+    let mut paths_by_canonical_path = BTreeMap::default();
+    let mut paths_by_contents = BTreeMap::default();
+    let mut result = vec![];
+    for path in paths {
+        let (canonical_path, contents_key) = match dedupe_keys(path) {
+            Ok(key) => key,
+            Err(error) => {
+                warn!(?path, ?error, "Failed to read path; not deduplicating it");
+                result.push(path.clone());
+                continue;
+            }
+        };
+        if let Some(previous) = paths_by_canonical_path.get(&canonical_path) {
+            warn!(?path, ?previous, "Skipping duplicate path to the same file");
+            continue;
+        }
+        if let Some(previous) = paths_by_contents.get(&contents_key) {
+            warn!(
+                ?path,
+                ?previous,
+                "Skipping file with the same contents as another file"
+            );
+            continue;
+        }
+        paths_by_canonical_path.insert(canonical_path, path);
+        paths_by_contents.insert(contents_key, path);
+        result.push(path.clone());
+    }
+
+    Ok(result)
+}
+
+/// Returns the canonical path of the given path, and the length and hash of its decompressed
+/// contents, for [dedupe_input_paths].
+fn dedupe_keys(path: &str) -> eyre::Result<(PathBuf, (u64, u64))> {
+    let canonical_path = Path::new(path).canonicalize()?;
+    let mut hasher = Fnv1aHasher::default();
+    let len = std::io::copy(&mut open_trace(&canonical_path)?, &mut hasher)?;
+
+    Ok((canonical_path, (len, hasher.finish())))
+}
+
+#[test]
+fn test_dedupe_input_paths() -> eyre::Result<()> {
+    let dir = mktemp::Temp::new_dir()?;
+    let original = dir.join("chrome1.json");
+    let copy = dir.join("chrome2.json");
+    let different = dir.join("chrome3.json");
+    let compressed_copy = dir.join("chrome4.json.gz");
+    std::fs::write(&original, "{}")?;
+    std::fs::write(&copy, "{}")?;
+    std::fs::write(&different, "[]")?;
+    {
+        use flate2::{write::GzEncoder, Compression};
+        let mut encoder = GzEncoder::new(File::create(&compressed_copy)?, Compression::default());
+        encoder.write_all(b"{}")?;
+        encoder.finish()?;
+    }
+    let original = original.to_str().ok_or_eyre("Unsupported path")?.to_owned();
+    let copy = copy.to_str().ok_or_eyre("Unsupported path")?.to_owned();
+    let compressed_copy = compressed_copy
+        .to_str()
+        .ok_or_eyre("Unsupported path")?
+        .to_owned();
+    let different = different
+        .to_str()
+        .ok_or_eyre("Unsupported path")?
+        .to_owned();
+    let same_file = format!(
+        "{}/./chrome1.json",
+        dir.to_str().ok_or_eyre("Unsupported path")?
+    );
+
+    assert_eq!(
+        dedupe_input_paths(&[
+            original.clone(),
+            same_file,
+            copy,
+            different.clone(),
+            compressed_copy
+        ])?,
+        [original.clone(), different],
+    );
+
+    // Paths we can’t read, like missing files or truncated gzip files, are kept as is.
+    let missing = dir.join("chrome5.json");
+    let missing = missing.to_str().ok_or_eyre("Unsupported path")?.to_owned();
+    let truncated = dir.join("chrome6.json.gz");
+    std::fs::write(&truncated, [0x1f, 0x8b, 0x08])?;
+    let truncated = truncated
+        .to_str()
+        .ok_or_eyre("Unsupported path")?
+        .to_owned();
+    assert_eq!(
+        dedupe_input_paths(&[original.clone(), missing.clone(), truncated.clone()])?,
+        [original, missing, truncated],
+    );
+
     Ok(())
 }
 
+/// What a JSON trace was converted from, stored next to the JSON trace as `<json path>.traceconv`,
+/// so we can tell when it needs converting again.
+#[derive(Debug, Deserialize, PartialEq, Serialize)]
+struct TraceconvManifest {
+    /// FNV-1a hash of the `.pftrace` contents, in hex (see [hash_file_contents]).
+    pftrace_hash: String,
+    traceconv_command: Vec<String>,
+}
+
 #[tracing::instrument(level = "error", err, skip(study))]
 fn convert_pftrace_to_json(study: &Study, pftrace_path: &str, json_path: &str) -> eyre::Result<()> {
+    // Skip conversion if the JSON trace was converted from the same `.pftrace` contents with the
+    // same `traceconv_command`, even if the sample directory has moved since then.
+    let manifest_path = format!("{json_path}.traceconv");
+    let manifest = TraceconvManifest {
+        pftrace_hash: format!("{:016x}", hash_file_contents(pftrace_path)?),
+        traceconv_command: study.traceconv_command.clone(),
+    };
+    let previous_manifest = std::fs::read_to_string(&manifest_path)
+        .ok()
+        .and_then(|json| serde_json::from_str::<TraceconvManifest>(&json).ok());
+    if std::fs::exists(json_path)? && previous_manifest.as_ref() == Some(&manifest) {
+        info!("Skipping conversion, since JSON trace is up to date");
+        return Ok(());
+    }
+    // Remove the old manifest first, so a failed conversion can never look up to date.
+    if previous_manifest.is_some() {
+        std::fs::remove_file(&manifest_path)?;
+    }
+
     let (program, args) = study
         .traceconv_command
         .split_first()
@@ -127,6 +516,97 @@ fn convert_pftrace_to_json(study: &Study, pftrace_path: &str, json_path: &str) -
     if !exit_status.success() {
         bail!("Process failed: {exit_status}");
     }
+    std::fs::write(manifest_path, serde_json::to_string(&manifest)?)?;
+
+    Ok(())
+}
+
+/// FNV-1a hasher, which unlike `DefaultHasher`, is stable across Rust releases, so its hashes can
+/// be stored and compared with later runs. Bytes can also be written to it with [Write], such as
+/// with [std::io::copy], to hash a file without reading it into memory.
+pub struct Fnv1aHasher(u64);
+
+impl Default for Fnv1aHasher {
+    fn default() -> Self {
+        Self(0xcbf29ce484222325)
+    }
+}
+
+impl Hasher for Fnv1aHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100000001b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+impl Write for Fnv1aHasher {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Hasher::write(self, buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Hashes the contents of a file with [Fnv1aHasher], so the hash can be stored.
+fn hash_file_contents(path: &str) -> eyre::Result<u64> {
+    let mut hasher = Fnv1aHasher::default();
+    std::io::copy(&mut File::open(path)?, &mut hasher)?;
+
+    Ok(hasher.finish())
+}
+
+#[test]
+fn test_hash_file_contents() -> eyre::Result<()> {
+    let dir = mktemp::Temp::new_dir()?;
+    let empty = dir.join("empty.pftrace");
+    let a = dir.join("a.pftrace");
+    std::fs::write(&empty, "")?;
+    std::fs::write(&a, "a")?;
+
+    // Known FNV-1a 64-bit test vectors.
+    assert_eq!(
+        hash_file_contents(empty.to_str().ok_or_eyre("Unsupported path")?)?,
+        0xcbf29ce484222325
+    );
+    assert_eq!(
+        hash_file_contents(a.to_str().ok_or_eyre("Unsupported path")?)?,
+        0xaf63dc4c8601ec8c
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_open_trace() -> eyre::Result<()> {
+    use flate2::{write::GzEncoder, Compression};
+
+    let dir = mktemp::Temp::new_dir()?;
+    let plain_path = dir.join("chrome1.json");
+    std::fs::write(&plain_path, "{}")?;
+    let gz_path = dir.join("chrome2.json.gz");
+    let mut encoder = GzEncoder::new(File::create(&gz_path)?, Compression::default());
+    encoder.write_all(b"{}")?;
+    encoder.finish()?;
+
+    for path in [&plain_path, &gz_path] {
+        let mut contents = String::default();
+        open_trace(path)?.read_to_string(&mut contents)?;
+        assert_eq!(contents, "{}");
+    }
+    let mut input_paths = sample_input_paths(&dir)?;
+    input_paths.sort();
+    assert_eq!(
+        input_paths,
+        [plain_path.to_str().unwrap(), gz_path.to_str().unwrap()]
+    );
 
     Ok(())
 }
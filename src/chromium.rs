@@ -1,30 +1,65 @@
 use std::{
     collections::{BTreeMap, BTreeSet},
-    fs::File,
     io::Read,
     time::Duration,
 };
 
 use jane_eyre::eyre::{self, bail, OptionExt};
+use perfetto_protos::debug_annotation::DebugAnnotation;
 use tracing::{debug, error_span, info, trace, warn};
 
 use crate::{
+    analyse::{dedupe_input_paths, expand_input_globs, open_trace, record_failed_path},
     json::{JsonTrace, TraceEvent},
     summary::{
-        Analysis, Event, EventKind, Individual, JsonRawSeries, JsonSummaries, SYNTHETIC_NAMES,
+        drop_implausible_events, Analysis, CategoryNames, Event, EventKind, Individual,
+        JsonRawSeries, JsonSummaries, MetricBasis, SummaryOptions, DEFAULT_LONG_TASK_THRESHOLD,
+        SUMMARIES_SCHEMA_VERSION, SYNTHETIC_NAMES,
     },
+    url_pattern::resolve_url_pattern,
 };
 
-static RENDERER_NAMES: &'static str = "ParseHTML EvaluateScript FunctionCall TimerFire UpdateLayoutTree Layout PrePaint Paint Layerize"; // TODO: does not include rasterisation and compositing
+static RENDERER_NAMES: &'static str = "ParseHTML EvaluateScript FunctionCall TimerFire UpdateLayoutTree Layout PrePaint Paint Layerize RasterTask Commit ActivateLayerTree";
 static PARSE_NAMES: &'static str = "ParseHTML";
 static SCRIPT_NAMES: &'static str = "EvaluateScript FunctionCall TimerFire";
 static LAYOUT_NAMES: &'static str = "UpdateLayoutTree Layout PrePaint Paint";
-static RASTERISE_NAMES: &'static str = "Layerize"; // TODO: does not include rasterisation and compositing
+/// `Layerize` runs on the main thread, `RasterTask` on the raster worker threads (one per tile),
+/// and `Commit` and `ActivateLayerTree` on the compositor thread, where the layer tree is handed
+/// over from the main thread then made ready to draw. This is the closest equivalent of Servo’s
+/// `Compositing`, but rasterisation in the GPU process (such as with OOP-R) is not included.
+static RASTERISE_NAMES: &'static str = "Layerize RasterTask Commit ActivateLayerTree";
+/// Events that run off the main thread, so they have no frame, and are instead taken from the
+/// renderer process of the page.
+static NO_FRAME_NAMES: &'static str = "RasterTask Commit ActivateLayerTree";
+/// Events that occur once for each navigation in a frame, such as the initial about:blank, so
+/// only the occurrence for our navigation is relevant.
+static NAVIGATION_EVENT_NAMES: &'static str = "navigationStart responseEnd domLoading domInteractive domContentLoadedEventStart domContentLoadedEventEnd domComplete";
 static METRICS: &'static [(&'static str, &'static str)] =
     &[("FP", "firstPaint"), ("FCP", "firstContentfulPaint")];
 
+/// Returns the built-in [CategoryNames], which a study can override.
+pub fn default_category_names() -> CategoryNames {
+    CategoryNames {
+        renderer: RENDERER_NAMES.to_owned(),
+        parse: PARSE_NAMES.to_owned(),
+        script: SCRIPT_NAMES.to_owned(),
+        layout: LAYOUT_NAMES.to_owned(),
+        rasterise: RASTERISE_NAMES.to_owned(),
+        // Chromium events are tied to the page by frame, not by URL.
+        no_url: NO_FRAME_NAMES.to_owned(),
+    }
+}
+
 pub fn main(args: Vec<String>) -> eyre::Result<()> {
-    let summaries = compute_summaries(args)?;
+    let summaries = compute_summaries(
+        expand_input_globs(args)?,
+        DEFAULT_LONG_TASK_THRESHOLD,
+        false,
+        MetricBasis::Native,
+        None,
+        SummaryOptions::default(),
+        &default_category_names(),
+    )?;
 
     println!("{}", summaries.json());
     println!();
@@ -34,9 +69,23 @@ pub fn main(args: Vec<String>) -> eyre::Result<()> {
 }
 
 #[tracing::instrument(level = "error")]
-pub fn compute_summaries(args: Vec<String>) -> Result<JsonSummaries, eyre::Error> {
+pub fn compute_summaries(
+    args: Vec<String>,
+    long_task_threshold: Duration,
+    frame_lifetime_only: bool,
+    metric_basis: MetricBasis,
+    max_plausible_event_duration: Option<Duration>,
+    summary_options: SummaryOptions,
+    category_names: &CategoryNames,
+) -> Result<JsonSummaries, eyre::Error> {
     info!("Computing summaries");
-    let individuals = analyse_individuals(&args)?;
+    let individuals = analyse_individuals(
+        &args,
+        frame_lifetime_only,
+        metric_basis,
+        max_plausible_event_duration,
+        category_names,
+    )?;
     let durations_keys = individuals
         .iter()
         .flat_map(|s| s.durations.keys())
@@ -56,12 +105,16 @@ pub fn compute_summaries(args: Vec<String>) -> Result<JsonSummaries, eyre::Error
         .collect::<Vec<_>>();
 
     let analysis = Analysis { individuals };
+    let real_events_self_time =
+        analysis.self_time_summaries(durations_keys.iter().map(|name| &**name), summary_options);
 
     let mut real_events = vec![];
     let mut synthetic_and_interpreted_events = vec![];
 
     for name in durations_keys {
-        if let Ok(summary) = analysis.summary(|s| s.durations.get(&name).map(|d| d.as_secs_f64())) {
+        let getter = |s: &IndividualAnalysis| s.durations.get(&name).map(|d| d.as_secs_f64());
+        let summary = analysis.summary_with_options(summary_options, getter);
+        if let Ok(summary) = summary {
             real_events.push(summary.to_json(&name));
         };
     }
@@ -72,7 +125,7 @@ pub fn compute_summaries(args: Vec<String>) -> Result<JsonSummaries, eyre::Error
             kind: EventKind::SyntheticOrInterpreted,
             xs: vec![],
         };
-        if let Ok(summary) = analysis.summary(|s| {
+        let getter = |s: &IndividualAnalysis| {
             let events = match s.synthetic_events() {
                 Ok(events) => events,
                 Err(error) => {
@@ -87,94 +140,191 @@ pub fn compute_summaries(args: Vec<String>) -> Result<JsonSummaries, eyre::Error
                 .sum::<f64>();
             series.xs.push(result);
             Some(result)
-        }) {
+        };
+        // Trim outliers from the summary only, so the raw series still has every value.
+        let summary = analysis.summary_with_options(summary_options, getter);
+        if let Ok(summary) = summary {
             synthetic_and_interpreted_events.push(summary.to_json(synthetic_name));
         }
         raw_series.push(series);
     }
 
-    Ok(JsonSummaries {
+    let (long_task_summaries, long_task_counts, long_task_series) =
+        analysis.long_task_summaries(long_task_threshold, summary_options)?;
+    synthetic_and_interpreted_events.extend(long_task_summaries);
+    raw_series.extend(long_task_series);
+    let (fractions, fraction_series) = analysis.utilization_summaries(summary_options);
+    raw_series.extend(fraction_series);
+
+    let mut summaries = JsonSummaries {
+        schema_version: SUMMARIES_SCHEMA_VERSION,
         real_events,
+        real_events_self_time,
         synthetic_and_interpreted_events,
+        counters: long_task_counts,
+        fractions,
         raw_series,
-    })
+        individuals: analysis.individuals_to_json(),
+        notes: vec![],
+    };
+    summaries.check_synthetic_phase_sum();
+
+    Ok(summaries)
 }
 
-pub fn analyse_individuals(args: &[String]) -> eyre::Result<Vec<IndividualAnalysis>> {
+pub fn analyse_individuals(
+    args: &[String],
+    frame_lifetime_only: bool,
+    metric_basis: MetricBasis,
+    max_plausible_event_duration: Option<Duration>,
+    category_names: &CategoryNames,
+) -> eyre::Result<Vec<IndividualAnalysis>> {
     let url = args.iter().nth(0).unwrap().to_owned();
-    let paths = args.into_iter().skip(1).collect::<Vec<_>>();
+    let paths = dedupe_input_paths(&args[1..])?;
 
     let mut individuals = vec![];
     for (path, result) in paths
         .iter()
-        .map(|path| (path.to_owned(), analyse_individual(&url, path)))
+        .map(|path| {
+            (
+                path.to_owned(),
+                analyse_individual(
+                    &url,
+                    path,
+                    frame_lifetime_only,
+                    metric_basis,
+                    max_plausible_event_duration,
+                    category_names,
+                ),
+            )
+        })
         .collect::<Vec<_>>()
     {
         let span = error_span!("analyse", path = path);
         let _enter = span.enter();
         match result {
             Ok(result) => individuals.push(result),
-            Err(error) => warn!("Failed to analyse file: {error}"),
+            Err(error) => {
+                warn!("Failed to analyse file: {error}");
+                record_failed_path(&path);
+            }
         }
     }
 
     Ok(individuals)
 }
 
-#[tracing::instrument(level = "error", skip(url))]
-fn analyse_individual(url: &str, path: &str) -> eyre::Result<IndividualAnalysis> {
+#[tracing::instrument(level = "error", skip(url, category_names))]
+fn analyse_individual(
+    url: &str,
+    path: &str,
+    frame_lifetime_only: bool,
+    metric_basis: MetricBasis,
+    max_plausible_event_duration: Option<Duration>,
+    category_names: &CategoryNames,
+) -> eyre::Result<IndividualAnalysis> {
     info!("Analysing individual");
 
     let mut json = String::default();
-    File::open(path)?.read_to_string(&mut json)?;
-    let mut all_events = serde_json::from_str::<JsonTrace>(&json)?.traceEvents;
+    open_trace(path)?.read_to_string(&mut json)?;
+    let mut all_events = merge_async_events(serde_json::from_str::<JsonTrace>(&json)?.traceEvents);
     all_events.sort_by(|p, q| p.ts.cmp(&q.ts).then(p.dur.cmp(&q.dur)));
 
-    let (navigation_id, frame) = all_events
+    // Resolve any pattern to the first matching URL, or leave it as is and fail below.
+    let url = resolve_url_pattern(
+        url,
+        all_events.iter().filter_map(|e| e.document_loader_url()),
+    )
+    .unwrap_or(url);
+    let url_event = all_events
         .iter()
-        .find(|e| e.document_loader_url() == Some(&url))
-        .ok_or_eyre("Failed to find event with the given documentLoaderURL")
-        .map(|e| e.navigation_id().zip(e.frame()))?
+        .find(|e| e.document_loader_url() == Some(url))
+        .ok_or_eyre("Failed to find event with the given documentLoaderURL")?;
+    let (navigation_id, frame) = url_event
+        .navigation_id()
+        .zip(url_event.frame())
         .ok_or_eyre("Event with the given documentLoaderURL has no navigationId and/or frame")?;
+    let pid = url_event.pid;
     trace!("navigation_id = {navigation_id}");
     trace!("frame = {frame}");
+    trace!("pid = {pid}");
 
+    // Events in `no_url` have no frame, so take them from the renderer process of the page.
+    let is_no_frame_event_name = |name: &str| {
+        category_names
+            .no_url
+            .split(" ")
+            .find(|&n| n == name)
+            .is_some()
+    };
     let relevant_events = all_events
         .iter()
-        .filter(|e| e.navigation_id() == Some(navigation_id) || e.frame() == Some(frame))
-        .collect::<Vec<_>>();
-
-    let indices_by_event_name = relevant_events
-        .iter()
-        .map(|e| {
-            (
-                &*e.name,
-                relevant_events
-                    .iter()
-                    .enumerate()
-                    .filter(|(_, e2)| e2.name == e.name)
-                    .map(|(i, _)| i)
-                    .collect(),
-            )
+        .filter(|e| {
+            e.navigation_id() == Some(navigation_id)
+                || e.frame() == Some(frame)
+                || (e.frame().is_none() && e.pid == pid && is_no_frame_event_name(&e.name))
         })
-        .collect::<BTreeMap<&str, Vec<usize>>>();
+        .collect::<Vec<_>>();
 
-    // Remove first occurrences of events with certain names.
-    let is_duplicated_event_name = |name: &str| {
-        "navigationStart responseEnd domLoading domInteractive domContentLoadedEventStart domContentLoadedEventEnd domComplete"
+    // Keep only the first occurrence of each navigation event for our navigation, since the
+    // frame may have other navigations before or after ours. Older traces have no navigationId
+    // for these events, so for those, fall back to dropping the first occurrence, which assumes
+    // there was exactly one other navigation (the initial about:blank).
+    let is_navigation_event_name = |name: &str| {
+        NAVIGATION_EVENT_NAMES
             .split(" ")
-            .find(|&d| d == name)
+            .find(|&n| n == name)
             .is_some()
     };
+    let names_with_navigation_id = relevant_events
+        .iter()
+        .filter(|e| e.navigation_id().is_some())
+        .map(|e| &*e.name)
+        .collect::<BTreeSet<_>>();
+    let mut first_index_by_name = BTreeMap::default();
+    for (i, e) in relevant_events.iter().enumerate() {
+        first_index_by_name.entry(&*e.name).or_insert(i);
+    }
+    let mut kept_navigation_event_names = BTreeSet::default();
     let relevant_events = relevant_events
         .iter()
         .enumerate()
         .filter(|(i, e)| {
-            !is_duplicated_event_name(&e.name) || *i != indices_by_event_name[&*e.name][0]
+            if !is_navigation_event_name(&e.name) {
+                true
+            } else if names_with_navigation_id.contains(&*e.name) {
+                e.navigation_id() == Some(navigation_id)
+                    && kept_navigation_event_names.insert(&*e.name)
+            } else {
+                *i != first_index_by_name[&*e.name]
+            }
         })
         .map(|(_, e)| e)
         .collect::<Vec<_>>();
 
+    // If requested, drop spans outside the frame’s lifetime, such as background work after the
+    // page has loaded. Instantaneous events like `markAsMainFrame` and paint metrics are kept.
+    let relevant_events = if frame_lifetime_only {
+        let start_ts = relevant_events
+            .iter()
+            .find(|e| e.name == "navigationStart")
+            .ok_or_eyre("No navigationStart event in frame")?
+            .ts;
+        let end_ts = relevant_events
+            .iter()
+            .find(|e| e.name == "loadEventEnd")
+            .or_else(|| relevant_events.iter().find(|e| e.name == "domComplete"))
+            .ok_or_eyre("No loadEventEnd or domComplete event in frame")?
+            .ts;
+        debug!(start_ts, end_ts, "Bounding analysis to frame lifetime");
+        relevant_events
+            .into_iter()
+            .filter(|e| e.dur.is_none() || (start_ts..=end_ts).contains(&e.ts))
+            .collect()
+    } else {
+        relevant_events
+    };
+
     let mut result = vec![];
     let ts_start = relevant_events[0].ts;
     for &event in relevant_events {
@@ -188,9 +338,15 @@ fn analyse_individual(url: &str, path: &str) -> eyre::Result<IndividualAnalysis>
         result.push(event.to_owned());
     }
 
+    let result = drop_implausible_events(
+        result,
+        |e| e.dur.map(|dur| Duration::from_micros(dur as u64)),
+        max_plausible_event_duration,
+        path,
+    );
+
     let mut durations = BTreeMap::default();
-    let interesting_event_names = format!("{RENDERER_NAMES}");
-    for name in interesting_event_names.split(" ") {
+    for name in category_names.renderer.split(" ") {
         let duration = IndividualAnalysis::sum_duration(&result, name)?;
         debug!("{name}: {:?}", duration);
         durations.insert(name.to_owned(), duration);
@@ -200,15 +356,282 @@ fn analyse_individual(url: &str, path: &str) -> eyre::Result<IndividualAnalysis>
         path: path.to_owned(),
         relevant_events: result,
         durations,
+        metric_basis,
+        category_names: category_names.clone(),
     };
 
     Ok(result)
 }
 
+/// Merges each pair of nestable async events (`b` and `e` with the same name and id) into one
+/// complete event (`X`) with a duration, like the events we get for synchronous work. Async
+/// instant events (`n`) are kept as is, and unpaired events are dropped.
+fn merge_async_events(mut events: Vec<TraceEvent>) -> Vec<TraceEvent> {
+    // Pair the events in timestamp order, in case the trace is not sorted.
+    events.sort_by_key(|e| e.ts);
+
+    // Async events with the same name and id can nest, so we use a stack for each.
+    let mut pending: BTreeMap<(String, Option<String>), Vec<TraceEvent>> = BTreeMap::default();
+    let mut result = vec![];
+    for event in events {
+        match &*event.ph {
+            "b" => {
+                let key = (
+                    event.name.clone(),
+                    event.id.as_ref().map(|id| id.to_string()),
+                );
+                pending.entry(key).or_default().push(event);
+            }
+            "e" => {
+                let key = (
+                    event.name.clone(),
+                    event.id.as_ref().map(|id| id.to_string()),
+                );
+                let Some(mut begin) = pending.get_mut(&key).and_then(|stack| stack.pop()) else {
+                    debug!(event.name, id = ?event.id, "Dropping async end event with no begin");
+                    continue;
+                };
+                begin.ph = "X".to_owned();
+                begin.dur = Some(event.ts - begin.ts);
+                begin.args.extend(event.args);
+                result.push(begin);
+            }
+            _ => result.push(event),
+        }
+    }
+    for begin in pending.into_values().flatten() {
+        debug!(begin.name, id = ?begin.id, "Dropping async begin event with no end");
+    }
+
+    result
+}
+
+#[test]
+fn test_merge_async_events() {
+    let event = |ts: usize, ph: &str, name: &str, id: Option<&str>| TraceEvent {
+        ts,
+        ph: ph.to_owned(),
+        name: name.to_owned(),
+        id: id.map(|id| id.into()),
+        ..Default::default()
+    };
+    let events = merge_async_events(vec![
+        event(1, "b", "ResourceLoad", Some("0x1")),
+        event(2, "b", "ResourceLoad", Some("0x2")),
+        event(3, "X", "Layout", None),
+        event(4, "b", "ResourceLoad", Some("0x1")),
+        event(5, "e", "ResourceLoad", Some("0x1")),
+        event(6, "n", "ResourceLoad", Some("0x2")),
+        event(7, "e", "ResourceLoad", Some("0x1")),
+        event(8, "e", "ResourceLoad", Some("0x3")),
+    ]);
+    let summary = events
+        .iter()
+        .map(|e| (e.ts, &*e.ph, e.dur))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        [
+            (3, "X", None),
+            (4, "X", Some(1)),
+            (6, "n", None),
+            (1, "X", Some(6)),
+        ]
+    );
+}
+
+#[test]
+fn test_analyse_individual_with_multiple_navigations() -> eyre::Result<()> {
+    let navigation_event = |ts: usize, name: &str, navigation_id: &str, url: Option<&str>| {
+        let mut data = serde_json::Map::default();
+        data.insert("navigationId".to_owned(), navigation_id.into());
+        if let Some(url) = url {
+            data.insert("documentLoaderURL".to_owned(), url.into());
+        }
+        TraceEvent {
+            ts,
+            ph: "R".to_owned(),
+            name: name.to_owned(),
+            args: [
+                ("frame".to_owned(), "F".into()),
+                ("data".to_owned(), data.into()),
+            ]
+            .into_iter()
+            .collect(),
+            ..Default::default()
+        }
+    };
+    let layout_event = TraceEvent {
+        ts: 350,
+        dur: Some(10),
+        ph: "X".to_owned(),
+        name: "Layout".to_owned(),
+        args: [("frame".to_owned(), "F".into())].into_iter().collect(),
+        ..Default::default()
+    };
+    // The frame navigates to about:blank, then to our URL, then to another URL.
+    let trace = JsonTrace {
+        traceEvents: vec![
+            navigation_event(100, "navigationStart", "A", Some("")),
+            navigation_event(150, "domInteractive", "A", None),
+            navigation_event(200, "navigationStart", "B", Some("https://example.com/")),
+            navigation_event(300, "domInteractive", "B", None),
+            layout_event,
+            navigation_event(
+                400,
+                "navigationStart",
+                "C",
+                Some("https://example.com/next"),
+            ),
+            navigation_event(500, "domInteractive", "C", None),
+        ],
+    };
+    let dir = mktemp::Temp::new_dir()?;
+    let path = dir.join("chrome1.json");
+    std::fs::write(&path, serde_json::to_string(&trace)?)?;
+
+    let individual = analyse_individual(
+        "https://example.com/",
+        path.to_str().ok_or_eyre("Unsupported path")?,
+        false,
+        MetricBasis::Native,
+        None,
+        &default_category_names(),
+    )?;
+    let events = individual
+        .relevant_events
+        .iter()
+        .map(|e| (e.ts, &*e.name))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            (200, "navigationStart"),
+            (300, "domInteractive"),
+            (350, "Layout")
+        ]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_analyse_individual_with_raster_events() -> eyre::Result<()> {
+    let event = |ts: usize, dur: Option<usize>, name: &str, pid: usize, frame: Option<&str>| {
+        let mut args = BTreeMap::default();
+        if let Some(frame) = frame {
+            args.insert("frame".to_owned(), frame.into());
+        }
+        TraceEvent {
+            ts,
+            dur,
+            ph: if dur.is_some() { "X" } else { "R" }.to_owned(),
+            name: name.to_owned(),
+            pid,
+            args,
+            ..Default::default()
+        }
+    };
+    let mut navigation_start = event(200, None, "navigationStart", 1, Some("F"));
+    navigation_start.args.insert(
+        "data".to_owned(),
+        serde_json::json!({ "documentLoaderURL": "https://example.com/", "navigationId": "B" }),
+    );
+    let trace = JsonTrace {
+        traceEvents: vec![
+            navigation_start,
+            event(300, Some(10), "Layout", 1, Some("F")),
+            // Raster work has no frame, but it overlaps the layout, so it extends the rendering.
+            event(305, Some(20), "RasterTask", 1, None),
+            // This raster work is in another renderer process, so it’s for another page.
+            event(400, Some(5), "RasterTask", 2, None),
+        ],
+    };
+    let dir = mktemp::Temp::new_dir()?;
+    let path = dir.join("chrome1.json");
+    std::fs::write(&path, serde_json::to_string(&trace)?)?;
+
+    let individual = analyse_individual(
+        "https://example.com/",
+        path.to_str().ok_or_eyre("Unsupported path")?,
+        false,
+        MetricBasis::Native,
+        None,
+        &default_category_names(),
+    )?;
+    let events = individual
+        .relevant_events
+        .iter()
+        .map(|e| (e.ts, &*e.name))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            (200, "navigationStart"),
+            (300, "Layout"),
+            (305, "RasterTask")
+        ]
+    );
+    let renderer_events = individual
+        .synthetic_events()?
+        .into_iter()
+        .filter(|e| e.name == "Renderer")
+        .map(|e| (e.start, e.duration))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        renderer_events,
+        [(Duration::from_micros(100), Some(Duration::from_micros(25)))]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn test_unique_instantaneous_event_from_first() -> eyre::Result<()> {
+    let event = |ts: usize, name: &str| TraceEvent {
+        ts,
+        ph: "R".to_owned(),
+        name: name.to_owned(),
+        ..Default::default()
+    };
+    let events = [
+        event(100, "markAsMainFrame"),
+        event(150, "firstPaint"),
+        event(200, "markAsMainFrame"),
+    ];
+    let result = |stop_name| {
+        IndividualAnalysis::unique_instantaneous_event_from_first(
+            &events,
+            "FP",
+            "markAsMainFrame",
+            stop_name,
+        )
+    };
+
+    let fp = result("firstPaint")?.ok_or_eyre("No FP")?;
+    assert_eq!(fp.start, Duration::from_micros(100));
+    assert_eq!(fp.duration, Some(Duration::from_micros(50)));
+    // Pages that never paint have no paint metrics, but that should not fail the analysis.
+    assert!(result("firstContentfulPaint")?.is_none());
+    assert!(IndividualAnalysis::unique_instantaneous_event_from_first(
+        &events[1..2],
+        "FP",
+        "navigationStart",
+        "firstPaint",
+    )?
+    .is_none());
+    // Ambiguous metrics are still an error.
+    assert!(result("markAsMainFrame").is_err());
+
+    Ok(())
+}
+
 pub struct IndividualAnalysis {
     path: String,
     relevant_events: Vec<TraceEvent>,
     durations: BTreeMap<String, Duration>,
+    metric_basis: MetricBasis,
+    category_names: CategoryNames,
 }
 
 impl Individual for IndividualAnalysis {
@@ -233,11 +656,16 @@ impl Individual for IndividualAnalysis {
                     Some(dur) => Some(Duration::from_micros(dur.try_into()?)),
                     None => None,
                 };
+                // Label the event with its thread, so that events on other threads are never
+                // treated as nested inside it (see [Event::build_tree]).
+                let mut track = DebugAnnotation::new();
+                track.set_name("track".to_owned());
+                track.set_string_value(format!("{}/{}", e.pid, e.tid));
                 Ok(Event {
                     name: e.name.clone(),
                     start: Duration::from_micros(start.try_into()?),
                     duration,
-                    metadata: BTreeMap::default(),
+                    metadata: BTreeMap::from([("track".to_owned(), track)]),
                 })
             })
             .collect::<eyre::Result<Vec<_>>>()?;
@@ -257,37 +685,43 @@ impl Individual for IndividualAnalysis {
 
         // Add some synthetic events with our interpretations.
         let renderer_events = real_events.iter().filter(|e| {
-            RENDERER_NAMES
+            self.category_names
+                .renderer
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
         let parse_events = real_events.iter().filter(|e| {
-            PARSE_NAMES
+            self.category_names
+                .parse
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
         let script_events = real_events.iter().filter(|e| {
-            SCRIPT_NAMES
+            self.category_names
+                .script
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
         let layout_events = real_events.iter().filter(|e| {
-            LAYOUT_NAMES
+            self.category_names
+                .layout
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
         let rasterise_events = real_events.iter().filter(|e| {
-            RASTERISE_NAMES
+            self.category_names
+                .rasterise
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
+        let renderer_events = Event::generate_merged_events(renderer_events, "Renderer")?;
         let mut result = [
-            Event::generate_merged_events(renderer_events, "Renderer")?,
+            renderer_events,
             Event::generate_merged_events(parse_events, "Parse")?,
             Event::generate_merged_events(script_events, "Script")?,
             Event::generate_merged_events(layout_events, "Layout")?,
@@ -298,15 +732,21 @@ impl Individual for IndividualAnalysis {
         .collect::<Vec<_>>();
         // “loading” category events like `firstPaint` and `firstContentfulPaint` are timed from `markAsMainFrame`.
         // <https://codereview.chromium.org/2712773002>
+        // Only the `navigationStart` for our navigation was kept above.
+        let start_name = match self.metric_basis {
+            MetricBasis::Native => "markAsMainFrame",
+            MetricBasis::Navigation => "navigationStart",
+        };
         for (result_name, stop_name) in METRICS {
-            let mut event = IndividualAnalysis::unique_instantaneous_event_from_first(
+            if let Some(mut event) = IndividualAnalysis::unique_instantaneous_event_from_first(
                 &self.relevant_events,
                 result_name,
-                "markAsMainFrame",
+                start_name,
                 stop_name,
-            )?;
-            event.start -= start;
-            result.push(event);
+            )? {
+                event.start -= start;
+                result.push(event);
+            }
         }
 
         Ok(result)
@@ -322,28 +762,36 @@ impl IndividualAnalysis {
         Ok(Duration::from_micros(result.try_into()?))
     }
 
+    /// Returns the time from the first `start_name` event to the only `stop_name` event, or None
+    /// if either is missing, such as for pages that never paint.
     fn unique_instantaneous_event_from_first(
         relevant_events: &[TraceEvent],
         result_name: &str,
         start_name: &str,
         stop_name: &str,
-    ) -> eyre::Result<Event> {
+    ) -> eyre::Result<Option<Event>> {
         let [start_ts, ..] = Self::ts_by_name(relevant_events, start_name)[..] else {
-            bail!("Expected at least one event with name {start_name}");
+            warn!("No {start_name} event; skipping {result_name}");
+            return Ok(None);
         };
-        let [stop_ts] = Self::ts_by_name(relevant_events, stop_name)[..] else {
-            bail!("Expected exactly one event with name {stop_name}");
+        let stop_ts = match Self::ts_by_name(relevant_events, stop_name)[..] {
+            [] => {
+                warn!("No {stop_name} event; skipping {result_name}");
+                return Ok(None);
+            }
+            [stop_ts] => stop_ts,
+            _ => bail!("Expected exactly one event with name {stop_name}"),
         };
 
         let start = Duration::from_micros(start_ts.try_into()?);
         let duration = Duration::from_micros(u64::try_from(stop_ts)? - u64::try_from(start_ts)?);
 
-        Ok(Event {
+        Ok(Some(Event {
             name: result_name.to_owned(),
             start,
             duration: Some(duration),
             metadata: BTreeMap::default(),
-        })
+        }))
     }
 
     fn dur_by_name(relevant_events: &[TraceEvent], name: &str) -> Vec<usize> {
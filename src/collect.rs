@@ -1,117 +1,666 @@
 use core::str;
 use std::{
-    collections::BTreeMap,
-    fs::{copy, create_dir_all, read_dir, File},
-    path::Path,
+    collections::{BTreeMap, BTreeSet},
+    fs::{copy, create_dir_all, read_dir, rename, File},
+    io::Write,
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
     process::Command,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, RecvTimeoutError},
+        Arc, Mutex,
+    },
     thread::sleep,
+    time::{Duration, Instant},
 };
 
 use jane_eyre::eyre::{self, bail, eyre, OptionExt};
+use rayon::{
+    iter::{IntoParallelRefIterator, ParallelIterator},
+    ThreadPoolBuilder,
+};
 use serde_json::json;
-use tracing::{debug, info};
-use webdriver_client::{chrome::ChromeDriver, messages::NewSessionCmd, Driver, LocationStrategy};
+use tracing::{debug, error, info, warn};
+use webdriver_client::{
+    chrome::ChromeDriver,
+    firefox::GeckoDriver,
+    messages::{ExecuteCmd, NewSessionCmd},
+    Driver, HttpDriverBuilder, LocationStrategy,
+};
 
 use crate::{
-    shell::SHELL,
+    shell::{Shell, SHELL},
     study::{Engine, KeyedCpuConfig, KeyedEngine, KeyedSite, Study},
 };
 
+/// Whether the user has pressed Ctrl-C, asking us to stop after the current run.
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+/// Process ids of the running benchmark runner scripts.
+static RUNNER_PIDS: Mutex<BTreeSet<u32>> = Mutex::new(BTreeSet::new());
+/// Returns how long it has been since the page last finished loading a resource, in milliseconds.
+/// The first call starts a PerformanceObserver, which keeps seeing new resources even after the
+/// resource timing buffer fills up.
+static NETWORK_QUIET_TIME_SCRIPT: &str = r#"
+    const state = window.__perfAnalysisNetworkIdle ??= (() => {
+        const state = { lastResponseEnd: 0 };
+        const update = entries => {
+            for (const entry of entries) {
+                state.lastResponseEnd = Math.max(state.lastResponseEnd, entry.responseEnd);
+            }
+        };
+        update(performance.getEntriesByType("resource"));
+        new PerformanceObserver(list => update(list.getEntries())).observe({ type: "resource" });
+        return state;
+    })();
+    return performance.now() - state.lastResponseEnd;
+"#;
+/// How long to wait for the network to be idle before giving up on the run, since some pages
+/// never stop loading things.
+const NETWORK_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
 pub fn main(args: Vec<String>) -> eyre::Result<()> {
     let study_dir = Path::new(
         args.iter()
             .nth(0)
-            .expect("Usage: collect <studies/example>"),
+            .expect("Usage: collect <studies/example> [--jobs <count>] [--dry-run]"),
     );
     let study = Study::load(study_dir.join("study.toml"))?;
+    let mut jobs = 1;
+    let mut dry_run = false;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match &**option {
+            "--jobs" => {
+                jobs = options.next().ok_or_eyre("--jobs needs a count")?.parse()?;
+                if jobs == 0 {
+                    bail!("--jobs must be positive");
+                }
+            }
+            "--dry-run" => dry_run = true,
+            other => bail!("Unknown option: {other}"),
+        }
+    }
+    let pool = ThreadPoolBuilder::new().num_threads(jobs).build()?;
 
     // Change working directory to the study directory.
     // We need this for `traceconv_command` and `isolate_cpu_command`.
     std::env::set_current_dir(study_dir)?;
 
+    // CPU isolation applies to our process and its children, which for a remote host is only
+    // `ssh`, not the browser.
+    if study.remote_host.is_some() {
+        warn!("CPU configs only isolate the local machine; the browser on remote_host is not isolated");
+    }
+
+    if dry_run {
+        return print_plan(&study);
+    }
+
+    ctrlc::set_handler(handle_interrupt)?;
+
+    // Engines don’t change during a collect, so run each `version_command` once, not per sample.
+    let versions = study
+        .engines()
+        .map(|engine| (engine.key, engine_version(engine)))
+        .collect::<BTreeMap<_, _>>();
+
     for cpu_config in study.cpu_configs() {
         info!("Setting up CPU isolation");
-        let (program, args) = study
-            .isolate_cpu_command
-            .split_first()
-            .ok_or_eyre("Bad isolate_cpu_command")?;
-        let mut args = args.to_owned();
-        args.push(std::process::id().to_string());
-        args.extend(cpu_config.cpus.iter().map(|cpu| cpu.to_string()));
+        let (program, args) = isolate_cpu_command(&study, cpu_config)?;
         info!(?program, ?args, "Running program");
         let exit_status = Command::new(program).args(args).spawn()?.wait()?;
         if !exit_status.success() {
             bail!("Process failed: {exit_status}");
         }
 
+        // With `--jobs`, collect samples for several sites and engines at once. Samples never
+        // share trace paths or WebDriver ports, but CPU isolation applies to our whole process,
+        // so only samples for the same CPU config can run concurrently, and they compete for the
+        // same CPUs.
+        let samples = study
+            .sites()
+            .flat_map(|site| study.engines().map(move |engine| (site, engine)))
+            .collect::<Vec<_>>();
+        pool.install(|| {
+            samples.par_iter().try_for_each(|&(site, engine)| {
+                check_interrupted()?;
+                let version = versions
+                    .get(engine.key)
+                    .and_then(|version| version.as_deref());
+                create_sample(&study, cpu_config, site, engine, version)
+            })
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Returns the program and arguments for isolating our process to the CPUs of a CPU config.
+fn isolate_cpu_command<'study>(
+    study: &'study Study,
+    cpu_config: KeyedCpuConfig<'_>,
+) -> eyre::Result<(&'study str, Vec<String>)> {
+    let (program, args) = study
+        .isolate_cpu_command
+        .split_first()
+        .ok_or_eyre("Bad isolate_cpu_command")?;
+    let mut args = args.to_owned();
+    if let Some(memory_nodes) = cpu_config.memory_nodes {
+        let memory_nodes = memory_nodes
+            .iter()
+            .map(|node| node.to_string())
+            .collect::<Vec<_>>();
+        args.push(format!("--mems={}", memory_nodes.join(",")));
+    }
+    args.push(std::process::id().to_string());
+    args.extend(cpu_config.cpus.iter().map(|cpu| cpu.to_string()));
+
+    Ok((program, args))
+}
+
+/// Prints the samples that `collect` would create, and how, without isolating any CPUs or
+/// starting any browsers.
+fn print_plan(study: &Study) -> eyre::Result<()> {
+    for cpu_config in study.cpu_configs() {
+        let (program, args) = isolate_cpu_command(study, cpu_config)?;
+        println!(
+            ">>> CPU config {}: would isolate with {program:?} {args:?}",
+            cpu_config.key,
+        );
         for site in study.sites() {
             for engine in study.engines() {
-                create_sample(&study, cpu_config, site, engine)?;
+                let sample_dir = sample_dir(cpu_config, site, engine);
+                if std::fs::exists(sample_dir.join("done"))? {
+                    println!("{sample_dir:?}: already done; would skip");
+                    continue;
+                }
+                if engine.uses_webdriver() {
+                    println!(
+                        "{sample_dir:?}: would do {} runs with {} at {:?}, with extra browser arguments {:?}",
+                        study.sample_size,
+                        engine.type_name(),
+                        engine.browser_path(),
+                        site.extra_engine_arguments(engine.key),
+                    );
+                } else {
+                    let sample_dir = sample_dir.to_str().ok_or_eyre("Bad sample path")?;
+                    println!(
+                        "{sample_dir:?}: would run the {} runner script with arguments {:?}",
+                        engine.type_name(),
+                        runner_args(study, site, engine, sample_dir)?,
+                    );
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the directory for the sample of the given CPU config, site, and engine, relative to the
+/// study directory.
+fn sample_dir(
+    cpu_config: KeyedCpuConfig<'_>,
+    site: KeyedSite<'_>,
+    engine: KeyedEngine<'_>,
+) -> PathBuf {
+    Path::new(cpu_config.key).join(site.key).join(engine.key)
+}
+
+/// Returns the arguments for the engine’s benchmark runner script (see
+/// [KeyedEngine::benchmark_runner_code]).
+fn runner_args(
+    study: &Study,
+    site: KeyedSite<'_>,
+    engine: KeyedEngine<'_>,
+    sample_dir: &str,
+) -> eyre::Result<Vec<String>> {
+    let mut result = vec![
+        engine.browser_path().to_owned(),
+        site.url.to_owned(),
+        study.sample_size.to_string(),
+        sample_dir.to_owned(),
+    ];
+    if let Engine::Servo { .. } = engine.engine {
+        if let Some((_, _, Some(dpr))) = site.screen_size()? {
+            result.extend(["--device-pixel-ratio".to_owned(), dpr.to_string()]);
+        }
+    }
+    result.extend(site.extra_engine_arguments(engine.key).to_owned());
+
+    Ok(result)
+}
+
+/// On the first Ctrl-C, lets the current run finish, then stops. On the second, aborts.
+fn handle_interrupt() {
+    let runner_pids = RUNNER_PIDS
+        .lock()
+        .map_or_else(|e| e.into_inner().clone(), |pids| pids.clone());
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        warn!("Interrupted again; aborting");
+        for runner_pid in runner_pids {
+            // The runner is the leader of its own process group, so this kills the browser too.
+            let _ = Command::new("kill")
+                .args(["-KILL", "--", &format!("-{runner_pid}")])
+                .status();
+        }
+        std::process::exit(130);
+    }
+    warn!("Interrupted; stopping after the current run (press Ctrl-C again to abort)");
+    for runner_pid in runner_pids {
+        // Signal the runner script only, not the browser, so the script can stop after this run.
+        let _ = Command::new("kill")
+            .args(["-INT", &runner_pid.to_string()])
+            .status();
+    }
+}
+
+fn check_interrupted() -> eyre::Result<()> {
+    if INTERRUPTED.load(Ordering::SeqCst) {
+        bail!("Interrupted; run `collect` again to resume");
+    }
+
+    Ok(())
+}
+
+/// Kills a hung browser once a deadline passes, so that one bad site can’t stall the whole study.
+/// The deadline is cancelled when the watchdog is dropped.
+struct Watchdog {
+    _cancel: mpsc::Sender<()>,
+    timed_out: Arc<AtomicBool>,
+}
+
+impl Watchdog {
+    fn new(timeout: Duration, kill: impl FnOnce() + Send + 'static) -> Self {
+        let (cancel, cancelled) = mpsc::channel();
+        let timed_out = Arc::new(AtomicBool::new(false));
+        let thread_timed_out = timed_out.clone();
+        std::thread::spawn(move || {
+            // Dropping the sender disconnects the channel, which wakes us up early.
+            if let Err(RecvTimeoutError::Timeout) = cancelled.recv_timeout(timeout) {
+                thread_timed_out.store(true, Ordering::SeqCst);
+                kill();
+            }
+        });
+
+        Self {
+            _cancel: cancel,
+            timed_out,
+        }
+    }
+
+    fn timed_out(&self) -> bool {
+        self.timed_out.load(Ordering::SeqCst)
+    }
+}
+
+/// Error for a run that was killed by its [Watchdog].
+#[derive(Debug)]
+struct RunTimedOut(Duration);
+
+impl std::fmt::Display for RunTimedOut {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Browser hung for more than {:?}; killed it", self.0)
+    }
+}
+
+impl std::error::Error for RunTimedOut {}
+
+/// Returns the process ids of the processes started by the current thread, and all of their
+/// descendants, as a function that can be called from another thread. This lets a [Watchdog]
+/// kill a WebDriver server and its browser, without touching those of other samples.
+fn descendants_of_current_thread() -> eyre::Result<impl Fn() -> Vec<String>> {
+    // `/proc/thread-self` links to `<pid>/task/<tid>`, whose `children` lists the processes
+    // started by this thread.
+    let task_dir = Path::new("/proc").join(std::fs::read_link("/proc/thread-self")?);
+    fn children(task_dir: &Path) -> Vec<String> {
+        let children = std::fs::read_to_string(task_dir.join("children")).unwrap_or_default();
+        let mut result = vec![];
+        for child in children.split_whitespace() {
+            let tasks = read_dir(Path::new("/proc").join(child).join("task"));
+            for task in tasks.into_iter().flatten().flatten() {
+                result.extend(children(&task.path()));
             }
+            result.push(child.to_owned());
         }
+        result
     }
 
+    Ok(move || children(&task_dir))
+}
+
+/// Marks a sample as failed, so that `collect` can move on to the next sample. Running `collect`
+/// again retries the sample.
+fn record_failed_sample(sample_dir: &Path, error: &eyre::Report) -> eyre::Result<()> {
+    error!(?error, "Sample failed; moving on to the next sample");
+    std::fs::write(sample_dir.join("failed"), format!("{error:?}\n"))?;
+
     Ok(())
 }
 
-#[tracing::instrument(level = "error", skip(study, cpu_config, site, engine), fields(cpu_config = cpu_config.key, site = site.key, engine = engine.key))]
+#[tracing::instrument(level = "error", skip(study, cpu_config, site, engine, version), fields(cpu_config = cpu_config.key, site = site.key, engine = engine.key))]
 fn create_sample(
     study: &Study,
     cpu_config: KeyedCpuConfig<'_>,
     site: KeyedSite<'_>,
     engine: KeyedEngine<'_>,
+    version: Option<&str>,
 ) -> eyre::Result<()> {
-    let sample_dir = Path::new(cpu_config.key).join(site.key).join(engine.key);
+    let sample_dir = sample_dir(cpu_config, site, engine);
     create_dir_all(&sample_dir)?;
 
     if std::fs::exists(sample_dir.join("done"))? {
         info!("Sample is already done; skipping");
         return Ok(());
     }
+    if std::fs::exists(sample_dir.join("failed"))? {
+        info!("Sample failed last time; retrying");
+        std::fs::remove_file(sample_dir.join("failed"))?;
+    }
+
+    if let Some(version) = version {
+        File::create(sample_dir.join("version.txt"))?.write_all(version.as_bytes())?;
+    }
+
+    if engine.uses_webdriver() {
+        if study.remote_host.is_some() {
+            bail!("remote_host is not yet supported for WebDriver-based engines");
+        }
 
-    if let Engine::ChromeDriver { path, .. } = engine.engine {
         // Resolve path against PATH if needed. ChromeDriver or WebDriver seems to need this.
-        let query = SHELL
-            .lock()
-            .map_err(|e| eyre!("Mutex poisoned: {e:?}"))?
-            .run(include_str!("../query-path.sh"), [path])?
-            .output()?;
-        if !query.status.success() {
-            bail!("Process failed: {}", query.status);
-        }
-        let path = str::from_utf8(&query.stdout)?
-            .strip_suffix("\n")
-            .ok_or_eyre("Output has no trailing newline")?;
+        // There is no local browser for a RemoteWebDriver engine.
+        let path = if let Engine::RemoteWebDriver { .. } = engine.engine {
+            None
+        } else {
+            let query = SHELL
+                .lock()
+                .map_err(|e| eyre!("Mutex poisoned: {e:?}"))?
+                .run(include_str!("../query-path.sh"), [engine.browser_path()])?
+                .output()?;
+            if !query.status.success() {
+                bail!("Process failed: {}", query.status);
+            }
+            Some(
+                str::from_utf8(&query.stdout)?
+                    .strip_suffix("\n")
+                    .ok_or_eyre("Output has no trailing newline")?
+                    .to_owned(),
+            )
+        };
+
+        // The browser behind a RemoteWebDriver engine writes its traces (if any) on its own
+        // machine, so there is nothing for us to copy into the sample directory.
+        let trace_file = match engine.engine {
+            Engine::GeckoDriver { .. } => Some(("firefox", "json")),
+            Engine::RemoteWebDriver { .. } => None,
+            _ => Some(("chrome", "pftrace")),
+        };
+        if matches!(engine.engine, Engine::GeckoDriver { .. }) && site.screen_size()?.is_some() {
+            warn!("screen_size is not yet supported for GeckoDriver-type engines; ignoring");
+        }
 
         for i in 1..=study.sample_size {
-            info!("Starting ChromeDriver");
-            let driver =
-                ChromeDriver::spawn().map_err(|e| eyre!("Failed to spawn ChromeDriver: {e}"))?;
+            // The WebDriver server and browser are in our process group, so Ctrl-C kills them,
+            // and the current run fails. But at least we can avoid starting another run.
+            check_interrupted()?;
+            // Each run’s trace only appears once the run succeeds, so we can skip runs that were
+            // done before the previous `collect` was interrupted.
+            let trace_path = trace_file.map(|(prefix, extension)| {
+                sample_dir.join(format!(
+                    "{prefix}{:0width$}.{extension}",
+                    i,
+                    width = study.sample_size.to_string().len()
+                ))
+            });
+            if let Some(trace_path) = &trace_path {
+                if std::fs::exists(trace_path)? {
+                    info!(?trace_path, "Run is already done; skipping");
+                    continue;
+                }
+            }
 
-            // Configure the browser with WebDriver capabilities. Note that ChromeDriver takes care
-            // of running Chromium with a clean profile (much like `--user-data-dir=$(mktemp -d)`)
-            // and in a way amenable to automation (e.g. `--no-first-run`).
-            // <https://www.w3.org/TR/webdriver/#capabilities>
-            // <https://developer.chrome.com/docs/chromedriver/capabilities>
-            let mut params = NewSessionCmd::default();
-            // Do not wait for page load to complete.
-            params.always_match("pageLoadStrategy", json!("none"));
-            // Allow the use of mitmproxy replay (see ../start-mitmproxy.sh).
-            params.always_match("acceptInsecureCerts", json!(true));
+            // Retry failed runs, like those where the WebDriver connection drops or an element
+            // never appears, up to `max_retries` times before giving up on the sample.
+            for attempt in 1.. {
+                let result = create_webdriver_run(
+                    study,
+                    site,
+                    engine,
+                    path.as_deref(),
+                    trace_file,
+                    trace_path.as_deref(),
+                );
+                match result {
+                    Ok(()) => break,
+                    // A hung browser is likely to hang again, so don’t retry.
+                    Err(error) if error.downcast_ref::<RunTimedOut>().is_some() => {
+                        return record_failed_sample(&sample_dir, &error);
+                    }
+                    Err(error)
+                        if attempt <= study.max_retries && !INTERRUPTED.load(Ordering::SeqCst) =>
+                    {
+                        warn!(
+                            run = i,
+                            attempt,
+                            max_retries = study.max_retries,
+                            ?error,
+                            "Run failed; retrying"
+                        );
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+        }
 
+        info!("Marking sample as done");
+        File::create_new(sample_dir.join("done"))?;
+
+        return Ok(());
+    }
+
+    let sample_dir = sample_dir.to_str().ok_or_eyre("Bad sample path")?;
+    info!("Creating sample");
+    let args = runner_args(study, site, engine, sample_dir)?;
+    // Use a shell of our own rather than `SHELL`, so that with `--jobs`, runners for other samples
+    // can run at the same time.
+    let mut shell = Shell::new()?;
+    // Keep the runner script alive until the script exits. We can’t use `shell` for the runner
+    // itself, because `shell` is running `run-remote.sh` from its only script file.
+    let mut remote_runner = None;
+    let mut command = if let Some(remote_host) = study.remote_host.as_deref() {
+        let runner = mktemp::Temp::new_file()?;
+        std::fs::write(&runner, engine.benchmark_runner_code())?;
+        let runner_path = runner.to_str().ok_or_eyre("Bad runner path")?;
+        let mut remote_args = vec![remote_host.to_owned(), runner_path.to_owned()];
+        remote_args.extend(args);
+        remote_runner = Some(runner);
+        shell.run(include_str!("../run-remote.sh"), remote_args)?
+    } else {
+        shell.run(engine.benchmark_runner_code(), args)?
+    };
+    command.env(
+        "SERVO_PERF_BROWSER_OPEN_TIME",
+        site.browser_open_time.as_secs().to_string(),
+    );
+    // Send the output of the runner and browser to `browser.log`, so runs of other samples are not
+    // interleaved with it, and it survives for debugging. Append, so that retries keep earlier logs.
+    let log_path = Path::new(sample_dir).join("browser.log");
+    info!(?log_path, "Writing runner and browser output to log");
+    let log = File::options().create(true).append(true).open(&log_path)?;
+    command.stdout(log.try_clone()?);
+    command.stderr(log);
+    if study.stage_traces_in_temp_dir && study.remote_host.is_some() {
+        warn!("stage_traces_in_temp_dir is not supported with remote_host; ignoring");
+    }
+    // Keep the staging directory alive until the script exits.
+    let staging_dir = if study.stage_traces_in_temp_dir && study.remote_host.is_none() {
+        let staging_dir = mktemp::Temp::new_dir()?;
+        command.env("SERVO_PERF_STAGING_DIR", &*staging_dir);
+        Some(staging_dir)
+    } else {
+        None
+    };
+    // Run the script in its own process group, so that Ctrl-C goes to us only, and we can let
+    // the script finish its current run.
+    command.process_group(0);
+    let mut child = command.spawn()?;
+    let runner_pid = child.id();
+    RUNNER_PIDS
+        .lock()
+        .map_err(|e| eyre!("Mutex poisoned: {e:?}"))?
+        .insert(runner_pid);
+    // The runner script does all of the runs, so give it `run_timeout` for each run. If it hangs,
+    // kill its process group, which includes the browser. For a remote host, the browser is not
+    // in that group, so ask `run-remote.sh` to kill it over SSH and exit instead.
+    let timeout = study.run_timeout() * study.sample_size.try_into()?;
+    let signal = if study.remote_host.is_some() {
+        "-TERM"
+    } else {
+        "-KILL"
+    };
+    let watchdog = Watchdog::new(timeout, move || {
+        warn!(runner_pid, "Runner timed out; killing runner and browser");
+        let _ = Command::new("kill")
+            .args([signal, "--", &format!("-{runner_pid}")])
+            .status();
+    });
+    let exit_status = child.wait();
+    let timed_out = watchdog.timed_out();
+    drop(watchdog);
+    RUNNER_PIDS
+        .lock()
+        .map_err(|e| eyre!("Mutex poisoned: {e:?}"))?
+        .remove(&runner_pid);
+    let exit_status = exit_status?;
+    drop(staging_dir);
+    drop(remote_runner);
+    check_interrupted()?;
+    if timed_out {
+        let sample_dir = Path::new(sample_dir);
+        return record_failed_sample(sample_dir, &RunTimedOut(timeout).into());
+    }
+    if !exit_status.success() {
+        bail!("Process failed: {exit_status}");
+    }
+
+    Ok(())
+}
+
+/// Does one run of a WebDriver-based engine, copying its trace (if any) to `trace_path`, or fails
+/// with [RunTimedOut] if the run takes longer than `run_timeout`.
+fn create_webdriver_run(
+    study: &Study,
+    site: KeyedSite<'_>,
+    engine: KeyedEngine<'_>,
+    path: Option<&str>,
+    trace_file: Option<(&str, &str)>,
+    trace_path: Option<&Path>,
+) -> eyre::Result<()> {
+    // If the browser hangs, kill the WebDriver server and browser, which makes any pending
+    // WebDriver command fail.
+    let descendants = descendants_of_current_thread()?;
+    let watchdog = Watchdog::new(study.run_timeout(), move || {
+        let pids = descendants();
+        warn!(?pids, "Run timed out; killing browser");
+        let _ = Command::new("kill").arg("-KILL").args(pids).status();
+    });
+    let result = create_webdriver_run_inner(study, site, engine, path, trace_file, trace_path);
+    if watchdog.timed_out() {
+        return Err(RunTimedOut(study.run_timeout()).into());
+    }
+
+    result
+}
+
+fn create_webdriver_run_inner(
+    study: &Study,
+    site: KeyedSite<'_>,
+    engine: KeyedEngine<'_>,
+    path: Option<&str>,
+    trace_file: Option<(&str, &str)>,
+    trace_path: Option<&Path>,
+) -> eyre::Result<()> {
+    // Configure the browser with WebDriver capabilities.
+    // <https://www.w3.org/TR/webdriver/#capabilities>
+    let mut params = NewSessionCmd::default();
+    // Do not wait for page load to complete.
+    params.always_match("pageLoadStrategy", json!("none"));
+    // Allow the use of mitmproxy replay (see ../start-mitmproxy.sh).
+    params.always_match("acceptInsecureCerts", json!(true));
+
+    let trace_temp_dir = mktemp::Temp::new_dir()?;
+    // Only used by GeckoDriver, but must outlive the session.
+    let profile_temp_dir = mktemp::Temp::new_dir()?;
+    let driver: Box<dyn Driver> = match engine.engine {
+        Engine::RemoteWebDriver {
+            url, capabilities, ..
+        } => {
+            // Capabilities from the study override our defaults above.
+            for (name, value) in capabilities {
+                params.always_match(name, value.clone());
+            }
+
+            info!(url, "Connecting to WebDriver server");
+            Box::new(
+                HttpDriverBuilder::default()
+                    .url(url)
+                    .build()
+                    .map_err(|e| eyre!("Failed to connect to WebDriver server: {e}"))?,
+            )
+        }
+        Engine::GeckoDriver { .. } => {
+            let path = path.expect("Guaranteed by path resolution");
+            let profile_path = profile_temp_dir.to_str().ok_or_eyre("Unsupported path")?;
+            let trace_temp_path = trace_temp_dir.join("firefox.json");
+            let trace_temp_path = trace_temp_path.to_str().ok_or_eyre("Unsupported path")?;
+            // Run Firefox with a clean profile of our own, so geckodriver doesn’t reuse
+            // anything between runs.
+            let mut args = vec!["-profile".to_owned(), profile_path.to_owned()];
+            args.extend(site.extra_engine_arguments(engine.key).to_owned());
+            let mut prefs = BTreeMap::default();
+            if let Some(user_agent) = site.user_agent {
+                prefs.insert("general.useragent.override", json!(user_agent));
+            }
+            params.always_match(
+                "moz:firefoxOptions",
+                json!({
+                    // <https://developer.mozilla.org/en-US/docs/Web/WebDriver/Capabilities/firefoxOptions>
+                    "binary": path,
+                    "args": args,
+                    "prefs": prefs,
+                    // Start the Gecko profiler on startup, and write the profile on exit.
+                    // <https://firefox-source-docs.mozilla.org/tools/profiler/code/index.html>
+                    "env": {
+                        "MOZ_PROFILER_STARTUP": "1",
+                        "MOZ_PROFILER_SHUTDOWN": trace_temp_path,
+                    },
+                }),
+            );
+
+            info!("Starting geckodriver");
+            Box::new(GeckoDriver::spawn().map_err(|e| eyre!("Failed to spawn geckodriver: {e}"))?)
+        }
+        _ => {
+            let path = path.expect("Guaranteed by path resolution");
+            // Note that ChromeDriver takes care of running Chromium with a clean profile
+            // (much like `--user-data-dir=$(mktemp -d)`) and in a way amenable to
+            // automation (e.g. `--no-first-run`).
+            // <https://developer.chrome.com/docs/chromedriver/capabilities>
             let mut mobile_emulation = BTreeMap::default();
             if let Some(user_agent) = site.user_agent {
-                // ChromeDriver does not support the standard `userAgent` capability, which goes in
-                // the top level. Use `.goog:chromeOptions.mobileEmulation.userAgent` instead.
+                // ChromeDriver does not support the standard `userAgent` capability, which
+                // goes in the top level. Use `.goog:chromeOptions.mobileEmulation.userAgent`.
                 mobile_emulation.insert("userAgent", json!(user_agent));
             }
-            if let Some((width, height)) = site.screen_size()? {
-                mobile_emulation
-                    .insert("deviceMetrics", json!({ "width": width, "height": height }));
+            if let Some((width, height, dpr)) = site.screen_size()? {
+                mobile_emulation.insert(
+                    "deviceMetrics",
+                    json!({ "width": width, "height": height, "pixelRatio": dpr.unwrap_or(1.0) }),
+                );
             }
 
-            let pftrace_temp_dir = mktemp::Temp::new_dir()?;
-            let attempted_pftrace_temp_path = pftrace_temp_dir.join("chrome.pftrace");
+            let attempted_pftrace_temp_path = trace_temp_dir.join("chrome.pftrace");
             let attempted_pftrace_temp_path = attempted_pftrace_temp_path
                 .to_str()
                 .ok_or_eyre("Unsupported path")?;
@@ -119,6 +668,13 @@ fn create_sample(
                 "--trace-startup".to_owned(),
                 format!("--trace-startup-file={attempted_pftrace_temp_path}"),
             ];
+            // Write the browser’s log next to the trace, like `chrome01.log`, for debugging.
+            if let Some(trace_path) = trace_path {
+                let log_path = std::path::absolute(trace_path.with_extension("log"))?;
+                let log_path = log_path.to_str().ok_or_eyre("Unsupported path")?;
+                args.push("--enable-logging".to_owned());
+                args.push(format!("--log-file={log_path}"));
+            }
             args.extend(site.extra_engine_arguments(engine.key).to_owned());
             params.always_match(
                 "goog:chromeOptions",
@@ -130,93 +686,134 @@ fn create_sample(
                 }),
             );
 
-            info!("Starting Chromium");
-            let session = driver.session(&params)?;
-
-            info!(site.url, "Navigating to site");
-            session.go(site.url)?;
-
-            info!(?site.browser_open_time, "Waiting for fixed amount of time");
-            sleep(site.browser_open_time);
-
-            info!(wait_for_selectors = ?site.wait_for_selectors().collect::<Vec<_>>(), "Checking for elements");
-            #[derive(Debug)]
-            struct ElementCounts {
-                expected: usize,
-                actual: usize,
-            }
-            let element_counts = site
-                .wait_for_selectors()
-                .map(
-                    |(selector, expected)| -> eyre::Result<(&String, ElementCounts)> {
-                        Ok((
-                            selector,
-                            ElementCounts {
-                                expected: *expected,
-                                actual: session
-                                    .find_elements(selector, LocationStrategy::Css)?
-                                    .len(),
-                            },
-                        ))
-                    },
-                )
-                .collect::<eyre::Result<BTreeMap<&String, ElementCounts>>>()?;
-            debug!(?element_counts, "Found elements");
-            for (selector, ElementCounts { expected, actual }) in element_counts {
-                assert_eq!(expected, actual, "Condition failed: wait_for_selectors.{selector:?}: expected {expected}, actual {actual}");
-            }
-
-            // When using ChromeDriver, for some reason, Chromium fails to rename the Perfetto trace
-            // to `--trace-startup-file`. Kill ChromeDriver and rename it ourselves.
-            drop(session);
-            let pftrace_path = sample_dir.join(format!(
-                "chrome{:0width$}.pftrace",
-                i,
-                width = study.sample_size.to_string().len()
-            ));
-            let pftrace_path = pftrace_path.to_str().ok_or_eyre("Unsupported path")?;
-            for entry in read_dir(&pftrace_temp_dir)? {
-                let pftrace_temp_path = entry?.path();
-                info!(
-                    ?pftrace_temp_path,
-                    ?pftrace_path,
-                    "Copying Perfetto trace to sample directory"
+            info!("Starting ChromeDriver");
+            Box::new(ChromeDriver::spawn().map_err(|e| eyre!("Failed to spawn ChromeDriver: {e}"))?)
+        }
+    };
+
+    info!(engine = engine.type_name(), "Starting browser");
+    let session = driver.session(&params)?;
+
+    info!(site.url, "Navigating to site");
+    session.go(site.url)?;
+
+    info!(?site.browser_open_time, "Waiting for fixed amount of time");
+    sleep(site.browser_open_time);
+
+    if let Some(wait_for_network_idle) = site.wait_for_network_idle {
+        info!(?wait_for_network_idle, "Waiting for network to be idle");
+        let deadline = Instant::now() + NETWORK_IDLE_TIMEOUT;
+        loop {
+            let quiet_time = session
+                .execute(ExecuteCmd {
+                    script: NETWORK_QUIET_TIME_SCRIPT.to_owned(),
+                    args: vec![],
+                })?
+                .as_f64()
+                .ok_or_eyre("Bad result from network idle script")?;
+            let quiet_time = Duration::from_secs_f64(quiet_time.max(0.0) / 1000.0);
+            debug!(?quiet_time, "Checked network activity");
+            if quiet_time >= wait_for_network_idle {
+                break;
+            }
+            if Instant::now() >= deadline {
+                bail!(
+                    "Network never became idle for site {:?} within {NETWORK_IDLE_TIMEOUT:?}: needed {wait_for_network_idle:?} without requests completing, last quiet time was {quiet_time:?}",
+                    site.key
                 );
-                copy(pftrace_temp_path, pftrace_path)?;
             }
-
-            // Extend the lifetime of `pftrace_temp_dir` to avoid premature deletion.
-            drop(pftrace_temp_dir);
+            // If nothing else loads in the meantime, this is exactly how long we need to wait.
+            sleep(wait_for_network_idle - quiet_time);
         }
+    }
 
-        info!("Marking sample as done");
-        File::create_new(sample_dir.join("done"))?;
+    info!(wait_for_selectors = ?site.wait_for_selectors().collect::<Vec<_>>(), selector_timeout = ?study.selector_timeout(), "Checking for elements");
+    // Poll each selector until it matches the expected count, or the timeout elapses.
+    // With the default timeout of zero, we check each selector exactly once.
+    for (selector, &expected) in site.wait_for_selectors() {
+        let deadline = Instant::now() + study.selector_timeout();
+        loop {
+            let actual = session
+                .find_elements(selector, LocationStrategy::Css)?
+                .len();
+            debug!(?selector, expected, actual, "Found elements");
+            if actual == expected {
+                break;
+            }
+            if Instant::now() >= deadline {
+                bail!("Condition failed: wait_for_selectors.{selector:?}: expected {expected}, actual {actual}");
+            }
+            sleep(Duration::from_millis(100));
+        }
+    }
 
-        return Ok(());
+    // When using ChromeDriver, for some reason, Chromium fails to rename the Perfetto trace
+    // to `--trace-startup-file`. Kill ChromeDriver and rename it ourselves. Firefox only
+    // writes its profile on exit, so we need to end the session first there too.
+    drop(session);
+    if let (Some(trace_path), Some((_, extension))) = (trace_path, trace_file) {
+        // Copy to a temporary name first, so the final rename is atomic.
+        let partial_trace_path = trace_path.with_extension(format!("{extension}.partial"));
+        for entry in read_dir(&trace_temp_dir)? {
+            let trace_temp_path = entry?.path();
+            info!(
+                ?trace_temp_path,
+                ?trace_path,
+                "Copying trace to sample directory"
+            );
+            copy(trace_temp_path, &partial_trace_path)?;
+            rename(&partial_trace_path, trace_path)?;
+        }
+        if !std::fs::exists(trace_path)? {
+            bail!("Browser exited without writing a trace");
+        }
     }
 
-    let sample_dir = sample_dir.to_str().ok_or_eyre("Bad sample path")?;
-    info!("Creating sample");
-    let mut args = vec![
-        engine.browser_path().to_owned(),
-        site.url.to_owned(),
-        study.sample_size.to_string(),
-        sample_dir.to_owned(),
-    ];
-    args.extend(site.extra_engine_arguments(engine.key).to_owned());
-    let exit_status = SHELL
+    // Extend the lifetime of the temporary directories to avoid premature deletion.
+    drop(trace_temp_dir);
+    drop(profile_temp_dir);
+
+    Ok(())
+}
+
+/// Runs the engine’s `version_command`, if any, and returns its output. If the command fails in
+/// any way, we warn and return None, and the report will say that the version is unknown.
+#[tracing::instrument(level = "error", skip(engine), fields(engine = engine.key))]
+fn engine_version(engine: KeyedEngine<'_>) -> Option<String> {
+    let version_command = engine.version_command()?;
+
+    info!(?version_command, "Running version_command");
+    let output = SHELL
         .lock()
-        .map_err(|e| eyre!("Mutex poisoned: {e:?}"))?
-        .run(engine.benchmark_runner_code(), args)?
-        .env(
-            "SERVO_PERF_BROWSER_OPEN_TIME",
-            site.browser_open_time.as_secs().to_string(),
-        )
-        .spawn()?
-        .wait()?;
-    if !exit_status.success() {
-        bail!("Process failed: {exit_status}");
+        .map_err(|e| eyre!("Mutex poisoned: {e:?}"))
+        .and_then(|mut shell| {
+            Ok(shell
+                .run(
+                    &format!("#!/bin/sh\n{version_command}\n"),
+                    Vec::<&str>::default(),
+                )?
+                .output()?)
+        });
+    let output = match output {
+        Ok(output) => output,
+        Err(error) => {
+            warn!(?error, "Failed to run version_command; version unknown");
+            return None;
+        }
+    };
+    if !output.status.success() {
+        warn!(status = ?output.status, "version_command failed; version unknown");
+        return None;
+    }
+    let Ok(version) = str::from_utf8(&output.stdout) else {
+        warn!("version_command printed invalid UTF-8; version unknown");
+        return None;
+    };
+    let version = version.trim();
+    if version.is_empty() {
+        warn!("version_command printed nothing; version unknown");
+        return None;
     }
 
-    Ok(())
+    Some(version.to_owned())
 }
@@ -1,36 +1,147 @@
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
-use jane_eyre::eyre::{self, bail, OptionExt};
-use serde_json::json;
+use jane_eyre::eyre::{self, bail, Context, OptionExt};
+use serde_json::{json, Value};
+use tracing::warn;
 
 use crate::{
+    analyse::expand_input_globs,
     json::{JsonTrace, TraceEvent},
-    summary::{Analysis, Event, Individual},
+    servo::MetricAnchor,
+    summary::{Analysis, Event, Individual, MetricBasis, RepresentativeStatistic},
 };
 
+/// Firefox Profiler marker phases, from `INSTANT` and `INTERVAL` in the profiler’s
+/// `src/constants.js`, as of processed profile version 46.
+static MARKER_PHASE_INSTANT: u8 = 0;
+static MARKER_PHASE_INTERVAL: u8 = 1;
+/// Firefox Profiler format versions that our processed profile follows, from
+/// `GECKO_PROFILE_VERSION` and `PROCESSED_PROFILE_VERSION` in the profiler’s
+/// `src/app-logic/constants.js`. The profiler upgrades older profiles when loading them, so newer
+/// profiler versions can still open ours.
+/// <https://github.com/firefox-devtools/profiler/blob/main/docs-developer/CHANGELOG-formats.md>
+static FIREFOX_GECKO_PROFILE_VERSION: usize = 27;
+static FIREFOX_PROCESSED_PROFILE_VERSION: usize = 46;
+
+/// Output format for the combined trace.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Format {
+    /// Chromium JSON trace, for Perfetto UI.
+    Chromium,
+    /// Firefox Profiler processed profile.
+    Firefox,
+}
+
+/// A “process” in the combined trace, one for each analysis given.
+struct TraceProcess {
+    name: String,
+    rows: Vec<TraceRow>,
+}
+
+/// A “thread” in the combined trace, one for the real and synthetic events of each individual.
+struct TraceRow {
+    id: usize,
+    name: String,
+    events: Vec<Event>,
+    /// Start of the anchor event in this row’s individual, if its command has an anchor.
+    anchor: Option<Duration>,
+}
+
 pub fn main(args: Vec<String>) -> eyre::Result<()> {
     let mut names = vec![];
+    let mut anchors = vec![];
     let mut analyses = vec![];
     let mut longest_path_prefix: Option<String> = None;
 
+    // Options come before the first command.
+    let mut representative_metric = None;
+    let mut statistic = RepresentativeStatistic::default();
+    let mut format = Format::Chromium;
+    let mut args = &args[..];
+    loop {
+        match args {
+            [option, metric, rest @ ..] if option == "--representative" => {
+                representative_metric = Some(&**metric);
+                args = rest;
+            }
+            [option, value, rest @ ..] if option == "--statistic" => {
+                statistic = value
+                    .parse()
+                    .wrap_err_with(|| format!("Unknown statistic: {value}"))?;
+                args = rest;
+            }
+            [option, value, rest @ ..] if option == "--format" => {
+                format = match &**value {
+                    "chromium" => Format::Chromium,
+                    "firefox" => Format::Firefox,
+                    other => bail!("Unknown format: {other}"),
+                };
+                args = rest;
+            }
+            _ => break,
+        }
+    }
+
     for args in args.split(|arg| arg == "--") {
         let mode = &args[0];
-        let args = &args[1..];
+        // Each command can align its individuals to the first event with the given name.
+        let (anchor, args) = match &args[1..] {
+            [option, name, rest @ ..] if option == "--anchor" => (Some(name.clone()), rest),
+            rest => (None, rest),
+        };
+        let args = expand_input_globs(args.to_vec())?;
         names.push(format!("{mode} (command {})", analyses.len()));
+        anchors.push(anchor);
 
         let individuals = match &**mode {
             // Usage: analyse servo <trace.html ...>
-            "servo" => crate::servo::analyse_individuals(&args)?
-                .into_iter()
-                .map(|s| Box::new(s) as Box<dyn Individual>)
-                .collect::<Vec<_>>(),
+            "servo" => crate::servo::analyse_individuals(
+                &args,
+                &MetricAnchor::default(),
+                &[],
+                None,
+                &crate::servo::default_category_names(),
+            )?
+            .into_iter()
+            .map(|s| Box::new(s) as Box<dyn Individual>)
+            .collect::<Vec<_>>(),
             // Usage: analyse chromium <page url> <chrome.json ...>
-            "chromium" => crate::chromium::analyse_individuals(&args)?
-                .into_iter()
-                .map(|s| Box::new(s) as Box<dyn Individual>)
-                .collect::<Vec<_>>(),
+            "chromium" => crate::chromium::analyse_individuals(
+                &args,
+                false,
+                MetricBasis::Native,
+                None,
+                &crate::chromium::default_category_names(),
+            )?
+            .into_iter()
+            .map(|s| Box::new(s) as Box<dyn Individual>)
+            .collect::<Vec<_>>(),
             other => bail!("Unknown command: {other}"),
         };
+        // If requested, keep only the representative individual for the given metric.
+        let individuals = match representative_metric {
+            Some(metric) => {
+                let index = Analysis {
+                    individuals: individuals.iter().enumerate().collect::<Vec<_>>(),
+                }
+                .representative_individual(statistic, |(_, individual)| {
+                    let events = individual.synthetic_events().ok()?;
+                    let total = events
+                        .iter()
+                        .filter(|e| e.name == metric)
+                        .flat_map(|e| e.duration)
+                        .sum::<Duration>();
+                    events
+                        .iter()
+                        .any(|e| e.name == metric)
+                        .then_some(total.as_secs_f64())
+                })
+                .map(|&(index, _)| index)
+                .ok_or_eyre("No individual has a value for the representative metric")?;
+                individuals.into_iter().skip(index).take(1).collect()
+            }
+            None => individuals,
+        };
 
         for individual in individuals.iter() {
             let path = Path::new(individual.path()).canonicalize()?;
@@ -57,17 +168,10 @@ pub fn main(args: Vec<String>) -> eyre::Result<()> {
     }
 
     let longest_path_prefix = longest_path_prefix.ok_or_eyre("No longest path prefix")?;
-    let mut events = vec![];
+    let mut processes = vec![];
     // For each analysis given, create a “process”.
-    for (i, (analysis, name)) in analyses.into_iter().zip(names).enumerate() {
-        events.push(TraceEvent {
-            ph: "M".to_owned(),
-            name: "process_name".to_owned(),
-            cat: "__metadata".to_owned(),
-            pid: i,
-            args: [("name".to_owned(), json!(name))].into_iter().collect(),
-            ..Default::default()
-        });
+    for ((analysis, name), anchor_name) in analyses.into_iter().zip(names).zip(anchors) {
+        let mut rows = vec![];
         // For each of its individuals, create two “threads”, one for synthetic events and one for real events.
         for (j, individual) in analysis.individuals.into_iter().enumerate() {
             // Strip the longest path prefix across all individuals and all commands, for brevity in Perfetto UI.
@@ -79,60 +183,273 @@ pub fn main(args: Vec<String>) -> eyre::Result<()> {
                 bail!("Failed to strip longest path prefix")
             };
 
-            struct TraceRow {
-                id: usize,
-                name: String,
-                events: Vec<Event>,
+            let real_events = individual.real_events()?;
+            let synthetic_events = individual.synthetic_events()?;
+            let anchor = anchor_name.as_ref().and_then(|anchor_name| {
+                let result = real_events
+                    .iter()
+                    .chain(&synthetic_events)
+                    .filter(|e| e.name == *anchor_name)
+                    .map(|e| e.start)
+                    .min();
+                if result.is_none() {
+                    warn!(
+                        path,
+                        %anchor_name, "No anchor event; not aligning this individual"
+                    );
+                }
+                result
+            });
+
+            rows.push(TraceRow {
+                id: j * 2 + 0,
+                name: format!("{path} (real)"),
+                events: real_events,
+                anchor,
+            });
+            rows.push(TraceRow {
+                id: j * 2 + 1,
+                name: format!("{path} (synthetic)"),
+                events: synthetic_events,
+                anchor,
+            });
+        }
+        processes.push(TraceProcess { name, rows });
+    }
+
+    // Shift each anchored individual so that its anchor event starts at the same time as the
+    // latest anchor event, since events can’t start before zero.
+    let latest_anchor = processes
+        .iter()
+        .flat_map(|process| &process.rows)
+        .flat_map(|row| row.anchor)
+        .max();
+    if let Some(latest_anchor) = latest_anchor {
+        for row in processes.iter_mut().flat_map(|process| &mut process.rows) {
+            if let Some(anchor) = row.anchor {
+                for event in row.events.iter_mut() {
+                    event.start += latest_anchor - anchor;
+                }
             }
-            for row in [
-                TraceRow {
-                    id: j * 2 + 0,
-                    name: format!("{path} (real)"),
-                    events: individual.real_events()?,
-                },
-                TraceRow {
-                    id: j * 2 + 1,
-                    name: format!("{path} (synthetic)"),
-                    events: individual.synthetic_events()?,
-                },
-            ] {
+        }
+    }
+
+    match format {
+        Format::Chromium => println!("{}", serde_json::to_string(&chromium_trace(processes)?)?),
+        Format::Firefox => println!("{}", serde_json::to_string(&firefox_profile(processes))?),
+    }
+
+    Ok(())
+}
+
+fn chromium_trace(processes: Vec<TraceProcess>) -> eyre::Result<JsonTrace> {
+    let mut events = vec![];
+    for (i, process) in processes.into_iter().enumerate() {
+        events.push(TraceEvent {
+            ph: "M".to_owned(),
+            name: "process_name".to_owned(),
+            cat: "__metadata".to_owned(),
+            pid: i,
+            args: [("name".to_owned(), json!(process.name))]
+                .into_iter()
+                .collect(),
+            ..Default::default()
+        });
+        for row in process.rows {
+            events.push(TraceEvent {
+                ph: "M".to_owned(),
+                name: "thread_name".to_owned(),
+                cat: "__metadata".to_owned(),
+                pid: i,
+                tid: row.id.try_into()?,
+                args: [("name".to_owned(), json!(row.name))].into_iter().collect(),
+                ..Default::default()
+            });
+            for event in row.events {
                 events.push(TraceEvent {
-                    ph: "M".to_owned(),
-                    name: "thread_name".to_owned(),
-                    cat: "__metadata".to_owned(),
+                    ts: event.start.as_micros().try_into()?,
+                    dur: match event.duration {
+                        Some(dur) => Some(dur.as_micros().try_into()?),
+                        None => None,
+                    },
+                    ph: if event.duration.is_some() {
+                        "X".to_owned()
+                    } else {
+                        "I".to_owned()
+                    },
+                    s: Some("t".to_owned()),
+                    name: event.name,
+                    cat: "content".to_owned(),
                     pid: i,
                     tid: row.id.try_into()?,
-                    args: [("name".to_owned(), json!(row.name))].into_iter().collect(),
                     ..Default::default()
                 });
-                for event in row.events {
-                    events.push(TraceEvent {
-                        ts: event.start.as_micros().try_into()?,
-                        dur: match event.duration {
-                            Some(dur) => Some(dur.as_micros().try_into()?),
-                            None => None,
-                        },
-                        ph: if event.duration.is_some() {
-                            "X".to_owned()
-                        } else {
-                            "I".to_owned()
-                        },
-                        s: Some("t".to_owned()),
-                        name: event.name,
-                        cat: "content".to_owned(),
-                        pid: i,
-                        tid: row.id.try_into()?,
-                        ..Default::default()
-                    });
-                }
             }
         }
     }
 
-    let trace = JsonTrace {
+    Ok(JsonTrace {
         traceEvents: events,
+    })
+}
+
+/// Firefox Profiler processed profile, with each event as a marker and no samples.
+///
+/// Format spec: <https://github.com/firefox-devtools/profiler/blob/main/docs-developer/PROCESSED-PROFILE-FORMAT.md>
+fn firefox_profile(processes: Vec<TraceProcess>) -> Value {
+    let mut threads = vec![];
+    for (i, process) in processes.into_iter().enumerate() {
+        for row in process.rows {
+            // Marker names are indices into the thread’s string table.
+            let mut strings: Vec<String> = vec![];
+            let mut markers = FirefoxMarkers::default();
+            for event in row.events {
+                let name = match strings.iter().position(|s| *s == event.name) {
+                    Some(index) => index,
+                    None => {
+                        strings.push(event.name);
+                        strings.len() - 1
+                    }
+                };
+                let start = event.start.as_secs_f64() * 1000.0;
+                markers.name.push(name);
+                markers.start_time.push(start);
+                match event.duration {
+                    Some(duration) => {
+                        markers
+                            .end_time
+                            .push(Some(start + duration.as_secs_f64() * 1000.0));
+                        markers.phase.push(MARKER_PHASE_INTERVAL);
+                    }
+                    None => {
+                        markers.end_time.push(None);
+                        markers.phase.push(MARKER_PHASE_INSTANT);
+                    }
+                }
+            }
+            let length = markers.name.len();
+            threads.push(json!({
+                "name": row.name,
+                "processName": process.name,
+                "processType": "default",
+                "processStartupTime": 0,
+                "processShutdownTime": null,
+                "registerTime": 0,
+                "unregisterTime": null,
+                "pausedRanges": [],
+                "isMainThread": false,
+                "pid": i.to_string(),
+                "tid": row.id,
+                "samples": {
+                    "weightType": "samples", "weight": null, "stack": [], "time": [], "length": 0,
+                },
+                "markers": {
+                    "data": vec![Value::Null; length],
+                    "name": markers.name,
+                    "startTime": markers.start_time,
+                    "endTime": markers.end_time,
+                    "phase": markers.phase,
+                    "category": vec![0; length],
+                    "length": length,
+                },
+                "stackTable": {
+                    "frame": [], "prefix": [], "category": [], "subcategory": [], "length": 0,
+                },
+                "frameTable": {
+                    "address": [], "inlineDepth": [], "category": [], "subcategory": [], "func": [],
+                    "nativeSymbol": [], "innerWindowID": [], "implementation": [], "line": [],
+                    "column": [], "length": 0,
+                },
+                "funcTable": {
+                    "isJS": [], "relevantForJS": [], "name": [], "resource": [], "fileName": [],
+                    "lineNumber": [], "columnNumber": [], "length": 0,
+                },
+                "resourceTable": { "lib": [], "name": [], "host": [], "type": [], "length": 0 },
+                "nativeSymbols": {
+                    "libIndex": [], "address": [], "name": [], "functionSize": [], "length": 0,
+                },
+                "stringArray": strings,
+            }));
+        }
+    }
+
+    json!({
+        "meta": {
+            "interval": 1,
+            "startTime": 0,
+            "processType": 0,
+            "product": "analyse combined",
+            "stackwalk": 0,
+            "version": FIREFOX_GECKO_PROFILE_VERSION,
+            "preprocessedProfileVersion": FIREFOX_PROCESSED_PROFILE_VERSION,
+            "symbolicated": true,
+            "categories": [{ "name": "Other", "color": "grey", "subcategories": ["Other"] }],
+            "markerSchema": [],
+        },
+        "libs": [],
+        "threads": threads,
+    })
+}
+
+#[test]
+fn test_firefox_profile() {
+    let event = |name: &str, start: u64, duration: Option<u64>| Event {
+        name: name.to_owned(),
+        start: Duration::from_millis(start),
+        duration: duration.map(Duration::from_millis),
+        metadata: Default::default(),
     };
-    println!("{}", serde_json::to_string(&trace)?);
+    let profile = firefox_profile(vec![TraceProcess {
+        name: "servo".to_owned(),
+        rows: vec![TraceRow {
+            id: 1,
+            name: "servo1.pftrace".to_owned(),
+            events: vec![
+                event("Layout", 2, Some(3)),
+                event("FCP", 4, None),
+                event("Layout", 10, Some(1)),
+            ],
+            anchor: None,
+        }],
+    }]);
+    assert_eq!(profile["meta"]["version"], FIREFOX_GECKO_PROFILE_VERSION);
+    assert_eq!(
+        profile["meta"]["preprocessedProfileVersion"],
+        FIREFOX_PROCESSED_PROFILE_VERSION
+    );
 
-    Ok(())
+    let thread = &profile["threads"][0];
+    let markers = &thread["markers"];
+    // Every column of the marker table has one entry per marker.
+    for column in ["data", "name", "startTime", "endTime", "phase", "category"] {
+        assert_eq!(
+            markers[column].as_array().map(|column| column.len()),
+            Some(3),
+            "{column}"
+        );
+    }
+    assert_eq!(markers["length"], 3);
+    // Names are indices into the thread’s string table, which has each name once.
+    assert_eq!(thread["stringArray"], json!(["Layout", "FCP"]));
+    assert_eq!(markers["name"], json!([0, 1, 0]));
+    // Times are in milliseconds, and instants have no end time.
+    assert_eq!(markers["startTime"], json!([2.0, 4.0, 10.0]));
+    assert_eq!(markers["endTime"], json!([5.0, null, 11.0]));
+    assert_eq!(
+        markers["phase"],
+        json!([
+            MARKER_PHASE_INTERVAL,
+            MARKER_PHASE_INSTANT,
+            MARKER_PHASE_INTERVAL
+        ])
+    );
+}
+
+/// Columns of a Firefox Profiler marker table, with times in milliseconds.
+#[derive(Default)]
+struct FirefoxMarkers {
+    name: Vec<usize>,
+    start_time: Vec<f64>,
+    end_time: Vec<Option<f64>>,
+    phase: Vec<u8>,
 }
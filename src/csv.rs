@@ -0,0 +1,93 @@
+use std::path::Path;
+
+use jane_eyre::eyre;
+use tracing::warn;
+
+use crate::{
+    report::read_analysed_summaries,
+    study::{Engine, Study},
+    summary::EventKind,
+};
+
+pub fn main(args: Vec<String>) -> eyre::Result<()> {
+    let study_dir = Path::new(args.iter().nth(0).expect("Usage: csv <studies/example>"));
+    let study = Study::load(study_dir.join("study.toml"))?;
+
+    // Change working directory to the study directory.
+    std::env::set_current_dir(study_dir)?;
+
+    println!("cpu_config,site,engine,kind,metric,n,mean,stdev,min,max");
+    for (cpu_config, site, engine, summaries) in read_analysed_summaries(&study, Path::new("."))? {
+        let real_kind = match engine.engine {
+            Engine::Servo { .. } => EventKind::Servo,
+            Engine::Chromium { .. } | Engine::ChromeDriver { .. } => EventKind::Chromium,
+            Engine::WebKit { .. } => EventKind::WebKit,
+            Engine::GeckoDriver { .. } | Engine::RemoteWebDriver { .. } => {
+                warn!(
+                    cpu_config = cpu_config.key,
+                    site = site.key,
+                    engine = engine.key,
+                    engine_type = engine.type_name(),
+                    "Engine type is not supported; skipping"
+                );
+                continue;
+            }
+        };
+        let rows = summaries
+            .real_events
+            .iter()
+            .map(|summary| (real_kind, summary))
+            .chain(
+                summaries
+                    .synthetic_and_interpreted_events
+                    .iter()
+                    .map(|summary| (EventKind::SyntheticOrInterpreted, summary)),
+            )
+            .chain(
+                summaries
+                    .counters
+                    .iter()
+                    .map(|summary| (EventKind::Counter, summary)),
+            )
+            .chain(
+                summaries
+                    .fractions
+                    .iter()
+                    .map(|summary| (EventKind::Fraction, summary)),
+            );
+        for (kind, summary) in rows {
+            let raw = &summary.raw;
+            println!(
+                "{},{},{},{},{},{},{},{},{},{}",
+                csv_field(cpu_config.key),
+                csv_field(site.key),
+                csv_field(engine.key),
+                kind,
+                csv_field(&summary.name),
+                raw.n,
+                raw.mean,
+                raw.stdev,
+                raw.min,
+                raw.max,
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Quotes the given field if needed, per RFC 4180.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_owned()
+    }
+}
+
+#[test]
+fn test_csv_field() {
+    assert_eq!(csv_field("servo.org"), "servo.org");
+    assert_eq!(csv_field("a,b"), "\"a,b\"");
+    assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+}
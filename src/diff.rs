@@ -0,0 +1,288 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    fs::File,
+    io::Write,
+    path::Path,
+};
+
+use jane_eyre::eyre::{self, bail, OptionExt};
+use serde::Serialize;
+use tracing::{info, warn};
+
+use crate::{
+    report::read_summaries,
+    study::Study,
+    summary::{fmt_seconds_offset, JsonSummaries, JsonSummary},
+};
+
+/// Relative change in a representative value, beyond which we mark the change.
+static DEFAULT_THRESHOLD: f64 = 0.05;
+
+/// Which list in `summaries.json` a metric is from. Metrics in different categories can have the
+/// same name, like the real and synthetic `Layout` in Chromium, so they are compared separately.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "snake_case")]
+enum Category {
+    Real,
+    RealSelfTime,
+    Synthetic,
+    Counter,
+    Fraction,
+}
+
+impl Display for Category {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Real => write!(f, "real"),
+            Self::RealSelfTime => write!(f, "real_self_time"),
+            Self::Synthetic => write!(f, "synthetic"),
+            Self::Counter => write!(f, "counter"),
+            Self::Fraction => write!(f, "fraction"),
+        }
+    }
+}
+
+/// Comparison of one metric in one sample between the baseline study and the new study.
+#[derive(Debug, Serialize)]
+struct MetricDiff {
+    cpu_config: String,
+    site: String,
+    engine: String,
+    category: Category,
+    metric: String,
+    /// Representative value in the baseline study, or None if the metric is missing there.
+    baseline: Option<f64>,
+    /// Representative value in the new study, or None if the metric is missing there.
+    new: Option<f64>,
+    /// Human-readable representative value in the baseline study.
+    baseline_text: Option<String>,
+    /// Human-readable representative value in the new study.
+    new_text: Option<String>,
+    #[serde(flatten)]
+    comparison: Comparison,
+}
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+struct Comparison {
+    absolute_delta: Option<f64>,
+    /// Delta as a fraction of the baseline value, or None if the baseline value is zero.
+    relative_delta: Option<f64>,
+    exceeds_threshold: bool,
+}
+
+pub fn main(args: Vec<String>) -> eyre::Result<()> {
+    let usage = "Usage: diff <baseline_study_dir> <new_study_dir> [--threshold <fraction>]";
+    let baseline_dir = Path::new(args.first().ok_or_eyre(usage)?);
+    let new_dir = Path::new(args.get(1).ok_or_eyre(usage)?);
+    let mut threshold = DEFAULT_THRESHOLD;
+    let mut options = args[2..].iter();
+    while let Some(option) = options.next() {
+        match &**option {
+            "--threshold" => {
+                threshold = options
+                    .next()
+                    .ok_or_eyre("--threshold needs a fraction")?
+                    .parse()?;
+            }
+            other => bail!("Unknown option: {other}"),
+        }
+    }
+
+    let baseline_study = Study::load(baseline_dir.join("study.toml"))?;
+    let new_study = Study::load(new_dir.join("study.toml"))?;
+    let mut sample_keys = BTreeSet::default();
+    for study in [&baseline_study, &new_study] {
+        for cpu_config in study.cpu_configs() {
+            for site in study.sites() {
+                for engine in study.analysed_engines() {
+                    sample_keys.insert((cpu_config.key, site.key, engine.key));
+                }
+            }
+        }
+    }
+
+    let mut diffs = vec![];
+    let mut missing_count = 0;
+    for (cpu_config, site, engine) in sample_keys {
+        let baseline = load_summaries(baseline_dir, cpu_config, site, engine)?;
+        let new = load_summaries(new_dir, cpu_config, site, engine)?;
+        let baseline = metrics_by_name(baseline.as_ref());
+        let new = metrics_by_name(new.as_ref());
+        let metrics = baseline.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+        for &(category, metric) in metrics {
+            let baseline = baseline.get(&(category, metric));
+            let new = new.get(&(category, metric));
+            if baseline.is_none() || new.is_none() {
+                warn!(
+                    cpu_config,
+                    site,
+                    engine,
+                    %category,
+                    metric,
+                    in_baseline = baseline.is_some(),
+                    in_new = new.is_some(),
+                    "Metric is missing from one of the studies"
+                );
+                missing_count += 1;
+            }
+            let comparison = compare(
+                baseline.map(|s| s.raw.representative()),
+                new.map(|s| s.raw.representative()),
+                threshold,
+            );
+            diffs.push(MetricDiff {
+                cpu_config: cpu_config.to_owned(),
+                site: site.to_owned(),
+                engine: engine.to_owned(),
+                category,
+                metric: metric.to_owned(),
+                baseline: baseline.map(|s| s.raw.representative()),
+                new: new.map(|s| s.raw.representative()),
+                baseline_text: baseline.map(|s| s.representative.clone()),
+                new_text: new.map(|s| s.representative.clone()),
+                comparison,
+            });
+        }
+    }
+
+    let json_path = new_dir.join("diff.json");
+    info!(?json_path, "Writing diff");
+    File::create(json_path)?.write_all(serde_json::to_string(&diffs)?.as_bytes())?;
+    print_table(&diffs);
+    if missing_count > 0 {
+        warn!("{missing_count} metrics are missing from one of the studies (see above)");
+    }
+
+    Ok(())
+}
+
+/// Reads `summaries.json` for the given sample in the given study, or None if the sample has
+/// not been analysed there.
+fn load_summaries(
+    study_dir: &Path,
+    cpu_config: &str,
+    site: &str,
+    engine: &str,
+) -> eyre::Result<Option<JsonSummaries>> {
+    let path = study_dir
+        .join(cpu_config)
+        .join(site)
+        .join(engine)
+        .join("summaries.json");
+    if !path.exists() {
+        warn!(
+            ?path,
+            "No summaries.json; all metrics will be flagged as missing"
+        );
+        return Ok(None);
+    }
+
+    Ok(Some(read_summaries(&path)?))
+}
+
+fn metrics_by_name(summaries: Option<&JsonSummaries>) -> BTreeMap<(Category, &str), &JsonSummary> {
+    summaries
+        .into_iter()
+        .flat_map(|s| {
+            [
+                (Category::Real, &s.real_events),
+                (Category::RealSelfTime, &s.real_events_self_time),
+                (Category::Synthetic, &s.synthetic_and_interpreted_events),
+                (Category::Counter, &s.counters),
+                (Category::Fraction, &s.fractions),
+            ]
+        })
+        .flat_map(|(category, summaries)| {
+            summaries
+                .iter()
+                .map(move |summary| ((category, &*summary.name), summary))
+        })
+        .collect()
+}
+
+fn compare(baseline: Option<f64>, new: Option<f64>, threshold: f64) -> Comparison {
+    let (Some(baseline), Some(new)) = (baseline, new) else {
+        return Comparison::default();
+    };
+    let absolute_delta = new - baseline;
+    let relative_delta = (baseline != 0.0).then_some(absolute_delta / baseline);
+
+    Comparison {
+        absolute_delta: Some(absolute_delta),
+        relative_delta,
+        exceeds_threshold: relative_delta.is_some_and(|delta| delta.abs() > threshold),
+    }
+}
+
+#[test]
+fn test_compare() {
+    assert_eq!(
+        compare(Some(2.0), Some(2.5), 0.05),
+        Comparison {
+            absolute_delta: Some(0.5),
+            relative_delta: Some(0.25),
+            exceeds_threshold: true,
+        }
+    );
+    assert_eq!(
+        compare(Some(2.0), Some(1.96875), 0.05),
+        Comparison {
+            absolute_delta: Some(-0.03125),
+            relative_delta: Some(-0.015625),
+            exceeds_threshold: false,
+        }
+    );
+    assert_eq!(
+        compare(Some(0.0), Some(1.0), 0.05),
+        Comparison {
+            absolute_delta: Some(1.0),
+            relative_delta: None,
+            exceeds_threshold: false,
+        }
+    );
+    assert_eq!(compare(None, Some(1.0), 0.05), Comparison::default());
+    assert_eq!(compare(Some(1.0), None, 0.05), Comparison::default());
+}
+
+fn print_table(diffs: &[MetricDiff]) {
+    println!(
+        "{:<12} {:<24} {:<16} {:<14} {:<28} {:>12} {:>12} {:>12} {:>8}",
+        "cpu_config", "site", "engine", "category", "metric", "baseline", "new", "delta", "delta%"
+    );
+    for diff in diffs {
+        // Counters are in their own units, and fractions change by percentage points.
+        let delta = diff
+            .comparison
+            .absolute_delta
+            .map_or(String::default(), |delta| match diff.category {
+                Category::Counter => format!("{delta:+.3}"),
+                Category::Fraction => format!("{:+.1}pp", delta * 100.0),
+                _ => fmt_seconds_offset(delta),
+            });
+        let relative_delta = diff
+            .comparison
+            .relative_delta
+            .map_or(String::default(), |delta| format!("{:+.1}%", delta * 100.0));
+        // Flag changes beyond the threshold, and metrics that only one side has.
+        let marker = if diff.comparison.exceeds_threshold {
+            " !"
+        } else if diff.baseline.is_none() || diff.new.is_none() {
+            " ?"
+        } else {
+            ""
+        };
+        println!(
+            "{:<12} {:<24} {:<16} {:<14} {:<28} {:>12} {:>12} {:>12} {:>8}{marker}",
+            diff.cpu_config,
+            diff.site,
+            diff.engine,
+            diff.category,
+            diff.metric,
+            diff.baseline_text.as_deref().unwrap_or("(missing)"),
+            diff.new_text.as_deref().unwrap_or("(missing)"),
+            delta,
+            relative_delta,
+        );
+    }
+}
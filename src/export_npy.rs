@@ -0,0 +1,129 @@
+use std::{fs::File, io::Write, path::Path};
+
+use jane_eyre::eyre;
+use serde::Serialize;
+use tracing::info;
+
+use crate::{report::read_analysed_summaries, study::Study, summary::EventKind};
+
+/// One entry in `npy/manifest.json`, describing one `.npy` file.
+#[derive(Debug, Serialize)]
+struct JsonNpyFile {
+    /// Path to the `.npy` file, relative to the `npy` directory.
+    path: String,
+    cpu_config: String,
+    site: String,
+    engine: String,
+    kind: EventKind,
+    metric: String,
+}
+
+pub fn main(args: Vec<String>) -> eyre::Result<()> {
+    let study_dir = Path::new(
+        args.iter()
+            .nth(0)
+            .expect("Usage: export-npy <studies/example>"),
+    );
+    let study = Study::load(study_dir.join("study.toml"))?;
+
+    // Change working directory to the study directory.
+    std::env::set_current_dir(study_dir)?;
+
+    let mut manifest = vec![];
+    for (cpu_config, site, engine, summaries) in read_analysed_summaries(&study, Path::new("."))? {
+        let sample_dir = Path::new(cpu_config.key).join(site.key).join(engine.key);
+        let npy_dir = Path::new("npy").join(&sample_dir);
+        std::fs::create_dir_all(&npy_dir)?;
+        for series in summaries.raw_series {
+            let relative_path = sample_dir.join(format!(
+                "{}.{}.npy",
+                series.kind,
+                escape_file_name(&series.name)
+            ));
+            let npy_path = Path::new("npy").join(&relative_path);
+            info!(?npy_path, "Writing raw series");
+            File::create(npy_path)?.write_all(&npy_f64(&series.xs))?;
+            manifest.push(JsonNpyFile {
+                path: relative_path.to_string_lossy().into_owned(),
+                cpu_config: cpu_config.key.to_owned(),
+                site: site.key.to_owned(),
+                engine: engine.key.to_owned(),
+                kind: series.kind,
+                metric: series.name,
+            });
+        }
+    }
+
+    std::fs::create_dir_all("npy")?;
+    File::create(Path::new("npy").join("manifest.json"))?
+        .write_all(serde_json::to_string_pretty(&manifest)?.as_bytes())?;
+
+    Ok(())
+}
+
+/// Escapes a metric name for use as part of a file name, so names with slashes, dots, or other
+/// special characters can neither escape the sample directory nor collide with each other.
+/// Bytes other than ASCII letters, digits, `-`, and `_` become `%XX`, as in URLs.
+fn escape_file_name(name: &str) -> String {
+    let mut result = String::new();
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'-' || byte == b'_' {
+            result.push(byte as char);
+        } else {
+            result.push_str(&format!("%{byte:02X}"));
+        }
+    }
+
+    result
+}
+
+#[test]
+fn test_escape_file_name() {
+    assert_eq!(escape_file_name("TimerFire"), "TimerFire");
+    assert_eq!(escape_file_name("../a/b"), "%2E%2E%2Fa%2Fb");
+    assert_eq!(escape_file_name("Layout 100%"), "Layout%20100%25");
+    assert_eq!(escape_file_name("é"), "%C3%A9");
+}
+
+/// Encodes the given values as a one-dimensional array of little-endian float64, in the NumPy
+/// `.npy` format version 1.0.
+/// <https://numpy.org/doc/stable/reference/generated/numpy.lib.format.html>
+fn npy_f64(xs: &[f64]) -> Vec<u8> {
+    let mut header = format!(
+        "{{'descr': '<f8', 'fortran_order': False, 'shape': ({},), }}",
+        xs.len()
+    );
+    // Pad the header with spaces and a newline, such that the data is 64-byte aligned.
+    let prefix_len = b"\x93NUMPY".len() + 2 + 2;
+    while (prefix_len + header.len() + 1) % 64 != 0 {
+        header.push(' ');
+    }
+    header.push('\n');
+
+    let mut result = vec![];
+    result.extend(b"\x93NUMPY");
+    result.extend([1, 0]);
+    result.extend((header.len() as u16).to_le_bytes());
+    result.extend(header.as_bytes());
+    for x in xs {
+        result.extend(x.to_le_bytes());
+    }
+
+    result
+}
+
+#[test]
+fn test_npy_f64() {
+    let result = npy_f64(&[1.0, 0.5]);
+    assert_eq!(&result[..8], b"\x93NUMPY\x01\x00");
+    let header_len = u16::from_le_bytes([result[8], result[9]]) as usize;
+    assert_eq!((10 + header_len) % 64, 0);
+    let header = std::str::from_utf8(&result[10..10 + header_len]).unwrap();
+    assert!(header.starts_with("{'descr': '<f8', 'fortran_order': False, 'shape': (2,), }"));
+    assert!(header.ends_with("\n"));
+    assert_eq!(
+        &result[10 + header_len..10 + header_len + 8],
+        1.0f64.to_le_bytes()
+    );
+    assert_eq!(&result[10 + header_len + 8..], 0.5f64.to_le_bytes());
+}
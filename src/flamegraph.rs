@@ -0,0 +1,339 @@
+use std::{
+    collections::BTreeMap, fmt::Write as _, fs::File, io::Write, path::Path, time::Duration,
+};
+
+use jane_eyre::eyre::{self, bail};
+use tracing::{info, warn};
+
+use crate::{
+    analyse::{expand_input_globs, sample_input_paths},
+    servo::MetricAnchor,
+    study::{Engine, KeyedCpuConfig, KeyedEngine, KeyedSite, Study},
+    summary::{fmt_seconds_short, Analysis, Event, EventNode, Individual, RepresentativeStatistic},
+};
+
+/// The synthetic event used to choose the representative individual in each sample.
+static REPRESENTATIVE_SYNTHETIC_NAME: &str = "Renderer";
+static FRAME_HEIGHT: usize = 16;
+static WIDTH: f64 = 1200.0;
+
+pub fn main(args: Vec<String>) -> eyre::Result<()> {
+    if args.first().is_some_and(|arg| arg == "servo") {
+        return single_servo_sample_main(args[1..].to_vec());
+    }
+
+    let study_dir = Path::new(
+        args.iter()
+            .nth(0)
+            .expect("Usage: flamegraph <studies/example>"),
+    );
+    let study = Study::load(study_dir.join("study.toml"))?;
+
+    // Change working directory to the study directory.
+    // We need this for `traceconv_command` and `isolate_cpu_command`.
+    std::env::set_current_dir(study_dir)?;
+
+    for cpu_config in study.cpu_configs() {
+        for site in study.sites() {
+            for engine in study.analysed_engines() {
+                create_flamegraph(&study, cpu_config, site, engine)?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints a flamegraph for a single Servo sample to stdout, without needing a study.
+fn single_servo_sample_main(args: Vec<String>) -> eyre::Result<()> {
+    let args = expand_input_globs(args)?;
+    let Some(url) = args.first() else {
+        bail!("Usage: flamegraph servo <page url> <manifest.json ...>");
+    };
+    let individuals = crate::servo::analyse_individuals(
+        &args,
+        &MetricAnchor::default(),
+        &[],
+        None,
+        &crate::servo::default_category_names(),
+    )?
+    .into_iter()
+    .map(|s| Box::new(s) as Box<dyn Individual>)
+    .collect::<Vec<_>>();
+
+    let analysis = Analysis { individuals };
+    // There is no study, so use the default statistic.
+    let Some(individual) = representative_individual(&analysis, RepresentativeStatistic::default())
+    else {
+        bail!("No representative individual");
+    };
+    info!(individual = individual.path(), "Rendering flamegraph");
+    print!("{}", render_flamegraph(url, &individual.real_events()?)?);
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "error", skip(study, cpu_config, site, engine), fields(cpu_config = cpu_config.key, site = site.key, engine = engine.key))]
+fn create_flamegraph(
+    study: &Study,
+    cpu_config: KeyedCpuConfig<'_>,
+    site: KeyedSite<'_>,
+    engine: KeyedEngine<'_>,
+) -> eyre::Result<()> {
+    let sample_dir = Path::new(cpu_config.key).join(site.key).join(engine.key);
+    let mut args = vec![site.analysis_url().to_owned()];
+    args.extend(sample_input_paths(&sample_dir)?);
+
+    let individuals = match engine.engine {
+        Engine::Servo { .. } => crate::servo::analyse_individuals(
+            &args,
+            &study.metric_anchor(),
+            &study.annotation_filters,
+            study.max_plausible_event_duration(),
+            &study.category_names(engine.key, crate::servo::default_category_names()),
+        )?
+        .into_iter()
+        .map(|s| Box::new(s) as Box<dyn Individual>)
+        .collect::<Vec<_>>(),
+        Engine::Chromium { .. } | Engine::ChromeDriver { .. } => {
+            crate::chromium::analyse_individuals(
+                &args,
+                study.frame_lifetime_only,
+                study.metric_basis,
+                study.max_plausible_event_duration(),
+                &study.category_names(engine.key, crate::chromium::default_category_names()),
+            )?
+            .into_iter()
+            .map(|s| Box::new(s) as Box<dyn Individual>)
+            .collect::<Vec<_>>()
+        }
+        Engine::WebKit { .. } => {
+            crate::webkit::analyse_individuals(&args, study.max_plausible_event_duration())?
+                .into_iter()
+                .map(|s| Box::new(s) as Box<dyn Individual>)
+                .collect::<Vec<_>>()
+        }
+        Engine::GeckoDriver { .. } => {
+            unreachable!("Guaranteed by KeyedEngine::has_summaries")
+        }
+        Engine::RemoteWebDriver { .. } => {
+            unreachable!("Guaranteed by KeyedEngine::has_summaries")
+        }
+    };
+
+    let analysis = Analysis { individuals };
+    let Some(individual) =
+        representative_individual(&analysis, study.summary_options().representative)
+    else {
+        warn!("No representative individual; skipping");
+        return Ok(());
+    };
+
+    let flamegraph_path = sample_dir.join("flamegraph.svg");
+    info!(
+        individual = individual.path(),
+        ?flamegraph_path,
+        "Writing flamegraph"
+    );
+    let svg = render_flamegraph(
+        &format!("{} {} {}", cpu_config.key, site.key, engine.key),
+        &individual.real_events()?,
+    )?;
+    File::create(flamegraph_path)?.write_all(svg.as_bytes())?;
+
+    Ok(())
+}
+
+/// Returns the individual whose total time in [`REPRESENTATIVE_SYNTHETIC_NAME`] is closest to the
+/// given statistic of all of the individuals.
+fn representative_individual(
+    analysis: &Analysis<Box<dyn Individual>>,
+    statistic: RepresentativeStatistic,
+) -> Option<&dyn Individual> {
+    analysis
+        .representative_individual(statistic, |individual| {
+            let events = match individual.synthetic_events() {
+                Ok(events) => events,
+                Err(error) => {
+                    warn!(?error, "Failed to get synthetic events");
+                    return None;
+                }
+            };
+            let total = events
+                .iter()
+                .filter(|e| e.name == REPRESENTATIVE_SYNTHETIC_NAME)
+                .flat_map(|e| e.duration)
+                .sum::<Duration>();
+            Some(total.as_secs_f64())
+        })
+        .map(|individual| &**individual)
+}
+
+/// One frame in a flamegraph, with the total time of all events with the same stack.
+#[derive(Debug, Default, PartialEq)]
+struct Frame {
+    total: Duration,
+    children: BTreeMap<String, Frame>,
+}
+
+/// Folds the given events into a tree of frames, nesting them with [Event::build_tree], so that
+/// events on different tracks (such as threads) are never nested in each other. Instantaneous
+/// events are ignored.
+fn fold_events(events: &[Event]) -> Frame {
+    fn fold(node: &EventNode, parent: &mut Frame) {
+        let frame = parent.children.entry(node.event.name.clone()).or_default();
+        frame.total += node.event.duration.unwrap_or_default();
+        for child in node.children.iter() {
+            fold(child, frame);
+        }
+    }
+
+    let mut root = Frame::default();
+    for node in Event::build_tree(events.iter().filter(|e| e.duration.is_some())) {
+        fold(&node, &mut root);
+    }
+    root.total = root.children.values().map(|f| f.total).sum();
+
+    root
+}
+
+#[test]
+fn test_fold_events() {
+    use perfetto_protos::debug_annotation::DebugAnnotation;
+
+    let event = |name: &str, start: u64, duration: u64| Event {
+        name: name.to_owned(),
+        start: Duration::from_secs(start),
+        duration: Some(Duration::from_secs(duration)),
+        metadata: BTreeMap::default(),
+    };
+    let frame = |total: u64, children: Vec<(&str, Frame)>| Frame {
+        total: Duration::from_secs(total),
+        children: children
+            .into_iter()
+            .map(|(name, frame)| (name.to_owned(), frame))
+            .collect(),
+    };
+    let result = fold_events(&[
+        event("a", 0, 10),
+        event("b", 1, 2),
+        event("b", 4, 2),
+        // Overlaps `a` without nesting, so it becomes a sibling of `a`.
+        event("c", 8, 4),
+    ]);
+    assert_eq!(
+        result,
+        frame(
+            14,
+            vec![
+                ("a", frame(10, vec![("b", frame(4, vec![]))])),
+                ("c", frame(4, vec![])),
+            ],
+        ),
+    );
+
+    // Events on another track are never nested in events on this track.
+    let mut raster = event("RasterTask", 1, 2);
+    let mut annotation = DebugAnnotation::new();
+    annotation.set_name("track".to_owned());
+    annotation.set_string_value("raster".to_owned());
+    raster.metadata.insert("track".to_owned(), annotation);
+    let result = fold_events(&[event("a", 0, 10), raster]);
+    assert_eq!(
+        result,
+        frame(
+            12,
+            vec![("RasterTask", frame(2, vec![])), ("a", frame(10, vec![]))],
+        ),
+    );
+}
+
+fn render_flamegraph(title: &str, events: &[Event]) -> eyre::Result<String> {
+    let root = fold_events(events);
+    let depth = max_depth(&root);
+    let height = (depth + 2) * FRAME_HEIGHT;
+    let scale = if root.total.is_zero() {
+        0.0
+    } else {
+        WIDTH / root.total.as_secs_f64()
+    };
+
+    let mut result = String::default();
+    writeln!(
+        result,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{height}" font-family="monospace" font-size="12">"#,
+    )?;
+    writeln!(
+        result,
+        r#"<text x="0" y="{}">{}</text>"#,
+        FRAME_HEIGHT - 4,
+        escape_xml(title),
+    )?;
+    render_frames(&mut result, &root, 0.0, 0, height, scale)?;
+    writeln!(result, "</svg>")?;
+
+    Ok(result)
+}
+
+fn render_frames(
+    result: &mut String,
+    frame: &Frame,
+    x: f64,
+    depth: usize,
+    height: usize,
+    scale: f64,
+) -> eyre::Result<()> {
+    let mut x = x;
+    for (name, child) in frame.children.iter() {
+        let width = child.total.as_secs_f64() * scale;
+        let y = height - (depth + 1) * FRAME_HEIGHT;
+        let label = format!("{name} ({})", fmt_seconds_short(child.total.as_secs_f64()));
+        writeln!(
+            result,
+            r#"<g><title>{}</title><rect x="{x:.2}" y="{y}" width="{width:.2}" height="{}" fill="{}" stroke="white"/>"#,
+            escape_xml(&label),
+            FRAME_HEIGHT,
+            colour(name),
+        )?;
+        // Only label frames that are wide enough for some text.
+        if width > 30.0 {
+            writeln!(
+                result,
+                r#"<svg x="{:.2}" y="{y}" width="{:.2}" height="{}"><text x="0" y="{}">{}</text></svg>"#,
+                x + 2.0,
+                width - 4.0,
+                FRAME_HEIGHT,
+                FRAME_HEIGHT - 4,
+                escape_xml(&label),
+            )?;
+        }
+        writeln!(result, "</g>")?;
+        render_frames(result, child, x, depth + 1, height, scale)?;
+        x += width;
+    }
+
+    Ok(())
+}
+
+fn max_depth(frame: &Frame) -> usize {
+    frame
+        .children
+        .values()
+        .map(|child| 1 + max_depth(child))
+        .max()
+        .unwrap_or(0)
+}
+
+/// Warm colours in the usual flamegraph style, stable for a given event name.
+fn colour(name: &str) -> String {
+    let hash = name.bytes().fold(0u32, |hash, byte| {
+        hash.wrapping_mul(31).wrapping_add(byte.into())
+    });
+    format!("hsl({}, 80%, {}%)", hash % 50, 55 + hash / 50 % 15)
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace("&", "&amp;")
+        .replace("<", "&lt;")
+        .replace(r#"""#, "&quot;")
+}
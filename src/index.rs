@@ -0,0 +1,80 @@
+use std::{collections::BTreeMap, fs::File, io::Write, path::Path};
+
+use jane_eyre::eyre::{self, OptionExt};
+use serde::{Deserialize, Serialize};
+use tracing::info;
+
+use crate::{report::read_analysed_summaries, study::Study};
+
+/// Version of the `index.jsonl` schema. Increment this whenever [`JsonIndexEntry`] changes in a
+/// way that would break readers, such as renaming or removing a field.
+pub static INDEX_SCHEMA_VERSION: usize = 1;
+
+/// One line of `index.jsonl`, for one individual in one sample of one study.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonIndexEntry {
+    pub schema_version: usize,
+    /// Name of the study directory, relative to the directory of studies.
+    pub study: String,
+    pub cpu_config: String,
+    pub site: String,
+    pub engine: String,
+    /// Path to the individual’s input file, relative to the study directory.
+    pub individual: String,
+    /// Total duration of each synthetic or interpreted event, in seconds, such as `Renderer` or
+    /// `FCP`. Events that did not occur in this individual are omitted, rather than zero.
+    pub metrics: BTreeMap<String, f64>,
+}
+
+pub fn main(args: Vec<String>) -> eyre::Result<()> {
+    let studies_dir = Path::new(args.iter().nth(0).expect("Usage: index <studies>"));
+
+    let mut study_dirs = vec![];
+    for entry in std::fs::read_dir(studies_dir)? {
+        let path = entry?.path();
+        if std::fs::exists(path.join("study.toml"))? {
+            study_dirs.push(path);
+        }
+    }
+    study_dirs.sort();
+
+    let mut result = String::default();
+    for study_dir in study_dirs {
+        let study_key = study_dir
+            .file_name()
+            .and_then(|name| name.to_str())
+            .ok_or_eyre("Unsupported path")?;
+        for entry in index_study(study_key, &study_dir)? {
+            result += &serde_json::to_string(&entry)?;
+            result += "\n";
+        }
+    }
+
+    let index_path = studies_dir.join("index.jsonl");
+    info!(?index_path, "Writing index");
+    File::create(index_path)?.write_all(result.as_bytes())?;
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "error", skip(study_dir))]
+fn index_study(study_key: &str, study_dir: &Path) -> eyre::Result<Vec<JsonIndexEntry>> {
+    let study = Study::load(study_dir.join("study.toml"))?;
+
+    let mut result = vec![];
+    for (cpu_config, site, engine, summaries) in read_analysed_summaries(&study, study_dir)? {
+        for individual in summaries.individuals {
+            result.push(JsonIndexEntry {
+                schema_version: INDEX_SCHEMA_VERSION,
+                study: study_key.to_owned(),
+                cpu_config: cpu_config.key.to_owned(),
+                site: site.key.to_owned(),
+                engine: engine.key.to_owned(),
+                individual: individual.path,
+                metrics: individual.synthetic_and_interpreted_events,
+            });
+        }
+    }
+
+    Ok(result)
+}
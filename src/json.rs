@@ -23,6 +23,9 @@ pub struct TraceEvent {
     pub cat: String,
     pub pid: usize,
     pub tid: isize,
+    /// Pairs up async events (`b`, `e`, and `n`). Usually a string like `"0x1"`, but may be
+    /// a number in some traces.
+    pub id: Option<Value>,
     pub args: BTreeMap<String, Value>,
     #[serde(flatten)]
     pub _rest: BTreeMap<String, Value>,
@@ -0,0 +1,42 @@
+use std::path::Path;
+
+use crate::{report::read_analysed_summaries, study::Study, summary::EventKind};
+use jane_eyre::eyre;
+use serde::Serialize;
+
+/// One line of output, for one value in a raw series.
+#[derive(Debug, Serialize)]
+struct JsonSampleValue<'a> {
+    cpu_config: &'a str,
+    site: &'a str,
+    engine: &'a str,
+    kind: EventKind,
+    name: &'a str,
+    value: f64,
+}
+
+pub fn main(args: Vec<String>) -> eyre::Result<()> {
+    let study_dir = Path::new(args.iter().nth(0).expect("Usage: jsonl <studies/example>"));
+    let study = Study::load(study_dir.join("study.toml"))?;
+
+    // Change working directory to the study directory.
+    std::env::set_current_dir(study_dir)?;
+
+    for (cpu_config, site, engine, summaries) in read_analysed_summaries(&study, Path::new("."))? {
+        for series in summaries.raw_series {
+            for &value in series.xs.iter() {
+                let line = JsonSampleValue {
+                    cpu_config: cpu_config.key,
+                    site: site.key,
+                    engine: engine.key,
+                    kind: series.kind,
+                    name: &series.name,
+                    value,
+                };
+                println!("{}", serde_json::to_string(&line)?);
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,4 @@
+//! Parts of the analysis that are useful to other tools, such as the interpretation of
+//! engine-specific events as synthetic phases ([summary::Event::generate_merged_events]).
+
+pub mod summary;
@@ -2,22 +2,39 @@ mod analyse;
 mod chromium;
 mod collect;
 mod combined;
+mod csv;
+mod diff;
 mod dom;
+mod export_npy;
+mod flamegraph;
+mod index;
 mod json;
+mod jsonl;
+mod profile;
 mod report;
 mod servo;
 mod shell;
 mod study;
-mod summary;
+mod url_pattern;
+mod verify;
+mod webkit;
 
 use std::env::args;
 
+use ::analyse::summary;
 use jane_eyre::eyre::{self, bail};
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
 
 fn main() -> eyre::Result<()> {
     jane_eyre::install()?;
+    let mode = args().nth(1).unwrap();
+    let args = args().skip(2).collect::<Vec<_>>();
+
+    // Only pay for span timing if `analyse --profile` needs it.
+    let profile_layer = (mode == "analyse" && args.iter().any(|arg| arg == "--profile"))
+        .then_some(crate::profile::ProfileLayer);
     tracing_subscriber::registry()
+        .with(profile_layer)
         .with(tracing_subscriber::fmt::layer().with_writer(std::io::stderr))
         .with(
             EnvFilter::builder()
@@ -26,22 +43,36 @@ fn main() -> eyre::Result<()> {
         )
         .init();
 
-    let mode = args().nth(1).unwrap();
-    let args = args().skip(2).collect::<Vec<_>>();
-
     match &*mode {
         // Usage: collect <studies/example>
         "collect" => crate::collect::main(args),
-        // Usage: analyse <studies/example>
+        // Usage: analyse <studies/example> [--profile] [--allow-failures]
         "analyse" => crate::analyse::main(args),
-        // Usage: report <studies/example>
+        // Usage: report <studies/example> [--format html|markdown] [--relative-to <metric>] [--strict] [--only-changed] [--compare-phases] [--units auto|s|ms|us|ns]
         "report" => crate::report::main(args),
+        // Usage: verify <studies/example>
+        "verify" => crate::verify::main(args),
+        // Usage: flamegraph <studies/example>
+        // Usage: flamegraph servo <page url> <manifest.json ...>
+        "flamegraph" => crate::flamegraph::main(args),
+        // Usage: diff <baseline_study_dir> <new_study_dir> [--threshold <fraction>]
+        "diff" => crate::diff::main(args),
+        // Usage: csv <studies/example>
+        "csv" => crate::csv::main(args),
+        // Usage: export-npy <studies/example>
+        "export-npy" => crate::export_npy::main(args),
+        // Usage: jsonl <studies/example>
+        "jsonl" => crate::jsonl::main(args),
+        // Usage: index <studies>
+        "index" => crate::index::main(args),
 
-        // Usage: analyse servo <trace.html ...>
+        // Usage: analyse servo [--all-frames] <page url> <manifest.json ...>
         "servo" => crate::servo::main(args),
         // Usage: analyse chromium <page url> <chrome.json ...>
         "chromium" => crate::chromium::main(args),
-        // Usage: analyse combined servo <trace.html ...> -- chromium <chrome.json ...>
+        // Usage: analyse webkit <page url> <webkit.json ...>
+        "webkit" => crate::webkit::main(args),
+        // Usage: analyse combined [--representative <metric> [--statistic min|median|mean]] [--format chromium|firefox] servo [--anchor <event>] <trace.html ...> -- chromium [--anchor <event>] <chrome.json ...>
         "combined" => crate::combined::main(args),
 
         other => bail!("Unknown command: {other}"),
@@ -0,0 +1,92 @@
+use std::{
+    collections::BTreeMap,
+    sync::{LazyLock, Mutex},
+    thread::ThreadId,
+    time::{Duration, Instant},
+};
+
+use tracing::{span, Subscriber};
+use tracing_subscriber::{layer::Context, registry::LookupSpan, Layer};
+
+use crate::summary::fmt_seconds;
+
+/// Total time spent in each kind of span, keyed by (target, name), for `analyse --profile`.
+static PROFILE: LazyLock<Mutex<BTreeMap<(&'static str, &'static str), SpanTimes>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::default()));
+
+#[derive(Debug, Default)]
+struct SpanTimes {
+    count: usize,
+    total: Duration,
+}
+
+/// Layer that records how long each span is entered for, such as those created by our
+/// `#[tracing::instrument]` attributes. Time in nested spans counts towards their parents too.
+pub struct ProfileLayer;
+
+/// When each thread that is currently in a span entered it, innermost last. A span can be entered
+/// by several threads at once, or more than once by the same thread, so one `Instant` is not enough.
+#[derive(Debug)]
+struct EnterTimes(Vec<(ThreadId, Instant)>);
+
+impl<S: Subscriber + for<'lookup> LookupSpan<'lookup>> Layer<S> for ProfileLayer {
+    fn on_enter(&self, id: &span::Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let entry = (std::thread::current().id(), Instant::now());
+            let mut extensions = span.extensions_mut();
+            if let Some(enter_times) = extensions.get_mut::<EnterTimes>() {
+                enter_times.0.push(entry);
+            } else {
+                extensions.insert(EnterTimes(vec![entry]));
+            }
+        }
+    }
+
+    fn on_exit(&self, id: &span::Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else {
+            return;
+        };
+        let thread_id = std::thread::current().id();
+        let start = {
+            let mut extensions = span.extensions_mut();
+            let Some(enter_times) = extensions.get_mut::<EnterTimes>() else {
+                return;
+            };
+            let Some(index) = enter_times
+                .0
+                .iter()
+                .rposition(|&(thread, _)| thread == thread_id)
+            else {
+                return;
+            };
+            enter_times.0.remove(index).1
+        };
+        let metadata = span.metadata();
+        if let Ok(mut profile) = PROFILE.lock() {
+            let times = profile
+                .entry((metadata.target(), metadata.name()))
+                .or_default();
+            times.count += 1;
+            times.total += start.elapsed();
+        }
+    }
+}
+
+/// Prints the time spent in each kind of span, longest first.
+pub fn print_profile() {
+    let Ok(profile) = PROFILE.lock() else {
+        return;
+    };
+    let mut rows = profile.iter().collect::<Vec<_>>();
+    rows.sort_by(|p, q| q.1.total.cmp(&p.1.total));
+
+    println!(">>> Time spent in each span (including nested spans)");
+    for ((target, name), times) in rows {
+        println!(
+            "{target}::{name}: {} total, {} mean, n={}",
+            fmt_seconds(times.total.as_secs_f64()),
+            fmt_seconds(times.total.as_secs_f64() / times.count as f64),
+            times.count,
+        );
+    }
+}
@@ -1,8 +1,16 @@
 use core::str;
-use std::{collections::BTreeMap, fs::File, io::Write, path::Path};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Write as _,
+    fs::File,
+    hash::Hasher,
+    io::{BufWriter, Write},
+    path::Path,
+};
 
+use askama::Template;
 use dataurl::DataUrl;
-use jane_eyre::eyre::{self, bail, eyre, OptionExt};
+use jane_eyre::eyre::{self, bail, eyre, Context, OptionExt};
 use poloto::{
     num::float::{FloatFmt, FloatTickFmt},
     ticks::{
@@ -10,28 +18,93 @@ use poloto::{
         IndexRequester, RenderFrameBound, TickDistGen, TickDistribution, TickRes,
     },
 };
-use rand::Rng;
-use tracing::info;
+use rand::{rngs::StdRng, Rng, SeedableRng};
+use tracing::{info, warn};
 
 use crate::{
+    analyse::Fnv1aHasher,
     shell::SHELL,
     study::{Engine, KeyedCpuConfig, KeyedEngine, KeyedSite, Study},
-    summary::{fmt_seconds_short, EventKind, JsonRawSeries, JsonSummaries, JsonSummary, Summary},
+    summary::{
+        fmt_count, fmt_seconds, fmt_seconds_in, fmt_seconds_offset, fmt_seconds_short,
+        geometric_mean, Analysis, EventKind, JsonIndividual, JsonRawSeries, JsonSummaries,
+        JsonSummary, MetricBasis, RepresentativeStatistic, Summary, Units,
+        SUMMARIES_SCHEMA_VERSION,
+    },
 };
 
 static USER_FACING_PAINT_METRICS: &str = "FP FCP";
 static REAL_SERVO_EVENTS: &str = "Compositing LayoutPerform ScriptEvaluate ScriptParseHTML";
-static REAL_CHROMIUM_EVENTS: &str = "EvaluateScript FunctionCall Layerize Layout Paint ParseHTML PrePaint TimerFire UpdateLayoutTree";
+static REAL_CHROMIUM_EVENTS: &str = "ActivateLayerTree Commit EvaluateScript FunctionCall Layerize Layout Paint ParseHTML PrePaint RasterTask TimerFire UpdateLayoutTree";
 static RENDERING_PHASES_MODEL_EVENTS: &str = "Parse Script Layout Rasterise";
 static OVERALL_RENDERING_TIME_MODEL_EVENTS: &str = "Renderer";
+static LONG_TASK_EVENTS: &str = "LongTasks";
+static LONG_TASK_COUNTS: &str = "LongTaskCount";
+/// Cells whose coefficient of variation exceeds this are highlighted as too noisy to rely on.
+static NOISY_CV_THRESHOLD: f64 = 0.15;
 
 pub fn main(args: Vec<String>) -> eyre::Result<()> {
     let study_dir = Path::new(
         args.iter()
             .nth(0)
-            .expect("Usage: analyse <studies/example>"),
+            .expect("Usage: report <studies/example> [--format html|markdown] [--relative-to <metric>] [--strict] [--only-changed] [--compare-phases] [--error-bars] [--units auto|s|ms|us|ns] [--out <report.html>]"),
     );
     let study = Study::load(study_dir.join("study.toml"))?;
+    let mut format = "html";
+    let mut relative_to = None;
+    let mut strict = false;
+    let mut only_changed = false;
+    let mut compare_phases = false;
+    let mut error_bars = false;
+    let mut units = Units::Auto;
+    let mut out_path = None;
+    let mut options = args[1..].iter();
+    while let Some(option) = options.next() {
+        match &**option {
+            "--format" => {
+                format = &**options.next().ok_or_eyre("--format needs a format")?;
+            }
+            "--relative-to" => {
+                relative_to = Some(&**options.next().ok_or_eyre("--relative-to needs a metric")?);
+            }
+            "--strict" => strict = true,
+            "--only-changed" => only_changed = true,
+            "--compare-phases" => compare_phases = true,
+            "--error-bars" => error_bars = true,
+            "--units" => {
+                units = match &**options.next().ok_or_eyre("--units needs a unit")? {
+                    "auto" => Units::Auto,
+                    "s" => Units::Seconds,
+                    "ms" => Units::Milliseconds,
+                    "us" | "μs" => Units::Microseconds,
+                    "ns" => Units::Nanoseconds,
+                    other => bail!("Unknown unit: {other}"),
+                };
+            }
+            "--out" => {
+                out_path = Some(Path::new(options.next().ok_or_eyre("--out needs a path")?));
+            }
+            other => bail!("Unknown option: {other}"),
+        }
+    }
+
+    // Write the report to stdout, or with `--out`, to a single self-contained file. Create the file
+    // before changing directory, so that relative paths are relative to where we were run.
+    let mut output: Box<dyn Write> = match out_path {
+        Some(out_path) => Box::new(BufWriter::new(
+            File::create(out_path).wrap_err_with(|| format!("Failed to create {out_path:?}"))?,
+        )),
+        None => Box::new(std::io::stdout().lock()),
+    };
+    let out = &mut *output;
+    // The plots are embedded in the report as data URLs, so with `--out`, there is no need to also
+    // write them to separate files.
+    let plot_options = PlotOptions {
+        only_changed,
+        error_bars,
+        units,
+        write_plot_files: out_path.is_none(),
+    };
 
     // Change working directory to the study directory.
     // We need this for `traceconv_command` and `isolate_cpu_command`.
@@ -39,11 +112,34 @@ pub fn main(args: Vec<String>) -> eyre::Result<()> {
 
     let mut real_events_map = BTreeMap::default();
     let mut synthetic_and_interpreted_events_map = BTreeMap::default();
+    let mut counters_map = BTreeMap::default();
     let mut raw_series_map = BTreeMap::default();
+    let mut individuals_map = BTreeMap::default();
     for cpu_config in study.cpu_configs() {
         for site in study.sites() {
-            for engine in study.engines() {
-                let summaries = load_summaries(cpu_config, site, engine)?;
+            for engine in study.analysed_engines() {
+                // Skip samples we can’t load, so that `check_consistency` can report them below.
+                let summaries = match load_summaries(cpu_config, site, engine) {
+                    Ok(summaries) => summaries,
+                    Err(error) => {
+                        warn!(
+                            cpu_config = cpu_config.key,
+                            site = site.key,
+                            engine = engine.key,
+                            ?error,
+                            "Failed to load summaries; skipping"
+                        );
+                        continue;
+                    }
+                };
+                for note in summaries.notes.iter() {
+                    warn!(
+                        cpu_config = cpu_config.key,
+                        site = site.key,
+                        engine = engine.key,
+                        "{note}"
+                    );
+                }
                 real_events_map.insert(
                     (cpu_config.key, site.key, engine.key),
                     summaries.real_events,
@@ -52,14 +148,63 @@ pub fn main(args: Vec<String>) -> eyre::Result<()> {
                     (cpu_config.key, site.key, engine.key),
                     summaries.synthetic_and_interpreted_events,
                 );
+                counters_map.insert((cpu_config.key, site.key, engine.key), summaries.counters);
                 raw_series_map.insert((cpu_config.key, site.key, engine.key), summaries.raw_series);
+                individuals_map.insert(
+                    (cpu_config.key, site.key, engine.key),
+                    summaries.individuals,
+                );
             }
         }
     }
 
-    // Print the tooling version, engine keys, and engine descriptions.
-    // FIXME: Use askama to avoid having to escape HTML manually.
-    println!("<ul>");
+    // Check that every engine has data for every site and metric, before we present the results
+    // as a complete comparison.
+    let problems = [&real_events_map, &synthetic_and_interpreted_events_map]
+        .into_iter()
+        .flat_map(|summaries_map| check_consistency(&study, summaries_map))
+        .collect::<Vec<_>>();
+    for problem in problems.iter() {
+        warn!("{problem}");
+    }
+    if strict && !problems.is_empty() {
+        bail!("Found {} consistency problems (see above)", problems.len());
+    }
+
+    match format {
+        "html" => {}
+        "markdown" => {
+            print_markdown_report(
+                out,
+                &study,
+                &real_events_map,
+                &synthetic_and_interpreted_events_map,
+                &counters_map,
+                units,
+            )?;
+            out.flush()?;
+            return Ok(());
+        }
+        other => bail!("Unknown format: {other}"),
+    }
+
+    // Work out the sections in advance, so we can print a table of contents and an overview of
+    // every metric.
+    let sections = report_sections(
+        &study,
+        &real_events_map,
+        &synthetic_and_interpreted_events_map,
+        &counters_map,
+        relative_to,
+        compare_phases,
+    );
+    let headings = sections
+        .iter()
+        .map(ReportSection::heading)
+        .collect::<Vec<_>>();
+
+    // Print the tooling version, engine keys, engine descriptions, study config file, and the
+    // table of contents.
     let version = SHELL
         .lock()
         .map_err(|e| eyre!("Mutex poisoned: {e:?}"))?
@@ -74,118 +219,84 @@ pub fn main(args: Vec<String>) -> eyre::Result<()> {
     let version = str::from_utf8(&version.stdout)?
         .strip_suffix("\n")
         .ok_or_eyre("Output has no trailing newline")?;
-    println!(
-        r#"<li><a href="https://github.com/servo/perf-analysis-tools">perf-analysis-tools</a> version:"#,
-    );
-    println!(
-        r#"<a href="https://github.com/servo/perf-analysis-tools/commit/{}">{}</a>"#,
-        escape_html_for_attribute(version),
-        escape_html_for_inner_html(version),
-    );
-    for engine in study.engines() {
-        print!(
-            "<li><strong>{}</strong> = ",
-            escape_html_for_inner_html(engine.key),
-        );
-        if let Some(description) = engine.description() {
-            // HTML is allowed here.
-            println!("{}", description);
+    let mut engines = vec![];
+    for engine in study.analysed_engines() {
+        let version = if engine.version_command().is_some() {
+            load_engine_version(&study, engine)?
         } else {
-            println!(
-                "<code>{}</code> at <code>{}</code>",
-                escape_html_for_inner_html(engine.type_name()),
-                escape_html_for_inner_html(engine.browser_path()),
-            );
-        }
-    }
-    println!("</ul>");
-    println!();
-
-    // Print the study config file.
-    println!("<details><summary>study.toml</summary>\n");
-    println!(
-        "<pre><code>{}</code></pre>",
-        escape_html_for_inner_html(&study.source_toml),
-    );
-    println!("</details>");
-    println!();
-
-    // Print sections for user-facing paint metrics.
-    for summary_key in USER_FACING_PAINT_METRICS.split(" ") {
-        println!("<h3>{summary_key} (synthetic)</h3>\n");
-        print_section(
-            &study,
-            &raw_series_map,
-            &synthetic_and_interpreted_events_map,
-            EventKind::SyntheticOrInterpreted,
-            summary_key,
-        )?;
-    }
-
-    // If there were any Servo results, print sections for real Servo events.
-    if study
-        .engines()
-        .find(|engine| matches!(engine.engine, Engine::Servo { .. }))
-        .is_some()
-    {
-        for summary_key in REAL_SERVO_EVENTS.split(" ") {
-            println!("<h3>{summary_key} (real)</h3>\n");
-            print_section(
-                &study,
-                &raw_series_map,
-                &real_events_map,
-                EventKind::Servo,
-                summary_key,
-            )?;
-        }
+            None
+        };
+        engines.push(EngineListItem {
+            key: engine.key,
+            description: engine.description(),
+            type_name: engine.type_name(),
+            browser_path: engine.browser_path(),
+            has_version_command: engine.version_command().is_some(),
+            version,
+        });
     }
+    let header = ReportHeaderTemplate {
+        version,
+        metric_basis: study.metric_basis,
+        metric_anchor: study.metric_anchor().name,
+        engines,
+        study_toml: &study.source_toml,
+        contents: headings
+            .iter()
+            .map(|heading| (section_id(heading), &**heading))
+            .collect(),
+    };
+    writeln!(out, "{}", header.render()?)?;
 
-    // If there were any Chromium results, print sections for real Chromium events.
-    if study
-        .engines()
-        .find(|engine| {
-            matches!(
-                engine.engine,
-                Engine::Chromium { .. } | Engine::ChromeDriver { .. },
-            )
+    // Print an overview of every metric, as a heatmap of the geomean across sites. Counts are not
+    // times, so leave them out of the overview heatmap, which is in seconds.
+    print_section_heading(out, "Overview")?;
+    let metrics = sections
+        .iter()
+        .zip(headings.iter())
+        .filter_map(|(section, heading)| match section {
+            ReportSection::Metric(metric) if metric.value_kind == ValueKind::Duration => {
+                Some((&**heading, metric.summary_key, metric.summaries_map))
+            }
+            _ => None,
         })
-        .is_some()
-    {
-        for summary_key in REAL_CHROMIUM_EVENTS.split(" ") {
-            println!("<h3>{summary_key} (real)</h3>\n");
-            print_section(
-                &study,
-                &raw_series_map,
-                &real_events_map,
-                EventKind::Chromium,
-                summary_key,
-            )?;
-        }
-    }
+        .collect::<Vec<_>>();
+    print_overview_heatmap(out, &study, &metrics, units)?;
 
-    // Print sections for rendering phases model.
-    for summary_key in RENDERING_PHASES_MODEL_EVENTS.split(" ") {
-        println!("<h3>{summary_key} (synthetic)</h3>\n");
-        print_section(
-            &study,
-            &raw_series_map,
-            &synthetic_and_interpreted_events_map,
-            EventKind::SyntheticOrInterpreted,
-            summary_key,
-        )?;
+    for (section, heading) in sections.iter().zip(headings.iter()) {
+        print_section_heading(out, heading)?;
+        match section {
+            ReportSection::Metric(metric) => {
+                if metric.has_phase_definitions {
+                    print_synthetic_phase_definitions(out, &study, metric.summary_key)?;
+                }
+                print_section(
+                    out,
+                    &study,
+                    &raw_series_map,
+                    metric.summaries_map,
+                    metric.event_kind,
+                    metric.summary_key,
+                    metric.value_kind,
+                    plot_options,
+                )?;
+            }
+            ReportSection::Aggregate => {
+                print_geomean_aggregate(out, &study, &synthetic_and_interpreted_events_map, units)?;
+            }
+            ReportSection::RelativeTimeline(reference) => {
+                print_timeline(out, &study, &individuals_map, reference)?;
+            }
+            ReportSection::RepresentativeTimeline => {
+                print_representative_timelines(out, &study, &individuals_map)?;
+            }
+            ReportSection::PhaseComparison => {
+                print_phase_comparison(out, &study, &individuals_map)?;
+            }
+        }
     }
 
-    // Print sections for overall rendering time model.
-    for summary_key in OVERALL_RENDERING_TIME_MODEL_EVENTS.split(" ") {
-        println!("<h3>{summary_key} (synthetic)</h3>\n");
-        print_section(
-            &study,
-            &raw_series_map,
-            &synthetic_and_interpreted_events_map,
-            EventKind::SyntheticOrInterpreted,
-            summary_key,
-        )?;
-    }
+    out.flush()?;
 
     Ok(())
 }
@@ -197,29 +308,488 @@ fn load_summaries(
     engine: KeyedEngine<'_>,
 ) -> eyre::Result<JsonSummaries> {
     info!("Loading summaries.json");
-    let sample_dir = Path::new(cpu_config.key)
+    let path = Path::new(cpu_config.key)
         .join(site.key)
         .join(engine.key)
         .join("summaries.json");
 
-    Ok(serde_json::from_reader(File::open(&sample_dir)?)?)
+    read_summaries(&path)
+}
+
+/// Reads the `summaries.json` of every sample in the study, relative to `study_dir`, skipping
+/// samples that have not been analysed yet.
+pub fn read_analysed_summaries<'study>(
+    study: &'study Study,
+    study_dir: &Path,
+) -> eyre::Result<
+    Vec<(
+        KeyedCpuConfig<'study>,
+        KeyedSite<'study>,
+        KeyedEngine<'study>,
+        JsonSummaries,
+    )>,
+> {
+    let mut result = vec![];
+    for cpu_config in study.cpu_configs() {
+        for site in study.sites() {
+            for engine in study.engines() {
+                let summaries_path = study_dir
+                    .join(cpu_config.key)
+                    .join(site.key)
+                    .join(engine.key)
+                    .join("summaries.json");
+                if !std::fs::exists(&summaries_path)? {
+                    warn!(?summaries_path, "Sample has not been analysed; skipping");
+                    continue;
+                }
+                let summaries = read_summaries(&summaries_path)?;
+                result.push((cpu_config, site, engine, summaries));
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+/// Reads the given `summaries.json`, checking that it has the schema version we expect.
+pub fn read_summaries(path: &Path) -> eyre::Result<JsonSummaries> {
+    // Check the schema version before deserialising, so that older files fail with a clear error.
+    let json: serde_json::Value = serde_json::from_reader(
+        File::open(path).wrap_err_with(|| format!("Failed to open {path:?}"))?,
+    )
+    .wrap_err_with(|| format!("Failed to parse {path:?} as JSON"))?;
+    let schema_version = json
+        .get("schema_version")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(0);
+    if schema_version != SUMMARIES_SCHEMA_VERSION as u64 {
+        bail!(
+            "{path:?} has schema version {schema_version}, but we expected version {SUMMARIES_SCHEMA_VERSION}; try running `analyse` again"
+        );
+    }
+
+    serde_json::from_value(json).wrap_err_with(|| {
+        format!("Failed to read {path:?} as summaries.json version {SUMMARIES_SCHEMA_VERSION}; try running `analyse` again")
+    })
+}
+
+/// Prints the real events that make up a synthetic phase in each engine type in the study, since
+/// they differ between engines, so readers can judge whether the comparison is apples-to-apples.
+/// Engines whose study overrides the definitions are listed separately, by engine key.
+fn print_synthetic_phase_definitions(
+    out: &mut dyn Write,
+    study: &Study,
+    summary_key: &str,
+) -> eyre::Result<()> {
+    let mut definitions: Vec<(String, String)> = vec![];
+    for engine in study.analysed_engines() {
+        let (engine_type, defaults) = match engine.engine {
+            Engine::Servo { .. } => ("Servo", crate::servo::default_category_names()),
+            Engine::Chromium { .. } | Engine::ChromeDriver { .. } => {
+                ("Chromium", crate::chromium::default_category_names())
+            }
+            Engine::WebKit { .. } => ("WebKit", crate::webkit::default_category_names()),
+            Engine::GeckoDriver { .. } | Engine::RemoteWebDriver { .. } => continue,
+        };
+        let category_names = study.category_names(engine.key, defaults.clone());
+        let label = if category_names == defaults {
+            engine_type.to_owned()
+        } else {
+            format!("{engine_type} ({})", engine.key)
+        };
+        let phases = category_names.synthetic_phase_definitions();
+        let Some((_, names)) = phases.iter().find(|(phase, _)| *phase == summary_key) else {
+            continue;
+        };
+        if !definitions.iter().any(|(other, _)| *other == label) {
+            definitions.push((label, names.to_string()));
+        }
+    }
+
+    writeln!(out, "<ul>")?;
+    for (engine_type, names) in definitions {
+        let names = names
+            .split(" ")
+            .map(|name| format!("<code>{}</code>", escape_html_for_inner_html(name)))
+            .collect::<Vec<_>>();
+        writeln!(out, "<li>{engine_type}: {}", names.join(", "))?;
+    }
+    writeln!(out, "</ul>\n")?;
+
+    Ok(())
+}
+
+/// Returns a description of each case where an engine is missing data for a whole site, or for a
+/// metric that it has for other sites.
+fn check_consistency(
+    study: &Study,
+    summaries_map: &BTreeMap<(&str, &str, &str), Vec<JsonSummary>>,
+) -> Vec<String> {
+    let mut result = vec![];
+    for engine in study.analysed_engines() {
+        let expected_names = summaries_map
+            .iter()
+            .filter(|((_, _, engine_key), _)| *engine_key == engine.key)
+            .flat_map(|(_, summaries)| summaries.iter().map(|summary| &*summary.name))
+            .collect::<BTreeSet<_>>();
+        for cpu_config in study.cpu_configs() {
+            for site in study.sites() {
+                let summaries = summaries_map
+                    .get(&(cpu_config.key, site.key, engine.key))
+                    .map_or(&[][..], |summaries| &summaries[..]);
+                if summaries.is_empty() {
+                    result.push(format!(
+                        "Engine {} has no data for site {} in CPU config {}",
+                        engine.key, site.key, cpu_config.key,
+                    ));
+                    continue;
+                }
+                for name in expected_names.iter() {
+                    if summaries
+                        .iter()
+                        .find(|summary| summary.name == *name)
+                        .is_none()
+                    {
+                        result.push(format!(
+                            "Engine {} has no data for {} on site {} in CPU config {}",
+                            engine.key, name, site.key, cpu_config.key,
+                        ));
+                    }
+                }
+            }
+        }
+    }
+
+    result
+}
+
+/// Reads the `version.txt` recorded by `collect`, from the first sample of this engine that has one.
+fn load_engine_version(study: &Study, engine: KeyedEngine<'_>) -> eyre::Result<Option<String>> {
+    for cpu_config in study.cpu_configs() {
+        for site in study.sites() {
+            let version_path = Path::new(cpu_config.key)
+                .join(site.key)
+                .join(engine.key)
+                .join("version.txt");
+            if std::fs::exists(&version_path)? {
+                return Ok(Some(std::fs::read_to_string(&version_path)?));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// Returns the error bar for a summary in a plot, as (low, representative, high). The bar spans the
+/// confidence interval if the representative statistic is the median and the interval was
+/// computed, since that interval is for the median, or one standard deviation either side otherwise.
+fn error_bar(summary: &Summary<f64>) -> (f64, f64, f64) {
+    let x = summary.representative();
+    match (
+        summary.representative_statistic,
+        summary.ci_low,
+        summary.ci_high,
+    ) {
+        (RepresentativeStatistic::Median, Some(low), Some(high)) => (low, x, high),
+        _ => (x - summary.stdev, x, x + summary.stdev),
+    }
+}
+
+/// Kind of value a metric has, which decides how the report formats it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ValueKind {
+    /// Durations in seconds, shown in the [Units] of the report.
+    Duration,
+    /// Dimensionless counts, like `LongTaskCount`.
+    Count,
+}
+
+impl ValueKind {
+    fn fmt_representative(self, summary: &Summary<f64>, units: Units) -> String {
+        match self {
+            ValueKind::Duration => summary.fmt_representative(units),
+            ValueKind::Count => fmt_count(summary.representative()),
+        }
+    }
+
+    /// Returns the formatter for axis labels, which are short and never in fixed [Units].
+    fn fmt_short(self) -> fn(&f64) -> String {
+        match self {
+            ValueKind::Duration => |&x| fmt_seconds_short(x),
+            ValueKind::Count => |&x| fmt_count(x),
+        }
+    }
+
+    fn axis_label(self) -> &'static str {
+        match self {
+            ValueKind::Duration => "time",
+            ValueKind::Count => "count",
+        }
+    }
+}
+
+/// Options for the plots and tables in each section of the report.
+#[derive(Clone, Copy, Debug)]
+struct PlotOptions {
+    /// Skip rendering plots whose data has not changed since the last report.
+    only_changed: bool,
+    /// Draw the spread of each series (see [error_bar]).
+    error_bars: bool,
+    /// Unit to show the representative values in.
+    units: Units,
+    /// Write each plot to a separate `.svg` file, as well as embedding it in the report.
+    write_plot_files: bool,
+}
+
+/// A section of the report, after the overview. The HTML and Markdown reports are both built
+/// from the same list of sections (see [report_sections]).
+enum ReportSection<'a> {
+    /// Tables and plots for one metric.
+    Metric(MetricSection<'a>),
+    /// The user-facing paint metrics and overall rendering time aggregated across sites.
+    Aggregate,
+    /// A timeline of the user-facing paint metrics relative to another metric (`--relative-to`).
+    RelativeTimeline(&'a str),
+    /// A timeline of the representative run of each sample, so the order and overlap of the
+    /// metrics and phases are visible.
+    RepresentativeTimeline,
+    /// Each rendering phase as a share of the overall rendering time (`--compare-phases`).
+    PhaseComparison,
+}
+
+struct MetricSection<'a> {
+    summary_key: &'static str,
+    /// Kind of metric, shown in the heading after the summary key, like `synthetic` or `real`.
+    kind_label: &'static str,
+    summaries_map: &'a BTreeMap<(&'a str, &'a str, &'a str), Vec<JsonSummary>>,
+    event_kind: EventKind,
+    value_kind: ValueKind,
+    /// Whether to list the events that make up this synthetic phase for each engine.
+    has_phase_definitions: bool,
+}
+
+impl ReportSection<'_> {
+    fn heading(&self) -> String {
+        match self {
+            Self::Metric(metric) => format!("{} ({})", metric.summary_key, metric.kind_label),
+            Self::Aggregate => "Aggregate (geomean)".to_owned(),
+            Self::RelativeTimeline(reference) => format!("Timeline relative to {reference}"),
+            Self::RepresentativeTimeline => "Timeline of representative run".to_owned(),
+            Self::PhaseComparison => "Rendering phases as a percentage of Renderer".to_owned(),
+        }
+    }
+}
+
+/// Returns the sections of the report in the order they are printed. Real events only get sections
+/// if there are results for an engine that has them.
+fn report_sections<'a>(
+    study: &Study,
+    real_events_map: &'a BTreeMap<(&'a str, &'a str, &'a str), Vec<JsonSummary>>,
+    synthetic_and_interpreted_events_map: &'a BTreeMap<
+        (&'a str, &'a str, &'a str),
+        Vec<JsonSummary>,
+    >,
+    counters_map: &'a BTreeMap<(&'a str, &'a str, &'a str), Vec<JsonSummary>>,
+    relative_to: Option<&'a str>,
+    compare_phases: bool,
+) -> Vec<ReportSection<'a>> {
+    fn metrics<'a>(
+        summary_keys: &'static str,
+        kind_label: &'static str,
+        summaries_map: &'a BTreeMap<(&'a str, &'a str, &'a str), Vec<JsonSummary>>,
+        event_kind: EventKind,
+        value_kind: ValueKind,
+        has_phase_definitions: bool,
+    ) -> impl Iterator<Item = ReportSection<'a>> {
+        summary_keys.split(" ").map(move |summary_key| {
+            ReportSection::Metric(MetricSection {
+                summary_key,
+                kind_label,
+                summaries_map,
+                event_kind,
+                value_kind,
+                has_phase_definitions,
+            })
+        })
+    }
+    let synthetic = synthetic_and_interpreted_events_map;
+    let has_servo = study
+        .analysed_engines()
+        .any(|engine| matches!(engine.engine, Engine::Servo { .. }));
+    let has_chromium = study.analysed_engines().any(|engine| {
+        matches!(
+            engine.engine,
+            Engine::Chromium { .. } | Engine::ChromeDriver { .. },
+        )
+    });
+
+    let mut sections = vec![];
+    sections.extend(metrics(
+        USER_FACING_PAINT_METRICS,
+        "synthetic",
+        synthetic,
+        EventKind::SyntheticOrInterpreted,
+        ValueKind::Duration,
+        false,
+    ));
+    sections.push(ReportSection::Aggregate);
+    if let Some(reference) = relative_to {
+        sections.push(ReportSection::RelativeTimeline(reference));
+    }
+    sections.push(ReportSection::RepresentativeTimeline);
+    if has_servo {
+        sections.extend(metrics(
+            REAL_SERVO_EVENTS,
+            "real",
+            real_events_map,
+            EventKind::Servo,
+            ValueKind::Duration,
+            false,
+        ));
+    }
+    if has_chromium {
+        sections.extend(metrics(
+            REAL_CHROMIUM_EVENTS,
+            "real",
+            real_events_map,
+            EventKind::Chromium,
+            ValueKind::Duration,
+            false,
+        ));
+    }
+    sections.extend(metrics(
+        RENDERING_PHASES_MODEL_EVENTS,
+        "synthetic",
+        synthetic,
+        EventKind::SyntheticOrInterpreted,
+        ValueKind::Duration,
+        true,
+    ));
+    if compare_phases {
+        sections.push(ReportSection::PhaseComparison);
+    }
+    sections.extend(metrics(
+        OVERALL_RENDERING_TIME_MODEL_EVENTS,
+        "synthetic",
+        synthetic,
+        EventKind::SyntheticOrInterpreted,
+        ValueKind::Duration,
+        true,
+    ));
+    sections.extend(metrics(
+        LONG_TASK_EVENTS,
+        "synthetic",
+        synthetic,
+        EventKind::SyntheticOrInterpreted,
+        ValueKind::Duration,
+        false,
+    ));
+    sections.extend(metrics(
+        LONG_TASK_COUNTS,
+        "count",
+        counters_map,
+        EventKind::Counter,
+        ValueKind::Count,
+        false,
+    ));
+
+    sections
+}
+
+/// The report up to the first section: the tooling version, the engines, the study config file,
+/// and the table of contents.
+#[derive(Template)]
+#[template(path = "report-header.html")]
+struct ReportHeaderTemplate<'a> {
+    version: &'a str,
+    metric_basis: MetricBasis,
+    metric_anchor: String,
+    engines: Vec<EngineListItem<'a>>,
+    study_toml: &'a str,
+    /// Id and heading of each section.
+    contents: Vec<(String, &'a str)>,
+}
+
+struct EngineListItem<'a> {
+    key: &'a str,
+    /// Description of the engine, which may contain HTML.
+    description: Option<&'a str>,
+    type_name: &'a str,
+    browser_path: &'a str,
+    has_version_command: bool,
+    /// Output of the `version_command`, or None if unknown.
+    version: Option<String>,
+}
+
+#[derive(Template)]
+#[template(source = "<h3 id='{{ id }}'>{{ heading }}</h3>", ext = "html")]
+struct SectionHeadingTemplate<'a> {
+    id: String,
+    heading: &'a str,
+}
+
+/// The plot and table for one metric and site.
+#[derive(Template)]
+#[template(path = "report-section.html")]
+struct SectionTemplate<'a> {
+    site: &'a str,
+    plot: SitePlot,
+    cpu_configs: Vec<&'a str>,
+    statistic_label: &'a str,
+    rows: Vec<SectionRow<'a>>,
+    p_values: Option<PValueRow<'a>>,
+}
+
+enum SitePlot {
+    /// Data URL of the plot.
+    Image(String),
+    /// Reason the plot is unavailable.
+    Unavailable(String),
+}
+
+/// An engine and its cells, one for each CPU config with data for the metric.
+struct SectionRow<'a> {
+    engine: &'a str,
+    cells: Vec<SectionCell<'a>>,
+}
+
+struct SectionCell<'a> {
+    /// Full summary, shown on hover.
+    title: &'a str,
+    /// Representative value, or None if there are too few samples.
+    value: Option<String>,
+    noisy: bool,
+    bimodal: bool,
+}
+
+/// The p-value of Welch’s t-test between the only two engines with data, for each CPU config.
+struct PValueRow<'a> {
+    first: &'a str,
+    second: &'a str,
+    cells: Vec<Option<PValueCell>>,
+}
+
+struct PValueCell {
+    t: String,
+    p: String,
 }
 
 fn print_section(
+    out: &mut dyn Write,
     study: &Study,
     raw_series_map: &BTreeMap<(&str, &str, &str), Vec<JsonRawSeries>>,
     summaries_map: &BTreeMap<(&str, &str, &str), Vec<JsonSummary>>,
     event_kind: EventKind,
     summary_key: &str,
+    value_kind: ValueKind,
+    plot_options: PlotOptions,
 ) -> eyre::Result<()> {
     for site in study.sites() {
-        println!("<h4>{}</h4>\n", site.key);
-
         // Plot all of the data for this metric and site, organised by CPU config and engine.
         // First we define a tick distribution factory for the x axis, based on the default for f64
         // (`FloatTickFmt: TickDistGen`) but tweaked with our own stringifier. `FloatTickFmt` is
         // not to be confused with `FloatFmt: TickFmt`, the default stringifier for f64.
-        struct TicksX;
+        struct TicksX(fn(&f64) -> String);
         impl TickDistGen<f64> for TicksX {
             type Res = TickDistribution<Vec<f64>, WithTickFmt<FloatFmt, fn(&f64) -> String>>;
             fn generate(
@@ -230,7 +800,7 @@ fn print_section(
             ) -> Self::Res {
                 FloatTickFmt
                     .generate(data, canvas, req)
-                    .with_tick_fmt(|&x| fmt_seconds_short(x))
+                    .with_tick_fmt(self.0)
             }
         }
         // Then we define one for the y axis that gives us exactly one tick every 1.0f64,
@@ -261,11 +831,12 @@ fn print_section(
         }
         // Next we look up all of the raw data series (`JsonRawSeries`) for this metric and site.
         // There is one raw data series for each CPU config and engine. Create a plot builder for
-        // each series, pair them up, and collect them into a vec.
+        // each series, pair them up, and collect them into a vec. With `--error-bars`, also find the
+        // spread of each series, as (low, representative, high).
         let mut plots = vec![];
         for (cpu_config, site, engine) in study.cpu_configs().flat_map(|cpu_config| {
             study
-                .engines()
+                .analysed_engines()
                 .map(move |engine| (cpu_config, site, engine))
         }) {
             if let Some(series) = raw_series_map.get(&(cpu_config.key, site.key, engine.key)) {
@@ -273,116 +844,840 @@ fn print_section(
                     .iter()
                     .find(|s| s.kind == event_kind && s.name == summary_key)
                 {
-                    let plot = poloto::build::plot(format!("{} {}", cpu_config.key, engine.key));
-                    plots.push((series, plot));
+                    let label = format!("{} {}", cpu_config.key, engine.key);
+                    let plot = poloto::build::plot(label.clone());
+                    let error_bar = summaries_map
+                        .get(&(cpu_config.key, site.key, engine.key))
+                        .and_then(|summaries| {
+                            summaries.iter().find(|summary| summary.name == summary_key)
+                        })
+                        .filter(|_| plot_options.error_bars)
+                        .map(|summary| error_bar(&summary.raw))
+                        .filter(|bar| [bar.0, bar.1, bar.2].iter().all(|x| x.is_finite()));
+                    plots.push((label, engine.color(), series, error_bar, plot));
                 }
             }
         }
+        // Hash the exact data that goes into the plot, so that with `--only-changed`, we can skip
+        // rendering the plot if the previous plot has the same hash. This also seeds the jitter, so
+        // the same data always yields the same plot. The hash is compared across runs, so write
+        // each value as explicit bytes into a stable hasher, rather than relying on `Hash` impls.
+        let target = study.targets.get(summary_key).copied();
+        let mut hasher = Fnv1aHasher::default();
+        let hash_str = |hasher: &mut Fnv1aHasher, text: &str| {
+            Hasher::write(hasher, &(text.len() as u64).to_le_bytes());
+            Hasher::write(hasher, text.as_bytes());
+        };
+        let hash_f64s = |hasher: &mut Fnv1aHasher, xs: &[f64]| {
+            Hasher::write(hasher, &(xs.len() as u64).to_le_bytes());
+            for x in xs {
+                Hasher::write(hasher, &x.to_bits().to_le_bytes());
+            }
+        };
+        hash_str(&mut hasher, summary_key);
+        hash_str(&mut hasher, site.key);
+        hash_f64s(&mut hasher, target.as_slice());
+        for (label, color, series, error_bar, _) in plots.iter() {
+            hash_str(&mut hasher, label);
+            hash_str(&mut hasher, color);
+            let error_bar = error_bar.map(|(low, x, high)| [low, x, high]);
+            hash_f64s(&mut hasher, error_bar.as_ref().map_or(&[], |bar| &bar[..]));
+            hash_f64s(&mut hasher, &series.xs);
+        }
+        let data_hash = hasher.finish();
+        let data_hash_comment = format!("<!-- data hash: {data_hash:016x} -->");
+        let mut rng = StdRng::seed_from_u64(data_hash);
         // Plot each series on the respective plot as (time value ms: f64, index: i128), where
         // `index` is in reverse order of series. Since the y axis increases upwards but the legend
         // is read from top to bottom, this makes the plots appear in the same order as the legend.
         let series_count = plots.len() as f64;
-        let plots = plots.into_iter().enumerate().map(|(i, (series, plot))| {
-            plot.scatter(series.xs.iter().map(|&x| {
-                (
-                    x,
-                    series_count - i as f64 + (rand::thread_rng().gen::<f64>() - 0.5f64) * 0.25f64,
-                )
-            }))
-        });
-        // Render the plot as both an SVG file and a data URL.
-        let plot_svg = poloto::frame_build()
-            .data(poloto::plots!(
-                // Make sure x = 0ms is in view, plus space around each y series.
-                poloto::build::markers([0f64], [0f64, series_count + 1.0f64]),
+        let plotted_series = plots
+            .iter()
+            .map(|(_, _, series, _, _)| *series)
+            .collect::<Vec<_>>();
+        // Give each series the colour of its engine, so an engine looks the same in every plot.
+        // poloto colours the nth plot with the `poloto{n}stroke` and `poloto{n}fill` classes of the
+        // theme, so we override those classes with a stylesheet after the theme.
+        // The error bars, if any, are plotted after the series, so they come next.
+        let series_style = plots
+            .iter()
+            .map(|(_, color, _, _, _)| color)
+            .chain(
                 plots
-            ))
-            .map_xticks(|_| TicksX)
-            .map_yticks(|_| SeriesTickFmt)
-            .build_and_label((format!("{} {}", summary_key, site.key), "time", "sample"))
-            .append_to(poloto::header().light_theme())
-            .render_string()?;
-        let plot_path = format!("{}.{}.{}.svg", event_kind, summary_key, site.key);
-        File::create(&plot_path)?.write_all(plot_svg.as_bytes())?;
-        let mut plot_data_url = DataUrl::new();
-        plot_data_url.set_media_type(Some("image/svg+xml".to_owned()));
-        plot_data_url.set_data(plot_svg.as_bytes());
-        println!("<img src='{}'>\n", plot_data_url.to_string());
-
-        println!("<table border=1 cellpadding=3>");
-        println!("<tr>");
-        println!("<th colspan=2>");
-        for cpu_config in study.cpu_configs() {
-            println!("<th>{}", cpu_config.key);
-        }
-        let list: &[(&str, Box<dyn Fn(&Summary<_>) -> String>)] = &[
-            // ("n", Box::new(|s| s.fmt_n())),
-            // ("μ", Box::new(|s| s.fmt_mean())),
-            // ("s", Box::new(|s| s.fmt_stdev())),
-            ("min", Box::new(|s| s.fmt_min())),
-            // ("max", Box::new(|s| s.fmt_max())),
-        ];
-        for (statistic_label, statistic_getter) in list {
-            // Count the actual number of rows we will need, for rowspan.
-            let mut rowspan = 0;
-            for engine in study.engines() {
-                for cpu_config in study.cpu_configs() {
-                    let summaries = summaries_map
-                        .get(&(cpu_config.key, site.key, engine.key))
-                        .ok_or_eyre("Vec<JsonSummary> not found")?;
-                    if summaries
-                        .iter()
-                        .find(|summary| summary.name == summary_key)
-                        .is_some()
-                    {
-                        rowspan += 1;
+                    .iter()
+                    .filter(|(_, _, _, error_bar, _)| error_bar.is_some())
+                    .map(|(_, color, _, _, _)| color),
+            )
+            .enumerate()
+            .map(|(i, color)| {
+                format!(".poloto{i}stroke{{stroke:{color}}}.poloto{i}fill{{fill:{color}}}")
+            })
+            .collect::<String>();
+        // Draw each error bar as one line at the y index of its series: a whisker at the low end,
+        // a taller tick at the representative value, and a whisker at the high end.
+        let error_bar_plots = plots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, (label, _, _, error_bar, _))| {
+                let (low, x, high) = (*error_bar)?;
+                let y = series_count - i as f64;
+                let points = [
+                    (low, y - 0.15),
+                    (low, y + 0.15),
+                    (low, y),
+                    (x, y),
+                    (x, y + 0.25),
+                    (x, y - 0.25),
+                    (x, y),
+                    (high, y),
+                    (high, y + 0.15),
+                    (high, y - 0.15),
+                ];
+                Some(poloto::build::plot(format!("{label} spread")).line(points.into_iter()))
+            })
+            .collect::<Vec<_>>();
+        let points = plots
+            .iter()
+            .enumerate()
+            .map(|(i, (_, _, series, _, _))| {
+                series
+                    .xs
+                    .iter()
+                    .map(|&x| {
+                        (
+                            x,
+                            series_count - i as f64 + (rng.gen::<f64>() - 0.5f64) * 0.25f64,
+                        )
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        // If the metric has a target, move the points beyond the target into a plot of their own,
+        // so they stand out in a different colour, and draw a vertical line at the target.
+        let (points, over_target_points): (Vec<_>, Vec<_>) = points
+            .into_iter()
+            .map(|points| {
+                points
+                    .into_iter()
+                    .partition::<Vec<_>, _>(|&(x, _)| target.map_or(true, |target| x <= target))
+            })
+            .unzip();
+        let over_target_points = over_target_points.into_iter().flatten().collect::<Vec<_>>();
+        let plots = plots
+            .into_iter()
+            .zip(points)
+            .map(|((_, _, _, _, plot), points)| plot.scatter(points.into_iter()));
+        let target_line = target.map(|target| {
+            poloto::build::plot(format!("target {}", value_kind.fmt_short()(&target)))
+                .line([(target, 0f64), (target, series_count + 1.0f64)].into_iter())
+        });
+        let over_target_plot = (!over_target_points.is_empty())
+            .then(|| poloto::build::plot("over target").scatter(over_target_points.into_iter()));
+        // Render the plot as both an SVG file and a data URL. If that fails, say so in place of the
+        // plot, but keep going, since the table below does not depend on the plot.
+        let plot = if let Some(series) = plotted_series
+            .iter()
+            .find(|series| series.xs.iter().any(|x| !x.is_finite()))
+        {
+            SitePlot::Unavailable(format!("series {} has non-finite values", series.name))
+        } else {
+            let plot_path = format!("{}.{}.{}.svg", event_kind, summary_key, site.key);
+            let previous_plot_svg = std::fs::read_to_string(&plot_path)
+                .ok()
+                .filter(|svg| plot_options.only_changed && svg.starts_with(&data_hash_comment));
+            let plot_svg = if let Some(previous_plot_svg) = previous_plot_svg {
+                info!(?plot_path, "Plot data unchanged; skipping render");
+                Ok(previous_plot_svg)
+            } else {
+                poloto::frame_build()
+                    .data(poloto::plots!(
+                        // Make sure x = 0ms is in view, plus space around each y series.
+                        poloto::build::markers([0f64], [0f64, series_count + 1.0f64]),
+                        plots,
+                        error_bar_plots.into_iter(),
+                        target_line.into_iter(),
+                        over_target_plot.into_iter()
+                    ))
+                    .map_xticks(|_| TicksX(value_kind.fmt_short()))
+                    .map_yticks(|_| SeriesTickFmt)
+                    .build_and_label((
+                        format!("{} {}", summary_key, site.key),
+                        value_kind.axis_label(),
+                        "sample",
+                    ))
+                    .append_to(poloto::header().light_theme())
+                    .render_string()
+                    .map(|svg| {
+                        let end = svg.rfind("</svg>").unwrap_or(svg.len());
+                        let (body, end) = svg.split_at(end);
+                        format!("{data_hash_comment}\n{body}<style>{series_style}</style>{end}")
+                    })
+            };
+            match plot_svg {
+                Ok(plot_svg) => {
+                    if plot_options.write_plot_files {
+                        File::create(&plot_path)?.write_all(plot_svg.as_bytes())?;
                     }
+                    let mut plot_data_url = DataUrl::new();
+                    plot_data_url.set_media_type(Some("image/svg+xml".to_owned()));
+                    plot_data_url.set_data(plot_svg.as_bytes());
+                    SitePlot::Image(plot_data_url.to_string())
+                }
+                Err(error) => {
+                    warn!(?error, summary_key, site.key, "Failed to render plot");
+                    SitePlot::Unavailable(format!("{error:?}"))
                 }
             }
-            let mut need_statistic_label = true;
-            for engine in study.engines() {
-                // Loop and break to print a `<tr>` and `<th>` only when `summary_key` is applicable to `engine`.
+        };
+
+        // Tabulate the representative value for each engine (row) and CPU config (column).
+        let find_summary = |cpu_config: KeyedCpuConfig, engine: KeyedEngine| {
+            summaries_map
+                .get(&(cpu_config.key, site.key, engine.key))
+                .and_then(|summaries| summaries.iter().find(|summary| summary.name == summary_key))
+        };
+        let rows = study
+            .engines()
+            .filter_map(|engine| {
+                let cells = study
+                    .cpu_configs()
+                    .filter_map(|cpu_config| find_summary(cpu_config, engine))
+                    .map(|summary| {
+                        let sufficient = summary.raw.n >= study.min_samples_for_report();
+                        SectionCell {
+                            title: &summary.full,
+                            value: sufficient.then(|| {
+                                value_kind.fmt_representative(&summary.raw, plot_options.units)
+                            }),
+                            noisy: sufficient && summary.raw.cv > NOISY_CV_THRESHOLD,
+                            bimodal: sufficient && summary.bimodal,
+                        }
+                    })
+                    .collect::<Vec<_>>();
+                (!cells.is_empty()).then_some(SectionRow {
+                    engine: engine.key,
+                    cells,
+                })
+            })
+            .collect::<Vec<_>>();
+        // If exactly two engines have data for this metric, show whether the difference between
+        // their means is statistically significant, for each CPU config.
+        let engines_with_data = study
+            .engines()
+            .filter(|&engine| {
+                study
+                    .cpu_configs()
+                    .any(|cpu_config| find_summary(cpu_config, engine).is_some())
+            })
+            .collect::<Vec<_>>();
+        let p_values = if let [first, second] = engines_with_data[..] {
+            Some(PValueRow {
+                first: first.key,
+                second: second.key,
+                cells: study
+                    .cpu_configs()
+                    .map(|cpu_config| {
+                        let (t, p) = find_summary(cpu_config, first)
+                            .zip(find_summary(cpu_config, second))
+                            .and_then(|(first, second)| first.raw.welch_t_test(&second.raw))?;
+                        Some(PValueCell {
+                            t: format!("{t:.3}"),
+                            p: format!("{p:.3}"),
+                        })
+                    })
+                    .collect(),
+            })
+        } else {
+            None
+        };
+
+        let template = SectionTemplate {
+            site: site.key,
+            plot,
+            cpu_configs: study
+                .cpu_configs()
+                .map(|cpu_config| cpu_config.key)
+                .collect(),
+            statistic_label: study.summary_options().representative.label(),
+            rows,
+            p_values,
+        };
+        writeln!(out, "{}\n", template.render()?)?;
+    }
+
+    Ok(())
+}
+
+/// Prints the report as GitHub-flavoured Markdown, with tables only (no plots), for pasting into
+/// issues and pull requests.
+fn print_markdown_report(
+    out: &mut dyn Write,
+    study: &Study,
+    real_events_map: &BTreeMap<(&str, &str, &str), Vec<JsonSummary>>,
+    synthetic_and_interpreted_events_map: &BTreeMap<(&str, &str, &str), Vec<JsonSummary>>,
+    counters_map: &BTreeMap<(&str, &str, &str), Vec<JsonSummary>>,
+    units: Units,
+) -> eyre::Result<()> {
+    for engine in study.analysed_engines() {
+        writeln!(
+            out,
+            "- **{}** = `{}` at `{}`",
+            engine.key,
+            engine.type_name(),
+            engine.browser_path(),
+        )?;
+    }
+    writeln!(out)?;
+
+    // Markdown has tables for metrics only, so skip the other sections.
+    let sections = report_sections(
+        study,
+        real_events_map,
+        synthetic_and_interpreted_events_map,
+        counters_map,
+        None,
+        false,
+    );
+    for section in sections.iter() {
+        let ReportSection::Metric(metric) = section else {
+            continue;
+        };
+        let MetricSection {
+            summary_key,
+            summaries_map,
+            value_kind,
+            ..
+        } = *metric;
+        writeln!(out, "### {}", section.heading())?;
+        writeln!(out)?;
+        for site in study.sites() {
+            writeln!(out, "#### {}", site.key)?;
+            writeln!(out)?;
+            write!(out, "| |")?;
+            for cpu_config in study.cpu_configs() {
+                write!(out, " {} |", cpu_config.key)?;
+            }
+            writeln!(out)?;
+            write!(out, "|---|")?;
+            for _ in study.cpu_configs() {
+                write!(out, "---|")?;
+            }
+            writeln!(out)?;
+            for engine in study.analysed_engines() {
+                let mut row = format!("| {} |", engine.key);
+                let mut any = false;
                 for cpu_config in study.cpu_configs() {
-                    let summaries = summaries_map
+                    let summary = summaries_map
                         .get(&(cpu_config.key, site.key, engine.key))
-                        .ok_or_eyre("Vec<JsonSummary> not found")?;
-                    if summaries
+                        .map_or(&[][..], |summaries| &summaries[..])
                         .iter()
-                        .find(|summary| summary.name == summary_key)
-                        .is_some()
-                    {
-                        println!("<tr>");
-                        if need_statistic_label {
-                            println!("<th rowspan={rowspan}>{statistic_label}");
+                        .find(|summary| summary.name == summary_key);
+                    match summary {
+                        Some(summary) if summary.raw.n < study.min_samples_for_report() => {
+                            any = true;
+                            row += " n/a (insufficient samples) |";
                         }
-                        println!("<th>{}", engine.key);
-                        need_statistic_label = false;
-                        break;
+                        Some(summary) => {
+                            any = true;
+                            row += &format!(
+                                " {}{} |",
+                                value_kind.fmt_representative(&summary.raw, units),
+                                if summary.bimodal { " (bimodal)" } else { "" },
+                            );
+                        }
+                        None => row += " |",
                     }
                 }
-                // Now print the data for that row.
-                for cpu_config in study.cpu_configs() {
-                    let summaries = summaries_map
-                        .get(&(cpu_config.key, site.key, engine.key))
-                        .ok_or_eyre("Vec<JsonSummary> not found")?;
-                    if let Some(summary) =
-                        summaries.iter().find(|summary| summary.name == summary_key)
+                // Skip engines where `summary_key` is not applicable, like the HTML report.
+                if any {
+                    writeln!(out, "{row}")?;
+                }
+            }
+            writeln!(out)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints each user-facing paint metric as an offset from `reference`, within each individual.
+///
+/// Unlike the other sections, this aligns the metrics within the same individual, rather than
+/// comparing summaries computed independently across individuals.
+fn print_timeline(
+    out: &mut dyn Write,
+    study: &Study,
+    individuals_map: &BTreeMap<(&str, &str, &str), Vec<JsonIndividual>>,
+    reference: &str,
+) -> eyre::Result<()> {
+    for site in study.sites() {
+        writeln!(out, "<h4>{}</h4>\n", site.key)?;
+        writeln!(out, "<table border=1 cellpadding=3>")?;
+        writeln!(out, "<tr>")?;
+        writeln!(out, "<th colspan=2>")?;
+        for metric in USER_FACING_PAINT_METRICS.split(" ") {
+            writeln!(out, "<th>{metric}")?;
+        }
+        for engine in study.analysed_engines() {
+            for cpu_config in study.cpu_configs() {
+                let individuals = individuals_map
+                    .get(&(cpu_config.key, site.key, engine.key))
+                    .map_or(&[][..], |individuals| &individuals[..]);
+                writeln!(out, "<tr>")?;
+                writeln!(out, "<th>{}", engine.key)?;
+                writeln!(out, "<th>{}", cpu_config.key)?;
+                for metric in USER_FACING_PAINT_METRICS.split(" ") {
+                    let offsets = individuals
+                        .iter()
+                        .flat_map(|individual| {
+                            let events = &individual.synthetic_and_interpreted_events;
+                            Some(events.get(metric)? - events.get(reference)?)
+                        })
+                        .collect::<Vec<_>>();
+                    match (Analysis {
+                        individuals: offsets,
+                    })
+                    .summary_with_options(study.summary_options(), |&x| x)
                     {
-                        println!(
+                        Ok(summary) => writeln!(
+                            out,
+                            "<td title='n={}, min={}, max={}'>{}",
+                            summary.n,
+                            fmt_seconds_offset(summary.min),
+                            fmt_seconds_offset(summary.max),
+                            fmt_seconds_offset(summary.representative()),
+                        )?,
+                        Err(_) => writeln!(out, "<td>n/a")?,
+                    }
+                }
+            }
+        }
+        writeln!(out, "</table>\n")?;
+    }
+
+    Ok(())
+}
+
+/// Prints a table of the geometric mean across sites of the representative value of each
+/// user-facing paint metric and the overall rendering time, for each CPU config and engine. Sites
+/// with no summary (or too few samples) for a metric are skipped rather than treated as zero.
+fn print_geomean_aggregate(
+    out: &mut dyn Write,
+    study: &Study,
+    summaries_map: &BTreeMap<(&str, &str, &str), Vec<JsonSummary>>,
+    units: Units,
+) -> eyre::Result<()> {
+    let site_count = study.sites().count();
+    writeln!(out, "<table border=1 cellpadding=3>")?;
+    writeln!(out, "<tr>")?;
+    writeln!(out, "<th colspan=2>")?;
+    for cpu_config in study.cpu_configs() {
+        writeln!(out, "<th>{}", escape_html_for_inner_html(cpu_config.key))?;
+    }
+    let summary_keys = USER_FACING_PAINT_METRICS
+        .split(" ")
+        .chain(OVERALL_RENDERING_TIME_MODEL_EVENTS.split(" "));
+    for summary_key in summary_keys {
+        let mut need_summary_key_label = true;
+        for engine in study.analysed_engines() {
+            writeln!(out, "<tr>")?;
+            if need_summary_key_label {
+                writeln!(
+                    out,
+                    "<th rowspan={}>{}",
+                    study.analysed_engines().count(),
+                    escape_html_for_inner_html(summary_key),
+                )?;
+                need_summary_key_label = false;
+            }
+            writeln!(out, "<th>{}", escape_html_for_inner_html(engine.key))?;
+            for cpu_config in study.cpu_configs() {
+                let xs = representatives_across_sites(
+                    study,
+                    summaries_map,
+                    cpu_config,
+                    engine,
+                    summary_key,
+                )?;
+                match geometric_mean(&xs) {
+                    Some(mean) => writeln!(
+                        out,
+                        "<td title='{} of {} sites'>{}",
+                        xs.len(),
+                        site_count,
+                        fmt_seconds_in(mean, units),
+                    )?,
+                    None => writeln!(out, "<td>n/a")?,
+                }
+            }
+        }
+    }
+    writeln!(out, "</table>\n")?;
+
+    Ok(())
+}
+
+/// Returns the representative value of a metric for each site, skipping sites with no summary (or
+/// too few samples) for the metric, and values that would break a geometric mean.
+fn representatives_across_sites(
+    study: &Study,
+    summaries_map: &BTreeMap<(&str, &str, &str), Vec<JsonSummary>>,
+    cpu_config: KeyedCpuConfig,
+    engine: KeyedEngine,
+    summary_key: &str,
+) -> eyre::Result<Vec<f64>> {
+    let mut xs = vec![];
+    for site in study.sites() {
+        let summaries = summaries_map
+            .get(&(cpu_config.key, site.key, engine.key))
+            .map_or(&[][..], |summaries| &summaries[..]);
+        let Some(summary) = summaries.iter().find(|summary| summary.name == summary_key) else {
+            continue;
+        };
+        let x = summary.raw.representative();
+        if summary.raw.n < study.min_samples_for_report() || x <= 0.0 {
+            continue;
+        }
+        xs.push(x);
+    }
+
+    Ok(xs)
+}
+
+/// Prints a heatmap with a row for each metric and a column for each CPU config and engine, where
+/// each cell is the geometric mean across sites of the representative value, coloured from green
+/// (fastest in the row) to red (slowest in the row). Each metric links to its section.
+fn print_overview_heatmap(
+    out: &mut dyn Write,
+    study: &Study,
+    metrics: &[(&str, &str, &BTreeMap<(&str, &str, &str), Vec<JsonSummary>>)],
+    units: Units,
+) -> eyre::Result<()> {
+    let site_count = study.sites().count();
+    writeln!(out, "<table border=1 cellpadding=3>")?;
+    writeln!(out, "<tr>")?;
+    writeln!(out, "<th>")?;
+    for cpu_config in study.cpu_configs() {
+        for engine in study.analysed_engines() {
+            writeln!(
+                out,
+                "<th>{} {}",
+                escape_html_for_inner_html(cpu_config.key),
+                escape_html_for_inner_html(engine.key),
+            )?;
+        }
+    }
+    for &(heading, summary_key, summaries_map) in metrics {
+        let mut cells = vec![];
+        for cpu_config in study.cpu_configs() {
+            for engine in study.analysed_engines() {
+                let xs = representatives_across_sites(
+                    study,
+                    summaries_map,
+                    cpu_config,
+                    engine,
+                    summary_key,
+                )?;
+                cells.push(geometric_mean(&xs).map(|mean| (mean, xs.len())));
+            }
+        }
+        let means = cells.iter().flatten().map(|&(mean, _)| mean);
+        let min = means.clone().fold(f64::INFINITY, f64::min);
+        let max = means.fold(f64::NEG_INFINITY, f64::max);
+        writeln!(out, "<tr>")?;
+        writeln!(
+            out,
+            "<th><a href='#{}'>{}</a>",
+            escape_html_for_attribute(&section_id(heading)),
+            escape_html_for_inner_html(heading),
+        )?;
+        for cell in cells {
+            let Some((mean, n)) = cell else {
+                writeln!(out, "<td>n/a")?;
+                continue;
+            };
+            let t = if max > min {
+                (mean - min) / (max - min)
+            } else {
+                0.0
+            };
+            writeln!(
+                out,
+                "<td title='{} of {} sites, {:.2}× the fastest' style='background: hsl({:.0}, 70%, 85%)'>{}",
+                n,
+                site_count,
+                mean / min,
+                120.0 * (1.0 - t),
+                fmt_seconds_in(mean, units),
+            )?;
+        }
+    }
+    writeln!(out, "</table>\n")?;
+
+    Ok(())
+}
+
+/// Returns the id of the section with the given heading, for linking to it.
+fn section_id(heading: &str) -> String {
+    heading
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+fn print_section_heading(out: &mut dyn Write, heading: &str) -> eyre::Result<()> {
+    let template = SectionHeadingTemplate {
+        id: section_id(heading),
+        heading,
+    };
+    writeln!(out, "{}\n", template.render()?)?;
+
+    Ok(())
+}
+
+/// Returns the representative run of a sample, namely the one whose Renderer time is closest to
+/// the study’s representative statistic (see [Analysis::representative_individual]).
+fn representative_run<'individual>(
+    study: &Study,
+    individuals: &'individual [JsonIndividual],
+) -> Option<&'individual JsonIndividual> {
+    Analysis {
+        individuals: individuals.iter().collect::<Vec<_>>(),
+    }
+    .representative_individual(study.summary_options().representative, |individual| {
+        individual
+            .synthetic_and_interpreted_events
+            .get(OVERALL_RENDERING_TIME_MODEL_EVENTS)
+            .copied()
+    })
+    .copied()
+}
+
+/// Prints a 100%-stacked bar for each sample, showing each rendering phase as a percentage of the
+/// Renderer time in the representative run, so engines can be compared regardless of speed.
+fn print_phase_comparison(
+    out: &mut dyn Write,
+    study: &Study,
+    individuals_map: &BTreeMap<(&str, &str, &str), Vec<JsonIndividual>>,
+) -> eyre::Result<()> {
+    for site in study.sites() {
+        writeln!(out, "<h4>{}</h4>\n", site.key)?;
+        writeln!(out, "<table border=1 cellpadding=3>")?;
+        for engine in study.analysed_engines() {
+            for cpu_config in study.cpu_configs() {
+                let individuals = individuals_map
+                    .get(&(cpu_config.key, site.key, engine.key))
+                    .map_or(&[][..], |individuals| &individuals[..]);
+                writeln!(out, "<tr>")?;
+                writeln!(out, "<th>{}", engine.key)?;
+                writeln!(out, "<th>{}", cpu_config.key)?;
+                let Some(individual) = representative_run(study, individuals) else {
+                    writeln!(out, "<td>n/a")?;
+                    continue;
+                };
+                let events = &individual.synthetic_and_interpreted_events;
+                // Omit the bar if there was no Renderer time to divide by.
+                match events.get(OVERALL_RENDERING_TIME_MODEL_EVENTS) {
+                    Some(&total) if total > 0.0 => {
+                        let phases = RENDERING_PHASES_MODEL_EVENTS
+                            .split(" ")
+                            .map(|phase| (phase, events.get(phase).copied().unwrap_or(0.0) / total))
+                            .collect::<Vec<_>>();
+                        writeln!(
+                            out,
                             "<td title='{}'>{}",
-                            summary.full,
-                            statistic_getter(&summary.raw)
-                        );
+                            escape_html_for_attribute(&individual.path),
+                            render_phase_bar(&phases)?,
+                        )?;
                     }
+                    _ => writeln!(out, "<td>n/a")?,
                 }
             }
         }
-        println!("</table>\n");
+        writeln!(out, "</table>\n")?;
     }
 
     Ok(())
 }
 
+/// Renders the given phases and their fractions of the total as a 100%-stacked bar, with any
+/// remainder as “other”. Phases can overlap, so if they add up to more than 100%, the bar is
+/// scaled to fit, but the labels still show the true percentages.
+fn render_phase_bar(phases: &[(&str, f64)]) -> eyre::Result<String> {
+    static WIDTH: f64 = 600.0;
+    static HEIGHT: usize = 20;
+    static COLOURS: &[&str] = &["#4e79a7", "#f28e2b", "#59a14f", "#e15759"];
+
+    let sum = phases.iter().map(|(_, fraction)| fraction).sum::<f64>();
+    let mut segments = phases
+        .iter()
+        .zip(COLOURS.iter().cycle())
+        .map(|(&(phase, fraction), &colour)| (phase, fraction, colour))
+        .collect::<Vec<_>>();
+    if sum < 1.0 {
+        segments.push(("other", 1.0 - sum, "#bab0ac"));
+    }
+    let scale = WIDTH / sum.max(1.0);
+
+    let mut result = String::default();
+    write!(
+        result,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{WIDTH}" height="{HEIGHT}" font-family="sans-serif" font-size="11">"#,
+    )?;
+    let mut x = 0.0;
+    for (phase, fraction, colour) in segments {
+        let width = fraction * scale;
+        let label = format!("{phase} {:.0}%", fraction * 100.0);
+        write!(
+            result,
+            r#"<rect x="{x:.2}" width="{width:.2}" height="{HEIGHT}" fill="{colour}"><title>{}</title></rect>"#,
+            escape_html_for_inner_html(&label),
+        )?;
+        // Only label segments that are wide enough for some text.
+        if width > 60.0 {
+            write!(
+                result,
+                r#"<text x="{:.2}" y="{}" fill="white">{}</text>"#,
+                x + 3.0,
+                HEIGHT - 6,
+                escape_html_for_inner_html(&label),
+            )?;
+        }
+        x += width;
+    }
+    write!(result, "</svg>")?;
+
+    Ok(result)
+}
+
+/// Prints a timeline for each sample, drawing every synthetic and interpreted event of the
+/// representative run (the one whose Renderer time is closest to the study’s representative
+/// statistic, see [representative_run]) on one time axis per site.
+fn print_representative_timelines(
+    out: &mut dyn Write,
+    study: &Study,
+    individuals_map: &BTreeMap<(&str, &str, &str), Vec<JsonIndividual>>,
+) -> eyre::Result<()> {
+    for site in study.sites() {
+        let mut representatives = vec![];
+        for engine in study.analysed_engines() {
+            for cpu_config in study.cpu_configs() {
+                let individuals = individuals_map
+                    .get(&(cpu_config.key, site.key, engine.key))
+                    .map_or(&[][..], |individuals| &individuals[..]);
+                representatives.push((engine, cpu_config, representative_run(study, individuals)));
+            }
+        }
+
+        // Share the time axis between all of the timelines for this site.
+        let end = representatives
+            .iter()
+            .flat_map(|(_, _, representative)| representative)
+            .flat_map(|individual| individual.events.iter())
+            .map(|event| event.start + event.duration.unwrap_or(0.0))
+            .fold(0.0, f64::max);
+
+        writeln!(out, "<h4>{}</h4>\n", site.key)?;
+        writeln!(out, "<table border=1 cellpadding=3>")?;
+        for (engine, cpu_config, representative) in representatives {
+            writeln!(out, "<tr>")?;
+            writeln!(out, "<th>{}", engine.key)?;
+            writeln!(out, "<th>{}", cpu_config.key)?;
+            match representative {
+                Some(individual) => writeln!(
+                    out,
+                    "<td title='{}'>{}",
+                    escape_html_for_attribute(&individual.path),
+                    render_timeline(individual, end)?,
+                )?,
+                None => writeln!(out, "<td>n/a")?,
+            }
+        }
+        writeln!(out, "</table>\n")?;
+    }
+
+    Ok(())
+}
+
+/// Renders the events of the given individual as an SVG, with one lane per event name. Spans are
+/// drawn as bars, and instantaneous events (such as FP and FCP) as vertical lines.
+fn render_timeline(individual: &JsonIndividual, end: f64) -> eyre::Result<String> {
+    static LABEL_WIDTH: f64 = 80.0;
+    static PLOT_WIDTH: f64 = 720.0;
+    static LANE_HEIGHT: usize = 14;
+
+    // Lay out lanes in order of first appearance.
+    let mut lanes: Vec<&str> = vec![];
+    for event in individual.events.iter() {
+        if !lanes.contains(&&*event.name) {
+            lanes.push(&event.name);
+        }
+    }
+    let scale = if end > 0.0 { PLOT_WIDTH / end } else { 0.0 };
+    let height = (lanes.len() + 1) * LANE_HEIGHT;
+
+    let mut result = String::default();
+    write!(
+        result,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{height}" font-family="sans-serif" font-size="11">"#,
+        LABEL_WIDTH + PLOT_WIDTH,
+    )?;
+    for (i, lane) in lanes.iter().enumerate() {
+        write!(
+            result,
+            r#"<text x="0" y="{}">{}</text>"#,
+            (i + 1) * LANE_HEIGHT - 3,
+            escape_html_for_inner_html(lane),
+        )?;
+    }
+    for event in individual.events.iter() {
+        let lane = lanes
+            .iter()
+            .position(|&lane| lane == event.name)
+            .expect("Guaranteed by lanes");
+        let x = LABEL_WIDTH + event.start * scale;
+        let y = lane * LANE_HEIGHT;
+        let title = match event.duration {
+            Some(duration) => format!(
+                "{} at {} for {}",
+                event.name,
+                fmt_seconds(event.start),
+                fmt_seconds(duration),
+            ),
+            None => format!("{} at {}", event.name, fmt_seconds(event.start)),
+        };
+        match event.duration {
+            Some(duration) => write!(
+                result,
+                r#"<rect x="{x:.2}" y="{}" width="{:.2}" height="{}" fill="steelblue"><title>{}</title></rect>"#,
+                y + 2,
+                // Keep very short spans visible.
+                (duration * scale).max(0.5),
+                LANE_HEIGHT - 4,
+                escape_html_for_inner_html(&title),
+            )?,
+            None => write!(
+                result,
+                r#"<line x1="{x:.2}" y1="{y}" x2="{x:.2}" y2="{height}" stroke="crimson"><title>{}</title></line>"#,
+                escape_html_for_inner_html(&title),
+            )?,
+        }
+    }
+    write!(
+        result,
+        r#"<text x="{LABEL_WIDTH}" y="{}">0</text><text x="{}" y="{}" text-anchor="end">{}</text>"#,
+        height - 2,
+        LABEL_WIDTH + PLOT_WIDTH,
+        height - 2,
+        fmt_seconds(end),
+    )?;
+    write!(result, "</svg>")?;
+
+    Ok(result)
+}
+
 fn escape_html_for_inner_html(text: &str) -> String {
     text.replace("&", "&amp;").replace("<", "&lt;")
 }
@@ -392,3 +1687,54 @@ fn escape_html_for_attribute(text: &str) -> String {
         .replace("'", "&apos;")
         .replace(r#"""#, "&quot;")
 }
+
+#[test]
+fn test_section_template() -> eyre::Result<()> {
+    // Compare line by line, ignoring blank lines and indentation left behind by template tags.
+    fn lines(html: &str) -> Vec<&str> {
+        html.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect()
+    }
+    let template = SectionTemplate {
+        site: "example",
+        plot: SitePlot::Image("data:,".to_owned()),
+        cpu_configs: vec!["cpu1"],
+        statistic_label: "min",
+        rows: vec![
+            SectionRow {
+                engine: "servo",
+                cells: vec![SectionCell {
+                    title: "n=5",
+                    value: Some("1.0ms".to_owned()),
+                    noisy: true,
+                    bimodal: false,
+                }],
+            },
+            SectionRow {
+                engine: "chromium",
+                cells: vec![SectionCell {
+                    title: "n=1",
+                    value: None,
+                    noisy: false,
+                    bimodal: false,
+                }],
+            },
+        ],
+        p_values: Some(PValueRow {
+            first: "servo",
+            second: "chromium",
+            cells: vec![Some(PValueCell {
+                t: "1.234".to_owned(),
+                p: "0.050".to_owned(),
+            })],
+        }),
+    };
+    assert_eq!(
+        lines(&template.render()?),
+        lines(include_str!("../testdata/report-section.html")),
+    );
+
+    Ok(())
+}
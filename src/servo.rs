@@ -9,20 +9,29 @@ use std::{
 use jane_eyre::eyre::{self, bail, OptionExt};
 use markup5ever_rcdom::NodeData;
 use perfetto_protos::{
-    trace::Trace,
-    trace_packet::trace_packet::Data,
+    builtin_clock::BuiltinClock,
+    clock_snapshot::ClockSnapshot,
+    debug_annotation::DebugAnnotation,
+    trace_packet::{
+        trace_packet::{Data, SequenceFlags},
+        TracePacket,
+    },
     track_event::{track_event, TrackEvent},
 };
-use protobuf::Message;
-use serde::Deserialize;
+use protobuf::CodedInputStream;
+use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use tracing::{debug, error_span, info, trace, warn};
 
 use crate::{
+    analyse::{dedupe_input_paths, expand_input_globs, open_trace, record_failed_path},
     dom::{make_html_tag_name, parse, tendril_to_str, Traverse},
     summary::{
-        Analysis, Event, EventKind, Individual, JsonRawSeries, JsonSummaries, SYNTHETIC_NAMES,
+        drop_implausible_events, Analysis, CategoryNames, Event, EventKind, Individual,
+        JsonRawSeries, JsonSummaries, MetricBasis, SummaryOptions, DEFAULT_LONG_TASK_THRESHOLD,
+        SUMMARIES_SCHEMA_VERSION, SYNTHETIC_NAMES,
     },
+    url_pattern::{resolve_url_pattern, resolve_url_pattern_to_all},
 };
 
 static RENDERER_NAMES: &'static str = "ScriptParseHTML ScriptEvaluate LayoutPerform Compositing";
@@ -41,8 +50,45 @@ static METRICS: &'static [(&'static str, &'static str)] = &[
     ("TTI", "TimeToInteractive"),
 ];
 
+/// Returns the built-in [CategoryNames], which a study can override.
+pub fn default_category_names() -> CategoryNames {
+    CategoryNames {
+        renderer: RENDERER_NAMES.to_owned(),
+        parse: PARSE_NAMES.to_owned(),
+        script: SCRIPT_NAMES.to_owned(),
+        layout: LAYOUT_NAMES.to_owned(),
+        rasterise: RASTERISE_NAMES.to_owned(),
+        no_url: NO_URL_NAMES.to_owned(),
+    }
+}
+
 pub fn main(args: Vec<String>) -> eyre::Result<()> {
-    let summaries = compute_summaries(args)?;
+    if args.first().is_some_and(|arg| arg == "--all-frames") {
+        let summaries_by_frame = compute_summaries_by_frame(
+            expand_input_globs(args[1..].to_vec())?,
+            DEFAULT_LONG_TASK_THRESHOLD,
+            &MetricAnchor::default(),
+            &[],
+            None,
+            SummaryOptions::default(),
+            &default_category_names(),
+        )?;
+        for (url, summaries) in summaries_by_frame {
+            println!(">>>>>> Frame {url}");
+            println!("{}", summaries.text()?);
+        }
+        return Ok(());
+    }
+
+    let summaries = compute_summaries(
+        expand_input_globs(args)?,
+        DEFAULT_LONG_TASK_THRESHOLD,
+        &MetricAnchor::default(),
+        &[],
+        None,
+        SummaryOptions::default(),
+        &default_category_names(),
+    )?;
 
     println!("{}", summaries.json());
     println!();
@@ -52,9 +98,62 @@ pub fn main(args: Vec<String>) -> eyre::Result<()> {
 }
 
 #[tracing::instrument(level = "error")]
-pub fn compute_summaries(args: Vec<String>) -> Result<JsonSummaries, eyre::Error> {
+pub fn compute_summaries(
+    args: Vec<String>,
+    long_task_threshold: Duration,
+    metric_anchor: &MetricAnchor,
+    annotation_filters: &[AnnotationFilter],
+    max_plausible_event_duration: Option<Duration>,
+    summary_options: SummaryOptions,
+    category_names: &CategoryNames,
+) -> Result<JsonSummaries, eyre::Error> {
     info!("Computing summaries");
-    let individuals = analyse_individuals(&args)?;
+    let individuals = analyse_individuals(
+        &args,
+        metric_anchor,
+        annotation_filters,
+        max_plausible_event_duration,
+        category_names,
+    )?;
+
+    summarise_individuals(individuals, long_task_threshold, summary_options)
+}
+
+/// Like [compute_summaries], but analyses each frame whose URL matches the URL pattern separately,
+/// returning the summaries of each frame by URL.
+#[tracing::instrument(level = "error")]
+pub fn compute_summaries_by_frame(
+    args: Vec<String>,
+    long_task_threshold: Duration,
+    metric_anchor: &MetricAnchor,
+    annotation_filters: &[AnnotationFilter],
+    max_plausible_event_duration: Option<Duration>,
+    summary_options: SummaryOptions,
+    category_names: &CategoryNames,
+) -> eyre::Result<BTreeMap<String, JsonSummaries>> {
+    info!("Computing summaries by frame");
+    let individuals_by_frame = analyse_individuals_by_frame(
+        &args,
+        metric_anchor,
+        annotation_filters,
+        max_plausible_event_duration,
+        category_names,
+    )?;
+
+    let mut result = BTreeMap::default();
+    for (url, individuals) in individuals_by_frame {
+        let summaries = summarise_individuals(individuals, long_task_threshold, summary_options)?;
+        result.insert(url, summaries);
+    }
+
+    Ok(result)
+}
+
+fn summarise_individuals(
+    individuals: Vec<IndividualAnalysis>,
+    long_task_threshold: Duration,
+    summary_options: SummaryOptions,
+) -> eyre::Result<JsonSummaries> {
     let durations_keys = individuals
         .iter()
         .flat_map(|s| s.durations.keys())
@@ -74,12 +173,16 @@ pub fn compute_summaries(args: Vec<String>) -> Result<JsonSummaries, eyre::Error
         .collect::<Vec<_>>();
 
     let analysis = Analysis { individuals };
+    let real_events_self_time =
+        analysis.self_time_summaries(durations_keys.iter().map(|name| &**name), summary_options);
 
     let mut real_events = vec![];
     let mut synthetic_and_interpreted_events = vec![];
 
     for name in durations_keys {
-        if let Ok(summary) = analysis.summary(|s| s.durations.get(&name).map(|d| d.as_secs_f64())) {
+        let getter = |s: &IndividualAnalysis| s.durations.get(&name).map(|d| d.as_secs_f64());
+        let summary = analysis.summary_with_options(summary_options, getter);
+        if let Ok(summary) = summary {
             real_events.push(summary.to_json(&name));
         };
     }
@@ -90,7 +193,7 @@ pub fn compute_summaries(args: Vec<String>) -> Result<JsonSummaries, eyre::Error
             kind: EventKind::SyntheticOrInterpreted,
             xs: vec![],
         };
-        if let Ok(summary) = analysis.summary(|s| {
+        let getter = |s: &IndividualAnalysis| {
             let events = match s.synthetic_events() {
                 Ok(events) => events,
                 Err(error) => {
@@ -105,44 +208,180 @@ pub fn compute_summaries(args: Vec<String>) -> Result<JsonSummaries, eyre::Error
                 .sum::<f64>();
             series.xs.push(result);
             Some(result)
-        }) {
+        };
+        // Trim outliers from the summary only, so the raw series still has every value.
+        let summary = analysis.summary_with_options(summary_options, getter);
+        if let Ok(summary) = summary {
             synthetic_and_interpreted_events.push(summary.to_json(synthetic_name));
         }
         raw_series.push(series);
     }
 
-    Ok(JsonSummaries {
+    let (long_task_summaries, long_task_counts, long_task_series) =
+        analysis.long_task_summaries(long_task_threshold, summary_options)?;
+    synthetic_and_interpreted_events.extend(long_task_summaries);
+    raw_series.extend(long_task_series);
+    let (fractions, fraction_series) = analysis.utilization_summaries(summary_options);
+    raw_series.extend(fraction_series);
+
+    // Summarise the peak and mean of each counter track in each individual.
+    let mut counters = long_task_counts;
+    let counter_names = analysis
+        .individuals
+        .iter()
+        .flat_map(|s| s.counters.keys())
+        .map(|name| name.to_owned())
+        .collect::<BTreeSet<_>>();
+    for counter_name in counter_names {
+        for statistic in ["peak", "mean"] {
+            let name = format!("{counter_name} ({statistic})");
+            let mut series = JsonRawSeries {
+                name: name.clone(),
+                kind: EventKind::Counter,
+                xs: vec![],
+            };
+            let getter = |s: &IndividualAnalysis| {
+                let values = s
+                    .counters
+                    .get(&counter_name)?
+                    .iter()
+                    .map(|&(_, value)| value);
+                let result = match statistic {
+                    "peak" => values.fold(f64::NEG_INFINITY, f64::max),
+                    _ => values.clone().sum::<f64>() / values.count() as f64,
+                };
+                series.xs.push(result);
+                Some(result)
+            };
+            let summary = analysis.summary_with_options(summary_options, getter);
+            if let Ok(summary) = summary {
+                counters.push(summary.to_count_json(&name));
+            }
+            raw_series.push(series);
+        }
+    }
+
+    let mut summaries = JsonSummaries {
+        schema_version: SUMMARIES_SCHEMA_VERSION,
         real_events,
+        real_events_self_time,
         synthetic_and_interpreted_events,
+        counters,
+        fractions,
         raw_series,
-    })
+        individuals: analysis.individuals_to_json(),
+        notes: vec![],
+    };
+    summaries.check_synthetic_phase_sum();
+
+    Ok(summaries)
+}
+
+pub fn analyse_individuals(
+    args: &[String],
+    metric_anchor: &MetricAnchor,
+    annotation_filters: &[AnnotationFilter],
+    max_plausible_event_duration: Option<Duration>,
+    category_names: &CategoryNames,
+) -> eyre::Result<Vec<IndividualAnalysis>> {
+    let individuals_by_frame = analyse_frames(
+        args,
+        metric_anchor,
+        annotation_filters,
+        max_plausible_event_duration,
+        category_names,
+        false,
+    )?;
+
+    Ok(individuals_by_frame.into_values().flatten().collect())
 }
 
-pub fn analyse_individuals(args: &[String]) -> eyre::Result<Vec<IndividualAnalysis>> {
+/// Like [analyse_individuals], but analyses each frame whose URL matches the URL pattern
+/// separately, such as the iframes of an ad-heavy page, returning the individuals by URL.
+pub fn analyse_individuals_by_frame(
+    args: &[String],
+    metric_anchor: &MetricAnchor,
+    annotation_filters: &[AnnotationFilter],
+    max_plausible_event_duration: Option<Duration>,
+    category_names: &CategoryNames,
+) -> eyre::Result<BTreeMap<String, Vec<IndividualAnalysis>>> {
+    analyse_frames(
+        args,
+        metric_anchor,
+        annotation_filters,
+        max_plausible_event_duration,
+        category_names,
+        true,
+    )
+}
+
+fn analyse_frames(
+    args: &[String],
+    metric_anchor: &MetricAnchor,
+    annotation_filters: &[AnnotationFilter],
+    max_plausible_event_duration: Option<Duration>,
+    category_names: &CategoryNames,
+    all_frames: bool,
+) -> eyre::Result<BTreeMap<String, Vec<IndividualAnalysis>>> {
     let url = args.iter().nth(0).unwrap().to_owned();
-    let paths = args.into_iter().skip(1).collect::<Vec<_>>();
+    let paths = dedupe_input_paths(&args[1..])?;
 
-    let mut individuals = vec![];
-    for (path, result) in paths
+    // Skip manifests whose traces are missing or empty, e.g. due to a collection glitch, so that
+    // one bad run does not abort the whole sample.
+    let mut valid_paths = vec![];
+    for path in paths.iter() {
+        match check_manifest(path) {
+            Ok(()) => valid_paths.push(path.to_owned()),
+            Err(error) => warn!(path, "Skipping manifest: {error}"),
+        }
+    }
+    if valid_paths.len() < paths.len() {
+        warn!(
+            skipped = paths.len() - valid_paths.len(),
+            total = paths.len(),
+            "Skipped manifests with missing or empty traces"
+        );
+    }
+
+    let mut individuals: BTreeMap<String, Vec<IndividualAnalysis>> = BTreeMap::default();
+    for (path, result) in valid_paths
         .iter()
-        .map(|path| (path.to_owned(), analyse_individual(&url, path)))
+        .map(|path| {
+            (
+                path.to_owned(),
+                analyse_individual(
+                    &url,
+                    path,
+                    metric_anchor,
+                    annotation_filters,
+                    max_plausible_event_duration,
+                    category_names,
+                    all_frames,
+                ),
+            )
+        })
         .collect::<Vec<_>>()
     {
         let span = error_span!("analyse", path = path);
         let _enter = span.enter();
         match result {
-            Ok(result) => individuals.push(result),
-            Err(error) => warn!("Failed to analyse file: {error}"),
+            Ok(result) => {
+                for (frame_url, individual) in result {
+                    individuals.entry(frame_url).or_default().push(individual);
+                }
+            }
+            Err(error) => {
+                warn!("Failed to analyse file: {error}");
+                record_failed_path(&path);
+            }
         }
     }
 
     Ok(individuals)
 }
 
-#[tracing::instrument(level = "error", skip(url))]
-fn analyse_individual(url: &str, path: &str) -> eyre::Result<IndividualAnalysis> {
-    info!("Analysing individual");
-
+/// Reads the given manifest, returning the paths to its HTML trace and Perfetto trace.
+fn read_manifest(path: &str) -> eyre::Result<(PathBuf, PathBuf)> {
     #[derive(Deserialize)]
     struct Manifest {
         html: PathBuf,
@@ -153,70 +392,190 @@ fn analyse_individual(url: &str, path: &str) -> eyre::Result<IndividualAnalysis>
     let manifest = serde_json::from_str::<Manifest>(&manifest)?;
 
     let manifest_dir = Path::new(path).parent().ok_or_eyre("Path has no parent")?;
-    let html_trace_path = manifest_dir.join(manifest.html);
+    Ok((
+        manifest_dir.join(manifest.html),
+        manifest_dir.join(manifest.perfetto),
+    ))
+}
+
+/// Checks that the traces in the given manifest exist and are not empty. The manifest may be
+/// stale, e.g. left behind by a failed run.
+fn check_manifest(path: &str) -> eyre::Result<()> {
+    let (html_trace_path, perfetto_trace_path) = read_manifest(path)?;
+    for trace_path in [html_trace_path, perfetto_trace_path] {
+        if !std::fs::exists(&trace_path)? {
+            bail!("Manifest refers to missing trace {trace_path:?}");
+        }
+        if std::fs::metadata(&trace_path)?.len() == 0 {
+            bail!("Manifest refers to empty trace {trace_path:?}");
+        }
+    }
+
+    Ok(())
+}
+
+#[tracing::instrument(
+    level = "error",
+    skip(url, metric_anchor, annotation_filters, category_names)
+)]
+fn analyse_individual(
+    url: &str,
+    path: &str,
+    metric_anchor: &MetricAnchor,
+    annotation_filters: &[AnnotationFilter],
+    max_plausible_event_duration: Option<Duration>,
+    category_names: &CategoryNames,
+    all_frames: bool,
+) -> eyre::Result<Vec<(String, IndividualAnalysis)>> {
+    info!("Analysing individual");
+
+    let (html_trace_path, perfetto_trace_path) = read_manifest(path)?;
     let html_trace_path = html_trace_path.to_str().ok_or_eyre("Unsupported path")?;
-    let perfetto_trace_path = manifest_dir.join(manifest.perfetto);
     let perfetto_trace_path = perfetto_trace_path
         .to_str()
         .ok_or_eyre("Unsupported path")?;
 
-    let html_trace = analyse_html_trace(url, html_trace_path)?;
-    let perfetto_trace = analyse_perfetto_trace(url, perfetto_trace_path)?;
+    let all_html_events = read_html_trace(html_trace_path)?;
+    let all_perfetto_events = read_perfetto_trace(perfetto_trace_path)?;
 
-    // Perfetto traces contain all of the durations we need except metrics,
-    // which are in HTML traces only for now, so we need to merge the traces.
-    // First we need to align the start times.
-    debug!(
-        "First event in HTML trace: {:?}",
-        html_trace.relevant_events[0]
-    );
-    debug!(
-        "First event in Perfetto trace: {:?}",
-        perfetto_trace.relevant_events[0]
-    );
-    if html_trace.relevant_events[0].name != perfetto_trace.relevant_events[0].name {
-        bail!(
-            "First events in HTML trace and Perfetto trace are different ({} != {})",
-            html_trace.relevant_events[0].name,
-            perfetto_trace.relevant_events[0].name,
+    let analyse_frame = |url: &str| -> eyre::Result<IndividualAnalysis> {
+        let html_trace =
+            analyse_html_trace(url, html_trace_path, &all_html_events, category_names)?;
+        let perfetto_trace = analyse_perfetto_trace(
+            url,
+            perfetto_trace_path,
+            &all_perfetto_events,
+            annotation_filters,
+            category_names,
+        )?;
+
+        // Perfetto traces contain all of the durations we need except metrics,
+        // which are in HTML traces only for now, so we need to merge the traces.
+        // First we need to align the start times.
+        debug!(
+            "First event in HTML trace: {:?}",
+            html_trace.relevant_events[0]
         );
+        debug!(
+            "First event in Perfetto trace: {:?}",
+            perfetto_trace.relevant_events[0]
+        );
+        // Both traces normally start with the same event, but if one of them is missing its first
+        // event (e.g. `ScriptParseHTML`), align them on the first event name they have in common.
+        let html_first_name = &html_trace.relevant_events[0].name;
+        let perfetto_first_name = &perfetto_trace.relevant_events[0].name;
+        let perfetto_names = perfetto_trace
+            .relevant_events
+            .iter()
+            .map(|e| &e.name)
+            .collect::<BTreeSet<_>>();
+        let Some(html_anchor) = html_trace
+            .relevant_events
+            .iter()
+            .find(|e| perfetto_names.contains(&e.name))
+        else {
+            bail!(
+                "HTML trace {html_trace_path:?} and Perfetto trace {perfetto_trace_path:?} have no events in common \
+                (first events are {html_first_name} and {perfetto_first_name}); were they captured from the same run?"
+            );
+        };
+        let perfetto_anchor = perfetto_trace
+            .relevant_events
+            .iter()
+            .find(|e| e.name == html_anchor.name)
+            .expect("Guaranteed by perfetto_names");
+        if html_first_name != perfetto_first_name {
+            warn!(
+                html_trace_path,
+                perfetto_trace_path,
+                %html_first_name,
+                %perfetto_first_name,
+                aligned_on = %html_anchor.name,
+                "First events in HTML trace and Perfetto trace are different; aligning on the first event \
+                they have in common, but check that the traces were captured from the same run"
+            );
+        }
+        let perfetto_trace_start = perfetto_trace.relevant_events[0].start;
+        // Convert HTML trace times to Perfetto trace times, dropping any events before the start of
+        // the Perfetto trace.
+        let (html_anchor_start, perfetto_anchor_start) = (html_anchor.start, perfetto_anchor.start);
+        let html_trace_events = html_trace.relevant_events.into_iter().filter_map(|e| {
+            let start = (e.start + perfetto_anchor_start - perfetto_trace_start)
+                .checked_sub(html_anchor_start)?;
+            Some(Event { start, ..e })
+        });
+        let perfetto_trace_events = perfetto_trace.relevant_events.into_iter().map(|e| Event {
+            start: e.start - perfetto_trace_start,
+            ..e
+        });
+        // Now put the HTML-only events, plus all of the Perfetto events, in a sorted Vec.
+        let mut relevant_events = html_trace_events
+            .filter(|e| HTML_ONLY_NAMES.split(" ").find(|&n| n == e.name).is_some())
+            .chain(perfetto_trace_events)
+            .collect::<Vec<_>>();
+        relevant_events.sort_by(|p, q| p.start.cmp(&q.start).then(p.duration.cmp(&q.duration)));
+        // Finally merge in the HTML-only durations.
+        let mut durations = perfetto_trace.durations;
+        durations.extend(
+            html_trace
+                .durations
+                .into_iter()
+                .filter(|(name, _)| HTML_ONLY_NAMES.split(" ").find(|&n| n == name).is_some()),
+        );
+        // If any events were implausibly long, drop them and recompute the durations without them.
+        let event_count = relevant_events.len();
+        let relevant_events = drop_implausible_events(
+            relevant_events,
+            |e| e.duration,
+            max_plausible_event_duration,
+            path,
+        );
+        if relevant_events.len() < event_count {
+            for (name, duration) in durations.iter_mut() {
+                *duration = IndividualAnalysis::sum_duration(&relevant_events, name);
+            }
+        }
+
+        Ok(IndividualAnalysis {
+            path: path.to_owned(),
+            relevant_events: relevant_events,
+            durations: durations,
+            counters: perfetto_trace.counters,
+            metric_anchor: metric_anchor.clone(),
+            category_names: category_names.clone(),
+        })
+    };
+
+    if !all_frames {
+        return Ok(vec![(url.to_owned(), analyse_frame(url)?)]);
     }
-    let html_trace_start = html_trace.relevant_events[0].start;
-    let html_trace_events = html_trace.relevant_events.into_iter().map(|e| Event {
-        start: e.start - html_trace_start,
-        ..e
-    });
-    let perfetto_trace_start = perfetto_trace.relevant_events[0].start;
-    let perfetto_trace_events = perfetto_trace.relevant_events.into_iter().map(|e| Event {
-        start: e.start - perfetto_trace_start,
-        ..e
-    });
-    // Now put the HTML-only events, plus all of the Perfetto events, in a sorted Vec.
-    let mut relevant_events = html_trace_events
-        .filter(|e| HTML_ONLY_NAMES.split(" ").find(|&n| n == e.name).is_some())
-        .chain(perfetto_trace_events)
-        .collect::<Vec<_>>();
-    relevant_events.sort_by(|p, q| p.start.cmp(&q.start).then(p.duration.cmp(&q.duration)));
-    // Finally merge in the HTML-only durations.
-    let mut durations = perfetto_trace.durations;
-    durations.extend(
-        html_trace
-            .durations
-            .into_iter()
-            .filter(|(name, _)| HTML_ONLY_NAMES.split(" ").find(|&n| n == name).is_some()),
+    // Analyse each distinct URL that the pattern matches as a frame of its own.
+    let frame_urls = resolve_url_pattern_to_all(
+        url,
+        all_html_events
+            .iter()
+            .filter_map(|e| e.metadata.as_ref())
+            .map(|m| &*m.url),
     );
+    if frame_urls.is_empty() {
+        bail!("No entry with matching .metadata.url! Skipping individual");
+    }
+    let mut result = vec![];
+    for frame_url in frame_urls {
+        match analyse_frame(frame_url) {
+            Ok(individual) => result.push((frame_url.to_owned(), individual)),
+            Err(error) => warn!(frame_url, "Failed to analyse frame: {error}"),
+        }
+    }
 
-    Ok(IndividualAnalysis {
-        path: path.to_owned(),
-        relevant_events: relevant_events,
-        durations: durations,
-    })
+    Ok(result)
 }
 
+/// Reads all of the events in the given HTML trace, in order.
 #[tracing::instrument(level = "error")]
-fn analyse_html_trace(url: &str, path: &str) -> eyre::Result<IndividualAnalysis> {
+fn read_html_trace(path: &str) -> eyre::Result<Vec<HtmlTraceEvent>> {
     let mut input = vec![];
-    File::open(path)?.read_to_end(&mut input)?;
+    open_trace(path)?.read_to_end(&mut input)?;
     let dom = parse(&input)?;
 
     let mut script = None;
@@ -259,13 +618,37 @@ fn analyse_html_trace(url: &str, path: &str) -> eyre::Result<IndividualAnalysis>
             .cmp(&q.startTime)
             .then(p.endTime.cmp(&q.endTime))
     });
+
+    Ok(all_events)
+}
+
+#[tracing::instrument(level = "error", skip(all_events, category_names))]
+fn analyse_html_trace(
+    url: &str,
+    path: &str,
+    all_events: &[HtmlTraceEvent],
+    category_names: &CategoryNames,
+) -> eyre::Result<IndividualAnalysis> {
+    // Resolve any pattern to the first matching URL, or leave it as is and bail below.
+    let url = resolve_url_pattern(
+        url,
+        all_events
+            .iter()
+            .filter_map(|e| e.metadata.as_ref())
+            .map(|m| &*m.url),
+    )
+    .unwrap_or(url);
     let relevant_events = all_events
         .iter()
         .filter(|e| {
             // Ignore any events with the wrong .metadata.url, since they are for other iframes.
-            // Categories in NO_URL_NAMES have no .metadata.url.
+            // Categories in `no_url` have no .metadata.url.
             e.metadata.as_ref().is_some_and(|m| m.url == url)
-                || NO_URL_NAMES.split(" ").find(|&n| n == e.category).is_some()
+                || category_names
+                    .no_url
+                    .split(" ")
+                    .find(|&n| n == e.category)
+                    .is_some()
         })
         .cloned()
         .collect::<Vec<_>>();
@@ -297,8 +680,7 @@ fn analyse_html_trace(url: &str, path: &str) -> eyre::Result<IndividualAnalysis>
     }
 
     let mut durations = BTreeMap::default();
-    let interesting_categories = format!("{RENDERER_NAMES}");
-    for category in interesting_categories.split(" ") {
+    for category in category_names.renderer.split(" ") {
         let duration = IndividualAnalysis::sum_duration(&result, category);
         durations.insert(category.to_owned(), duration);
     }
@@ -307,51 +689,188 @@ fn analyse_html_trace(url: &str, path: &str) -> eyre::Result<IndividualAnalysis>
         path: path.to_owned(),
         relevant_events: result,
         durations,
+        counters: BTreeMap::default(),
+        metric_anchor: MetricAnchor::default(),
+        category_names: category_names.clone(),
     })
 }
 
+/// All of the slices in a Perfetto trace, in order, and the values of each counter track.
+struct PerfettoTraceEvents {
+    events: Vec<Event>,
+    counters: BTreeMap<String, Vec<(Duration, f64)>>,
+}
+
 #[tracing::instrument(level = "error")]
-fn analyse_perfetto_trace(url: &str, path: &str) -> eyre::Result<IndividualAnalysis> {
+fn read_perfetto_trace(path: &str) -> eyre::Result<PerfettoTraceEvents> {
     // Tracks can have slices, instants, and counters. Slices must have stack-like behaviour within
     // a track, so we can use a stack to find pairs and merge them together.
     let mut tracks: HashMap<u64, Vec<PendingSlice>> = HashMap::default();
     struct PendingSlice {
         start: u64,
+        /// None if the name was interned but never defined, so the slice is only kept for pairing.
+        name: Option<String>,
         event: TrackEvent,
     }
+    // Buggy instrumentation can break that, so count the slice ends we had to guess at per track.
+    let mut slice_anomaly_counts: HashMap<u64, usize> = HashMap::default();
+    // Counters have no duration, so they need no stack, only a series of values per track.
+    let mut counter_tracks: HashMap<u64, Vec<(Duration, f64)>> = HashMap::default();
+    let mut track_names: HashMap<u64, String> = HashMap::default();
+    let mut interned_event_names: HashMap<(u32, u64), String> = HashMap::default();
+    let mut missing_interned_name_count = 0usize;
+
+    let mut clocks = TraceClocks::default();
+    let mut unknown_clock_ids = BTreeSet::default();
 
     let mut all_events = vec![];
-    for mut packet in Trace::parse_from_reader(&mut File::open(path)?)?.packet {
-        // Assume the default clock (1ns, absolute).
-        assert!(packet.timestamp_clock_id.is_none());
+    // Read one packet at a time, rather than the whole `Trace`, so memory usage stays bounded on
+    // multi-gigabyte traces.
+    let mut reader = CountingReader {
+        inner: open_trace(path)?,
+        bytes_read: 0,
+    };
+    let mut input = CodedInputStream::new(&mut reader);
+    let mut truncation = None;
+    loop {
+        // If we automate Servo with SIGTERM, the last packet may be cut off mid-write, so treat
+        // the packets before it as the whole trace.
+        let packet_start = input.pos();
+        let mut packet = match read_next_trace_packet(&mut input) {
+            Ok(Some(packet)) => packet,
+            Ok(None) => break,
+            Err(error) => {
+                truncation = Some((packet_start, error));
+                break;
+            }
+        };
+        let sequence_id = packet.trusted_packet_sequence_id();
+        // Event names can be interned, that is, sent once per sequence and referred to by id.
+        // <https://perfetto.dev/docs/instrumentation/track-events#interning>
+        if packet.sequence_flags() & SequenceFlags::SEQ_INCREMENTAL_STATE_CLEARED as u32 != 0 {
+            interned_event_names.retain(|&(id, _), _| id != sequence_id);
+        }
+        if let Some(interned_data) = packet.interned_data.as_ref() {
+            for event_name in interned_data.event_names.iter() {
+                interned_event_names.insert(
+                    (sequence_id, event_name.iid()),
+                    event_name.name().to_owned(),
+                );
+            }
+        }
+        // Sequence defaults usually come in packets with no other data, or with a clock snapshot.
+        if let Some(defaults) = packet.trace_packet_defaults.as_ref() {
+            if let Some(clock_id) = defaults.timestamp_clock_id {
+                clocks.sequence_clock_ids.insert(sequence_id, clock_id);
+            }
+        }
+        // Packets with only interned data or defaults have nothing else for us.
+        let Some(data) = packet.data.take() else {
+            continue;
+        };
+        if let Data::ClockSnapshot(snapshot) = &data {
+            clocks.add_snapshot(sequence_id, snapshot);
+            continue;
+        }
+        // Descriptors need no timestamp, so keep their track names even if the clock is unknown.
+        if let Data::TrackDescriptor(descriptor) = &data {
+            // Thread and process tracks may be named by their descriptor instead.
+            let name = if descriptor.has_name() {
+                Some(descriptor.name())
+            } else if descriptor.thread.has_thread_name() {
+                Some(descriptor.thread.thread_name())
+            } else if descriptor.process.has_process_name() {
+                Some(descriptor.process.process_name())
+            } else {
+                None
+            };
+            if let Some(name) = name {
+                track_names.insert(descriptor.uuid(), name.to_owned());
+            }
+            continue;
+        }
+        let clock_id = packet
+            .timestamp_clock_id
+            .or_else(|| clocks.sequence_clock_ids.get(&sequence_id).copied());
+        let Some(timestamp) = clocks.to_primary(sequence_id, clock_id, packet.timestamp()) else {
+            if unknown_clock_ids.insert(clock_id) {
+                warn!(?clock_id, "Skipping packets with unknown clock");
+            }
+            continue;
+        };
 
-        match packet.data.take().ok_or_eyre("TracePacket has no data")? {
+        match data {
             Data::TrackEvent(event) => {
+                if event.type_() == track_event::Type::TYPE_COUNTER {
+                    let value = if event.has_double_counter_value() {
+                        event.double_counter_value()
+                    } else {
+                        event.counter_value() as f64
+                    };
+                    counter_tracks
+                        .entry(event.track_uuid())
+                        .or_default()
+                        .push((Duration::from_nanos(timestamp), value));
+                    continue;
+                }
                 let slice_stack = tracks.entry(event.track_uuid()).or_default();
                 match event.type_() {
                     track_event::Type::TYPE_SLICE_BEGIN => {
+                        // Fall back to the inline name if the name is not interned.
+                        let name = if event.has_name_iid() {
+                            let name = interned_event_names.get(&(sequence_id, event.name_iid()));
+                            if name.is_none() {
+                                missing_interned_name_count += 1;
+                            }
+                            name.cloned()
+                        } else {
+                            Some(event.name().to_owned())
+                        };
                         slice_stack.push(PendingSlice {
-                            start: packet.timestamp(),
+                            start: timestamp,
+                            name,
                             event,
                         });
                     }
                     track_event::Type::TYPE_SLICE_END => {
-                        let slice = slice_stack
-                            .pop()
-                            .ok_or_eyre("Slice stack for track is empty")?;
-                        if event.has_name() {
-                            assert_eq!(event.name(), slice.event.name());
+                        // End the innermost slice with the same name, if the end has a name, or
+                        // the innermost slice otherwise. Skip ends with no slice to end.
+                        let index = if event.has_name() {
+                            slice_stack
+                                .iter()
+                                .rposition(|slice| slice.name.as_deref() == Some(event.name()))
+                        } else {
+                            slice_stack.len().checked_sub(1)
+                        };
+                        let Some(index) = index else {
+                            *slice_anomaly_counts.entry(event.track_uuid()).or_default() += 1;
+                            continue;
+                        };
+                        if index != slice_stack.len() - 1 {
+                            *slice_anomaly_counts.entry(event.track_uuid()).or_default() += 1;
+                        }
+                        let slice = slice_stack.remove(index);
+                        let Some(name) = slice.name else {
+                            continue;
+                        };
+                        let mut metadata = slice
+                            .event
+                            .debug_annotations
+                            .into_iter()
+                            .map(|a| (a.name().to_owned(), a))
+                            .collect::<BTreeMap<_, _>>();
+                        // Label the event with its track, such as the thread it ran on.
+                        if let Some(track_name) = track_names.get(&event.track_uuid()) {
+                            let mut annotation = DebugAnnotation::new();
+                            annotation.set_name("track".to_owned());
+                            annotation.set_string_value(track_name.to_owned());
+                            metadata.insert("track".to_owned(), annotation);
                         }
                         let event = Event {
-                            name: slice.event.name().to_owned(),
+                            name,
                             start: Duration::from_nanos(slice.start),
-                            duration: Some(Duration::from_nanos(packet.timestamp() - slice.start)),
-                            metadata: slice
-                                .event
-                                .debug_annotations
-                                .into_iter()
-                                .map(|a| (a.name().to_owned(), a))
-                                .collect(),
+                            duration: Some(Duration::from_nanos(timestamp - slice.start)),
+                            metadata,
                         };
                         all_events.push(event);
                     }
@@ -362,18 +881,158 @@ fn analyse_perfetto_trace(url: &str, path: &str) -> eyre::Result<IndividualAnaly
         }
     }
 
+    drop(input);
+    if let Some((packet_start, error)) = truncation {
+        // Count the rest of the trace as we go, rather than reading the whole trace again. The
+        // rest may be unreadable too, like a truncated gzip stream, so count what we can.
+        if let Err(read_error) = std::io::copy(&mut reader, &mut std::io::sink()) {
+            debug!(error = ?read_error, "Failed to read the rest of the truncated trace");
+        }
+        warn!(
+            discarded_bytes = reader.bytes_read.saturating_sub(packet_start),
+            "Trace is truncated; ignoring the rest of the trace: {error}"
+        );
+    }
+
+    if missing_interned_name_count > 0 {
+        warn!(
+            missing_interned_name_count,
+            "Skipping slices whose interned event names were never defined"
+        );
+    }
+    for (track_uuid, anomaly_count) in slice_anomaly_counts {
+        warn!(
+            track = ?track_names.get(&track_uuid),
+            track_uuid,
+            anomaly_count,
+            "Slice ends on track were not properly nested; paired them by name where possible"
+        );
+    }
+
+    // Slices that never ended, e.g. because the trace is truncated, have no duration to count.
+    let unended_slice_count = tracks.values().map(|stack| stack.len()).sum::<usize>();
+    if unended_slice_count > 0 {
+        debug!(unended_slice_count, "Dropping slices that never ended");
+    }
+
     all_events.sort_by(|p, q| p.start.cmp(&q.start).then(p.duration.cmp(&q.duration)));
 
+    // Counter tracks are not tied to any URL, so keep them all, keyed by track name.
+    let counters = counter_tracks
+        .into_iter()
+        .map(|(uuid, values)| {
+            let name = track_names
+                .remove(&uuid)
+                .unwrap_or_else(|| format!("Counter{uuid}"));
+            (name, values)
+        })
+        .collect();
+
+    Ok(PerfettoTraceEvents {
+        events: all_events,
+        counters,
+    })
+}
+
+#[test]
+fn test_read_perfetto_trace_with_sequence_defaults() -> eyre::Result<()> {
+    use perfetto_protos::{
+        clock_snapshot::clock_snapshot::Clock, trace::Trace,
+        trace_packet_defaults::TracePacketDefaults,
+    };
+    use protobuf::{Message, MessageField};
+
+    // Like the Perfetto SDK, send the sequence’s default clock together with its clock snapshot.
+    let clock = |clock_id: u32, timestamp: u64| {
+        let mut clock = Clock::new();
+        clock.set_clock_id(clock_id);
+        clock.set_timestamp(timestamp);
+        clock
+    };
+    let mut snapshot = ClockSnapshot::new();
+    snapshot.clocks = vec![
+        clock(BuiltinClock::BUILTIN_CLOCK_BOOTTIME as u32, 1_000_000),
+        clock(64, 0),
+    ];
+    let mut defaults = TracePacketDefaults::new();
+    defaults.timestamp_clock_id = Some(64);
+    let mut first = TracePacket::new();
+    first.set_trusted_packet_sequence_id(1);
+    first.trace_packet_defaults = MessageField::some(defaults);
+    first.data = Some(Data::ClockSnapshot(snapshot));
+
+    let slice = |timestamp: u64, type_: track_event::Type| {
+        let mut event = TrackEvent::new();
+        event.set_type(type_);
+        event.set_track_uuid(1);
+        event.set_name("ScriptEvaluate".to_owned());
+        let mut packet = TracePacket::new();
+        packet.set_trusted_packet_sequence_id(1);
+        packet.set_timestamp(timestamp);
+        packet.data = Some(Data::TrackEvent(event));
+        packet
+    };
+    let mut trace = Trace::new();
+    trace.packet = vec![
+        first,
+        slice(10, track_event::Type::TYPE_SLICE_BEGIN),
+        slice(30, track_event::Type::TYPE_SLICE_END),
+    ];
+    let dir = mktemp::Temp::new_dir()?;
+    let path = dir.join("servo1.pftrace");
+    std::fs::write(&path, trace.write_to_bytes()?)?;
+
+    let result = read_perfetto_trace(path.to_str().ok_or_eyre("Unsupported path")?)?;
+    let events = result
+        .events
+        .iter()
+        .map(|e| (&*e.name, e.start, e.duration))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [(
+            "ScriptEvaluate",
+            Duration::from_nanos(1_000_010),
+            Some(Duration::from_nanos(20))
+        )]
+    );
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "error", skip(trace, annotation_filters, category_names))]
+fn analyse_perfetto_trace(
+    url: &str,
+    path: &str,
+    trace: &PerfettoTraceEvents,
+    annotation_filters: &[AnnotationFilter],
+    category_names: &CategoryNames,
+) -> eyre::Result<IndividualAnalysis> {
+    let all_events = &trace.events;
+    // Resolve any pattern to the first matching URL, or leave it as is and bail below.
+    let url = resolve_url_pattern(
+        url,
+        all_events
+            .iter()
+            .filter_map(|e| e.metadata.get("url"))
+            .map(|v| v.string_value()),
+    )
+    .unwrap_or(url);
     let relevant_events = all_events
         .iter()
         .filter(|e| {
             // Ignore any entries with the wrong .metadata.url, since they are for other iframes.
-            // Categories in NO_URL_NAMES have no .metadata.url.
+            // Categories in `no_url` have no .metadata.url.
             e.metadata
                 .get("url")
                 .is_some_and(|v| v.string_value() == url)
-                || NO_URL_NAMES.split(" ").find(|&n| n == e.name).is_some()
+                || category_names
+                    .no_url
+                    .split(" ")
+                    .find(|&n| n == e.name)
+                    .is_some()
         })
+        .filter(|e| annotation_filters.iter().all(|filter| filter.keeps(e)))
         .collect::<Vec<_>>();
     if relevant_events
         .iter()
@@ -401,8 +1060,7 @@ fn analyse_perfetto_trace(url: &str, path: &str) -> eyre::Result<IndividualAnaly
     }
 
     let mut durations = BTreeMap::default();
-    let interesting_event_names = format!("{RENDERER_NAMES}");
-    for name in interesting_event_names.split(" ") {
+    for name in category_names.renderer.split(" ") {
         let duration = IndividualAnalysis::sum_duration(&result, name);
         debug!("{name}: {:?}", duration);
         durations.insert(name.to_owned(), duration);
@@ -412,11 +1070,249 @@ fn analyse_perfetto_trace(url: &str, path: &str) -> eyre::Result<IndividualAnaly
         path: path.to_owned(),
         relevant_events: result,
         durations,
+        counters: trace.counters.clone(),
+        metric_anchor: MetricAnchor::default(),
+        category_names: category_names.clone(),
     };
 
     Ok(result)
 }
 
+/// Reader that counts the bytes read through it, including those a buffering reader on top of it
+/// has read ahead but not yet consumed.
+struct CountingReader<R> {
+    inner: R,
+    bytes_read: u64,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let len = self.inner.read(buf)?;
+        self.bytes_read += len as u64;
+        Ok(len)
+    }
+}
+
+/// Reads the next packet of a Perfetto trace, or None at the end of the trace. A `Trace` is just
+/// `repeated TracePacket packet = 1`, so each packet is a length-delimited field with that tag.
+/// <https://perfetto.dev/docs/reference/trace-packet-proto>
+fn read_next_trace_packet(input: &mut CodedInputStream) -> eyre::Result<Option<TracePacket>> {
+    const PACKET_TAG: u32 = (1 << 3) | 2;
+    let Some(tag) = input.read_raw_tag_or_eof()? else {
+        return Ok(None);
+    };
+    if tag != PACKET_TAG {
+        bail!("Unexpected tag in Trace: {tag}");
+    }
+
+    Ok(Some(input.read_message()?))
+}
+
+#[test]
+fn test_read_next_trace_packet() -> eyre::Result<()> {
+    use perfetto_protos::trace::Trace;
+    use protobuf::Message;
+
+    let mut trace = Trace::new();
+    for i in 0..100_000 {
+        let mut packet = TracePacket::new();
+        packet.set_timestamp(i);
+        trace.packet.push(packet);
+    }
+    let bytes = trace.write_to_bytes()?;
+
+    let mut reader = &bytes[..];
+    let mut input = CodedInputStream::new(&mut reader);
+    let mut timestamps = vec![];
+    while let Some(packet) = read_next_trace_packet(&mut input)? {
+        timestamps.push(packet.timestamp());
+    }
+    assert_eq!(timestamps, (0..100_000).collect::<Vec<_>>());
+
+    // A truncated packet is an error, but the packets before it are still readable.
+    let mut reader = &bytes[..bytes.len() - 1];
+    let mut input = CodedInputStream::new(&mut reader);
+    for i in 0..99_999 {
+        assert_eq!(read_next_trace_packet(&mut input)?.unwrap().timestamp(), i);
+    }
+    assert!(read_next_trace_packet(&mut input).is_err());
+
+    Ok(())
+}
+
+/// Converts timestamps from any clock in a Perfetto trace to the primary trace clock, in
+/// nanoseconds, using the latest `ClockSnapshot` that includes both clocks.
+/// <https://perfetto.dev/docs/concepts/clock-sync>
+#[derive(Debug)]
+struct TraceClocks {
+    primary_clock_id: u32,
+    /// Latest snapshot of each clock, keyed by [`TraceClocks::key`].
+    snapshots: HashMap<(Option<u32>, u32), ClockSample>,
+    /// Latest absolute timestamp of each incremental clock in each packet sequence.
+    incremental_timestamps: HashMap<(u32, u32), u64>,
+    /// Default clock of each packet sequence, from its `trace_packet_defaults`.
+    sequence_clock_ids: HashMap<u32, u32>,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct ClockSample {
+    timestamp: u64,
+    /// Multiplier from the clock’s unit to nanoseconds.
+    multiplier: u64,
+    /// Primary clock timestamp at the same moment.
+    primary_timestamp: u64,
+    /// Whether packet timestamps on this clock are deltas from the previous packet’s timestamp.
+    is_incremental: bool,
+}
+
+impl Default for TraceClocks {
+    fn default() -> Self {
+        Self {
+            primary_clock_id: BuiltinClock::BUILTIN_CLOCK_BOOTTIME as u32,
+            snapshots: HashMap::default(),
+            incremental_timestamps: HashMap::default(),
+            sequence_clock_ids: HashMap::default(),
+        }
+    }
+}
+
+impl TraceClocks {
+    /// Clock ids 64 and up are scoped to the packet sequence that defines them, so the same id
+    /// can mean different clocks in different sequences.
+    fn key(sequence_id: u32, clock_id: u32) -> (Option<u32>, u32) {
+        if clock_id >= 64 {
+            (Some(sequence_id), clock_id)
+        } else {
+            (None, clock_id)
+        }
+    }
+
+    fn add_snapshot(&mut self, sequence_id: u32, snapshot: &ClockSnapshot) {
+        if snapshot.has_primary_trace_clock() {
+            self.primary_clock_id = snapshot.primary_trace_clock() as u32;
+        }
+        let Some(primary_timestamp) = snapshot
+            .clocks
+            .iter()
+            .find(|clock| clock.clock_id() == self.primary_clock_id)
+            .map(|clock| clock.timestamp())
+        else {
+            warn!("ClockSnapshot has no primary trace clock; ignoring");
+            return;
+        };
+        for clock in snapshot.clocks.iter() {
+            let sample = ClockSample {
+                timestamp: clock.timestamp(),
+                multiplier: clock.unit_multiplier_ns.unwrap_or(1),
+                primary_timestamp,
+                is_incremental: clock.is_incremental(),
+            };
+            self.snapshots
+                .insert(Self::key(sequence_id, clock.clock_id()), sample);
+            // The snapshot gives the starting point for the deltas that follow.
+            if sample.is_incremental {
+                self.incremental_timestamps
+                    .insert((sequence_id, clock.clock_id()), clock.timestamp());
+            }
+        }
+    }
+
+    /// Returns the given timestamp of a packet in the given sequence on the primary trace clock,
+    /// or None if the clock is unknown. Packets with no clock use the primary trace clock.
+    ///
+    /// Timestamps on incremental clocks are decoded against the previous packet in the same
+    /// sequence, so every such packet must be passed here in order.
+    fn to_primary(
+        &mut self,
+        sequence_id: u32,
+        clock_id: Option<u32>,
+        timestamp: u64,
+    ) -> Option<u64> {
+        let clock_id = match clock_id {
+            None => return Some(timestamp),
+            Some(clock_id) if clock_id == self.primary_clock_id => return Some(timestamp),
+            Some(clock_id) => clock_id,
+        };
+        let sample = *self.snapshots.get(&Self::key(sequence_id, clock_id))?;
+        let timestamp = if sample.is_incremental {
+            let previous = self
+                .incremental_timestamps
+                .entry((sequence_id, clock_id))
+                .or_insert(sample.timestamp);
+            *previous = previous.wrapping_add(timestamp);
+            *previous
+        } else {
+            timestamp
+        };
+        let offset =
+            (i128::from(timestamp) - i128::from(sample.timestamp)) * i128::from(sample.multiplier);
+        u64::try_from(i128::from(sample.primary_timestamp) + offset).ok()
+    }
+}
+
+#[test]
+fn test_trace_clocks() {
+    use perfetto_protos::clock_snapshot::clock_snapshot::Clock;
+
+    let clock = |clock_id: u32, timestamp: u64, unit_multiplier_ns: Option<u64>| {
+        let mut clock = Clock::new();
+        clock.set_clock_id(clock_id);
+        clock.set_timestamp(timestamp);
+        clock.unit_multiplier_ns = unit_multiplier_ns;
+        clock
+    };
+    let mut snapshot = ClockSnapshot::new();
+    snapshot.clocks = vec![
+        clock(BuiltinClock::BUILTIN_CLOCK_BOOTTIME as u32, 1_000_000, None),
+        clock(BuiltinClock::BUILTIN_CLOCK_MONOTONIC as u32, 400_000, None),
+        clock(64, 10, Some(1_000)),
+        clock(65, 2_000_000, None),
+    ];
+    let mut incremental = clock(66, 500, None);
+    incremental.set_is_incremental(true);
+    snapshot.clocks.push(incremental);
+    let mut clocks = TraceClocks::default();
+    clocks.add_snapshot(1, &snapshot);
+
+    let boottime = BuiltinClock::BUILTIN_CLOCK_BOOTTIME as u32;
+    let monotonic = BuiltinClock::BUILTIN_CLOCK_MONOTONIC as u32;
+    assert_eq!(clocks.to_primary(1, None, 123), Some(123));
+    assert_eq!(clocks.to_primary(1, Some(boottime), 123), Some(123));
+    assert_eq!(
+        clocks.to_primary(1, Some(monotonic), 400_500),
+        Some(1_000_500)
+    );
+    assert_eq!(
+        clocks.to_primary(1, Some(monotonic), 399_000),
+        Some(999_000)
+    );
+    assert_eq!(clocks.to_primary(1, Some(64), 12), Some(1_002_000));
+    assert_eq!(clocks.to_primary(1, Some(64), 9), Some(999_000));
+    // Builtin clocks are shared by all sequences, but sequence-scoped clocks are not.
+    assert_eq!(
+        clocks.to_primary(2, Some(monotonic), 400_500),
+        Some(1_000_500)
+    );
+    assert_eq!(clocks.to_primary(2, Some(64), 12), None);
+    // Incremental clocks are deltas from the previous packet in the same sequence.
+    assert_eq!(clocks.to_primary(1, Some(66), 100), Some(1_000_100));
+    assert_eq!(clocks.to_primary(1, Some(66), 50), Some(1_000_150));
+    assert_eq!(clocks.to_primary(2, Some(66), 50), None);
+    // Unknown clock, and a timestamp that would be before the start of the primary clock.
+    assert_eq!(clocks.to_primary(1, Some(67), 12), None);
+    assert_eq!(clocks.to_primary(1, Some(65), 0), None);
+
+    // Another sequence can define the same clock id differently.
+    let mut snapshot = ClockSnapshot::new();
+    snapshot.clocks = vec![
+        clock(BuiltinClock::BUILTIN_CLOCK_BOOTTIME as u32, 2_000_000, None),
+        clock(64, 0, None),
+    ];
+    clocks.add_snapshot(2, &snapshot);
+    assert_eq!(clocks.to_primary(2, Some(64), 12), Some(2_000_012));
+    assert_eq!(clocks.to_primary(1, Some(64), 12), Some(1_002_000));
+}
+
 #[derive(Clone, Debug, Deserialize)]
 #[allow(non_snake_case)]
 struct HtmlTraceEvent {
@@ -453,11 +1349,27 @@ impl TryFrom<HtmlTraceEvent> for Event {
             Some(Duration::from_nanos(event.endTime - event.startTime))
         };
 
+        // Carry through the metadata as string annotations, like the debug annotations in
+        // Perfetto traces, so merged events have consistent metadata regardless of source.
+        let mut metadata = BTreeMap::default();
+        if let Some(HtmlTraceMetadata { url, _rest: rest }) = event.metadata {
+            let fields = rest.into_iter().map(|(name, value)| match value {
+                Value::String(value) => (name, value),
+                other => (name, other.to_string()),
+            });
+            for (name, value) in [("url".to_owned(), url)].into_iter().chain(fields) {
+                let mut annotation = DebugAnnotation::new();
+                annotation.set_name(name.clone());
+                annotation.set_string_value(value);
+                metadata.insert(name, annotation);
+            }
+        }
+
         Ok(Self {
             name: event.category,
             start: Duration::from_nanos(event.startTime),
             duration,
-            metadata: BTreeMap::default(),
+            metadata,
         })
     }
 }
@@ -467,6 +1379,66 @@ pub struct IndividualAnalysis {
     path: String,
     relevant_events: Vec<Event>,
     durations: BTreeMap<String, Duration>,
+    /// Values of each counter track (e.g. resident memory), with their timestamps, in order.
+    counters: BTreeMap<String, Vec<(Duration, f64)>>,
+    metric_anchor: MetricAnchor,
+    category_names: CategoryNames,
+}
+
+/// The event that metrics like FCP are measured from: the first event with the given name.
+#[derive(Clone, Debug)]
+pub struct MetricAnchor {
+    pub name: String,
+    /// If true, fail when there is no such event. If false, skip the metrics instead, which is
+    /// useful for pages that are pure script with no HTML parse (e.g. single-page app shells).
+    pub required: bool,
+    /// If [MetricBasis::Navigation], measure from the first event instead, ignoring `name`.
+    pub basis: MetricBasis,
+}
+
+impl Default for MetricAnchor {
+    fn default() -> Self {
+        Self {
+            name: "ScriptParseHTML".to_owned(),
+            required: true,
+            basis: MetricBasis::Native,
+        }
+    }
+}
+
+/// Includes or excludes Perfetto events by the value of one of their debug annotations. Events
+/// without the annotation are unaffected. Filters are not applied to events from HTML traces.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnnotationFilter {
+    pub name: String,
+    pub equals: String,
+    /// If false, keep only events whose annotation equals the value. If true, drop them instead.
+    #[serde(default)]
+    pub exclude: bool,
+}
+
+impl AnnotationFilter {
+    fn keeps(&self, event: &Event) -> bool {
+        let Some(annotation) = event.metadata.get(&self.name) else {
+            return true;
+        };
+        let value = if annotation.has_string_value() {
+            annotation.string_value().to_owned()
+        } else if annotation.has_int_value() {
+            annotation.int_value().to_string()
+        } else if annotation.has_uint_value() {
+            annotation.uint_value().to_string()
+        } else if annotation.has_bool_value() {
+            annotation.bool_value().to_string()
+        } else if annotation.has_double_value() {
+            annotation.double_value().to_string()
+        } else {
+            return true;
+        };
+
+        (value == self.equals) != self.exclude
+    }
 }
 
 impl Individual for IndividualAnalysis {
@@ -510,37 +1482,43 @@ impl Individual for IndividualAnalysis {
 
         // Add some synthetic events with our interpretations.
         let renderer_events = real_events.iter().filter(|e| {
-            RENDERER_NAMES
+            self.category_names
+                .renderer
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
         let parse_events = real_events.iter().filter(|e| {
-            PARSE_NAMES
+            self.category_names
+                .parse
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
         let script_events = real_events.iter().filter(|e| {
-            SCRIPT_NAMES
+            self.category_names
+                .script
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
         let layout_events = real_events.iter().filter(|e| {
-            LAYOUT_NAMES
+            self.category_names
+                .layout
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
         let rasterise_events = real_events.iter().filter(|e| {
-            RASTERISE_NAMES
+            self.category_names
+                .rasterise
                 .split(" ")
                 .find(|&name| name == e.name)
                 .is_some()
         });
+        let renderer_events = Event::generate_merged_events(renderer_events, "Renderer")?;
         let mut result = [
-            Event::generate_merged_events(renderer_events, "Renderer")?,
+            renderer_events,
             Event::generate_merged_events(parse_events, "Parse")?,
             Event::generate_merged_events(script_events, "Script")?,
             Event::generate_merged_events(layout_events, "Layout")?,
@@ -550,13 +1528,12 @@ impl Individual for IndividualAnalysis {
         .flatten()
         .collect::<Vec<_>>();
         for (result_name, category) in METRICS {
-            if let Some(mut event) =
-                IndividualAnalysis::unique_instantaneous_event_from_first_parse(
-                    &self.relevant_events,
-                    result_name,
-                    category,
-                )?
-            {
+            if let Some(mut event) = IndividualAnalysis::unique_instantaneous_event_from_anchor(
+                &self.relevant_events,
+                &self.metric_anchor,
+                result_name,
+                category,
+            )? {
                 event.start -= start;
                 result.push(event);
             }
@@ -571,14 +1548,25 @@ impl IndividualAnalysis {
         Self::dur_by_name(relevant_events, name).iter().sum()
     }
 
-    fn unique_instantaneous_event_from_first_parse(
+    fn unique_instantaneous_event_from_anchor(
         relevant_events: &[Event],
+        anchor: &MetricAnchor,
         result_name: &str,
         name: &str,
     ) -> eyre::Result<Option<Event>> {
-        let Some(first_parse_event) = relevant_events.iter().find(|e| e.name == "ScriptParseHTML")
-        else {
-            bail!("No events with category ScriptParseHTML")
+        let anchor_event = match anchor.basis {
+            MetricBasis::Native => relevant_events.iter().find(|e| e.name == anchor.name),
+            MetricBasis::Navigation => relevant_events.iter().min_by_key(|e| e.start),
+        };
+        let Some(anchor_event) = anchor_event else {
+            if anchor.required {
+                bail!("No events with category {}", anchor.name);
+            }
+            debug!(
+                anchor = %anchor.name,
+                "No anchor event; skipping {result_name}"
+            );
+            return Ok(None);
         };
         let matching_events = relevant_events
             .iter()
@@ -592,14 +1580,17 @@ impl IndividualAnalysis {
         if event.duration.is_some() {
             bail!("Event is not instantaneous: {event:?}");
         }
-        if event.start < first_parse_event.start {
-            bail!("Event is earlier than first ScriptParseHTML event: {event:?}");
+        if event.start < anchor_event.start {
+            bail!(
+                "Event is earlier than first {} event: {event:?}",
+                anchor.name
+            );
         }
 
         Ok(Some(Event {
             name: result_name.to_owned(),
-            start: first_parse_event.start,
-            duration: Some(event.start - first_parse_event.start),
+            start: anchor_event.start,
+            duration: Some(event.start - anchor_event.start),
             metadata: event.metadata.clone(),
         }))
     }
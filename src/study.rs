@@ -1,15 +1,67 @@
-use std::{collections::BTreeMap, fs::File, io::Read, path::Path, time::Duration};
+use std::{collections::BTreeMap, fs::File, hash::Hasher, io::Read, path::Path, time::Duration};
 
-use jane_eyre::eyre::{self, bail};
-use serde::Deserialize;
+use jane_eyre::eyre::{self, bail, Context};
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
 
-#[derive(Debug, Deserialize)]
+use crate::{
+    analyse::Fnv1aHasher,
+    servo::{AnnotationFilter, MetricAnchor},
+    summary::{
+        CategoryNames, CategoryNamesOverrides, MetricBasis, RepresentativeStatistic,
+        SummaryOptions, DEFAULT_BOOTSTRAP_RESAMPLES, DEFAULT_LONG_TASK_THRESHOLD,
+    },
+};
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
 pub struct Study {
     pub sample_size: usize,
     pub traceconv_command: Vec<String>,
+    /// Maximum number of traceconv processes to run at once.
+    traceconv_jobs: Option<usize>,
     pub isolate_cpu_command: Vec<String>,
+    long_task_threshold: Option<u64>,
+    min_samples_for_report: Option<usize>,
+    /// Minimum fraction of runs that a real event must occur in to be summarised.
+    pub min_event_coverage: Option<f64>,
+    #[serde(default)]
+    pub stage_traces_in_temp_dir: bool,
+    selector_timeout: Option<u64>,
+    /// Number of times to retry a failed run of a WebDriver-based engine before giving up.
+    #[serde(default)]
+    pub max_retries: usize,
+    default_browser_open_time: Option<u64>,
+    run_timeout: Option<u64>,
+    max_plausible_event_duration: Option<u64>,
+    #[serde(default)]
+    pub frame_lifetime_only: bool,
+    #[serde(default)]
+    trim_outliers: bool,
+    bootstrap_resamples: Option<usize>,
+    #[serde(default)]
+    representative: RepresentativeStatistic,
+    #[serde(default)]
+    pub metric_basis: MetricBasis,
+    metric_anchor: Option<String>,
+    #[serde(default)]
+    skip_metrics_without_anchor: bool,
+    #[serde(default)]
+    pub annotation_filters: Vec<AnnotationFilter>,
+    /// Default `user_agent` for sites that do not specify one.
+    user_agent: Option<String>,
+    /// Default `screen_size` for sites that do not specify one.
+    screen_size: Option<Vec<f64>>,
+    /// Target value of each metric by name, in seconds, drawn as a reference line in report plots.
+    #[serde(default)]
+    pub targets: BTreeMap<String, f64>,
+    /// SSH destination to run benchmark runner scripts on, instead of the local machine.
+    pub remote_host: Option<String>,
+    /// Overrides for the event names that the analysis looks for, keyed on the engine key.
+    #[serde(default)]
+    categories: BTreeMap<String, CategoryNamesOverrides>,
 
     cpu_configs: BTreeMap<String, CpuConfig>,
+    #[serde(deserialize_with = "deserialize_sites")]
     sites: BTreeMap<String, Site>,
     engines: BTreeMap<String, Engine>,
 
@@ -17,52 +69,216 @@ pub struct Study {
     pub source_toml: String,
 }
 
-#[derive(Debug, Deserialize)]
-struct CpuConfig(Vec<usize>);
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged, deny_unknown_fields)]
+enum CpuConfig {
+    CpusOnly(Vec<usize>),
+    Full {
+        cpus: Vec<usize>,
+        memory_nodes: Option<Vec<usize>>,
+    },
+}
 #[derive(Clone, Copy, Debug)]
 pub struct KeyedCpuConfig<'study> {
     pub key: &'study str,
     pub cpus: &'study [usize],
+    /// NUMA nodes to restrict memory allocation to, or None to leave memory policy unchanged.
+    pub memory_nodes: Option<&'study [usize]>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize)]
 #[serde(untagged)]
 enum Site {
     UrlOnly(String),
-    Full {
-        url: String,
-        browser_open_time: Option<u64>,
-        user_agent: Option<String>,
-        screen_size: Option<Vec<usize>>,
-        wait_for_selectors: Option<BTreeMap<String, usize>>,
-        extra_engine_arguments: Option<BTreeMap<String, Vec<String>>>,
-    },
+    Full(FullSite),
+}
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct FullSite {
+    url: String,
+    url_pattern: Option<String>,
+    browser_open_time: Option<u64>,
+    user_agent: Option<String>,
+    screen_size: Option<Vec<f64>>,
+    wait_for_selectors: Option<BTreeMap<String, usize>>,
+    wait_for_network_idle: Option<u64>,
+    #[serde(default)]
+    all_frames: bool,
+    extra_engine_arguments: Option<BTreeMap<String, Vec<String>>>,
+}
+/// With `#[serde(untagged)]`, a site with a bad field would only fail with “data did not match any
+/// variant of untagged enum Site”, so deserialize tables as [FullSite] ourselves to keep the error.
+impl<'de> Deserialize<'de> for Site {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum UrlOrTable {
+            Url(String),
+            Table(toml::Table),
+        }
+        match UrlOrTable::deserialize(deserializer)? {
+            UrlOrTable::Url(url) => Ok(Site::UrlOnly(url)),
+            UrlOrTable::Table(table) => toml::Value::Table(table)
+                .try_into()
+                .map(Site::Full)
+                .map_err(|e| D::Error::custom(e.message())),
+        }
+    }
 }
+
+/// Sites can be a table of sites by key, or an array of tables that each have a `key`. Like for
+/// [Site], we dispatch on the form ourselves rather than with `#[serde(untagged)]`, so that errors
+/// in a site are not replaced with “data did not match any variant”.
+fn deserialize_sites<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<BTreeMap<String, Site>, D::Error> {
+    let site = |key: &str, value: toml::Value| {
+        value
+            .try_into::<Site>()
+            .map_err(|e| D::Error::custom(format!("Bad site {key:?}: {}", e.message())))
+    };
+    match toml::Value::deserialize(deserializer)? {
+        toml::Value::Table(sites) => {
+            let mut result = BTreeMap::default();
+            for (key, value) in sites {
+                let value = site(&key, value)?;
+                result.insert(key, value);
+            }
+            Ok(result)
+        }
+        toml::Value::Array(sites) => {
+            let mut result = BTreeMap::default();
+            for (i, listed_site) in sites.into_iter().enumerate() {
+                let toml::Value::Table(mut listed_site) = listed_site else {
+                    return Err(D::Error::custom(format!("Bad site {i}: expected a table")));
+                };
+                let Some(toml::Value::String(key)) = listed_site.remove("key") else {
+                    return Err(D::Error::custom(format!("Bad site {i}: missing `key`")));
+                };
+                if result.contains_key(&key) {
+                    return Err(D::Error::custom(format!("Duplicate site key: {key:?}")));
+                }
+                let value = site(&key, toml::Value::Table(listed_site))?;
+                result.insert(key, value);
+            }
+            Ok(result)
+        }
+        _ => Err(D::Error::custom(
+            "sites must be a table of sites or an array of tables",
+        )),
+    }
+}
+
+#[test]
+fn test_deserialize_sites() {
+    #[derive(Debug, Deserialize)]
+    struct Sites {
+        #[serde(deserialize_with = "deserialize_sites")]
+        sites: BTreeMap<String, Site>,
+    }
+    let urls = |source: &str| -> Result<Vec<(String, String)>, String> {
+        let sites = toml::from_str::<Sites>(source).map_err(|e| e.to_string())?;
+        Ok(sites
+            .sites
+            .into_iter()
+            .map(|(key, site)| match site {
+                Site::UrlOnly(url) => (key, url),
+                Site::Full(site) => (key, site.url),
+            })
+            .collect())
+    };
+    let expected = Ok(vec![
+        ("a".to_owned(), "http://a".to_owned()),
+        ("b".to_owned(), "http://b".to_owned()),
+    ]);
+
+    // Map form, with a string and a table.
+    assert_eq!(
+        urls("[sites]\na = \"http://a\"\nb = { url = \"http://b\" }"),
+        expected
+    );
+    // List form.
+    assert_eq!(
+        urls("[[sites]]\nkey = \"a\"\nurl = \"http://a\"\n[[sites]]\nkey = \"b\"\nurl = \"http://b\""),
+        expected
+    );
+
+    // Unknown fields keep their error and the site key, in both forms.
+    for source in [
+        "[sites.a]\nurl = \"http://a\"\nbrowser_open_tim = 3",
+        "[[sites]]\nkey = \"a\"\nurl = \"http://a\"\nbrowser_open_tim = 3",
+    ] {
+        let error = urls(source).unwrap_err();
+        assert!(error.contains("Bad site \"a\""), "{error}");
+        assert!(
+            error.contains("unknown field `browser_open_tim`"),
+            "{error}"
+        );
+    }
+    let error = urls(
+        "[[sites]]\nkey = \"a\"\nurl = \"http://a\"\n[[sites]]\nkey = \"a\"\nurl = \"http://b\"",
+    )
+    .unwrap_err();
+    assert!(error.contains("Duplicate site key: \"a\""), "{error}");
+}
+
 #[derive(Clone, Copy, Debug)]
 pub struct KeyedSite<'study> {
     pub key: &'study str,
     pub url: &'study str,
+    /// Pattern for the URLs to analyse, if different from `url`.
+    pub url_pattern: Option<&'study str>,
     pub browser_open_time: Duration,
     pub user_agent: Option<&'study str>,
-    screen_size: Option<&'study [usize]>,
+    screen_size: Option<&'study [f64]>,
     wait_for_selectors: Option<&'study BTreeMap<String, usize>>,
+    /// How long the page must go without any requests completing before we close the window.
+    pub wait_for_network_idle: Option<Duration>,
+    /// Whether to also analyse each frame that matches the URL (pattern) separately.
+    pub all_frames: bool,
     extra_engine_arguments: Option<&'study BTreeMap<String, Vec<String>>>,
 }
 
-#[derive(Debug, Deserialize)]
-#[serde(tag = "type")]
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", deny_unknown_fields)]
 pub enum Engine {
     Servo {
         path: String,
         description: Option<String>,
+        version_command: Option<String>,
+        color: Option<String>,
     },
     Chromium {
         path: String,
         description: Option<String>,
+        version_command: Option<String>,
+        color: Option<String>,
+    },
+    WebKit {
+        path: String,
+        description: Option<String>,
+        version_command: Option<String>,
+        color: Option<String>,
     },
     ChromeDriver {
         path: String,
         description: Option<String>,
+        version_command: Option<String>,
+        color: Option<String>,
+    },
+    GeckoDriver {
+        path: String,
+        description: Option<String>,
+        version_command: Option<String>,
+        color: Option<String>,
+    },
+    RemoteWebDriver {
+        url: String,
+        #[serde(default)]
+        capabilities: BTreeMap<String, serde_json::Value>,
+        description: Option<String>,
+        version_command: Option<String>,
+        color: Option<String>,
     },
 }
 #[derive(Clone, Copy, Debug)]
@@ -78,20 +294,156 @@ impl Study {
         let mut result: Study = toml::from_str(&source)?;
         result.source_toml = source;
 
+        if let Some(min_event_coverage) = result.min_event_coverage {
+            if !(0.0..=1.0).contains(&min_event_coverage) {
+                bail!("min_event_coverage must be between 0 and 1");
+            }
+        }
+        for (name, target) in result.targets.iter() {
+            if !target.is_finite() || *target < 0.0 {
+                bail!("targets.{name:?} must be a non-negative number of seconds");
+            }
+        }
+        if result.default_browser_open_time == Some(0) {
+            bail!("default_browser_open_time must be positive");
+        }
+        if result.run_timeout == Some(0) {
+            bail!("run_timeout must be positive");
+        }
+
+        // Check that every screen_size is well-formed, including study-level defaults that are
+        // overridden by every site, and site overrides that differ from the default.
+        if let Some(screen_size) = result.screen_size.as_deref() {
+            parse_screen_size(screen_size).wrap_err("Bad study-level screen_size")?;
+        }
+        for (key, site) in result.sites.iter() {
+            if let Site::Full(FullSite {
+                screen_size: Some(screen_size),
+                ..
+            }) = site
+            {
+                parse_screen_size(screen_size)
+                    .wrap_err_with(|| format!("Bad screen_size for site {key:?}"))?;
+            }
+        }
+
+        // Check that extra_engine_arguments only refers to engines that exist, since arguments for
+        // a misspelled engine would otherwise be silently ignored.
+        for (key, site) in result.sites.iter() {
+            if let Site::Full(FullSite {
+                extra_engine_arguments: Some(extra_engine_arguments),
+                ..
+            }) = site
+            {
+                for engine_key in extra_engine_arguments.keys() {
+                    if !result.engines.contains_key(engine_key) {
+                        bail!("extra_engine_arguments for site {key:?} refers to unknown engine {engine_key:?}");
+                    }
+                }
+            }
+        }
+        // Likewise for categories, which only some types of engines use.
+        for engine_key in result.categories.keys() {
+            let Some(engine) = result.engines.get(engine_key) else {
+                bail!("categories refers to unknown engine {engine_key:?}");
+            };
+            if !matches!(
+                engine,
+                Engine::Servo { .. } | Engine::Chromium { .. } | Engine::ChromeDriver { .. }
+            ) {
+                bail!("categories.{engine_key:?} is only supported for Servo-type and Chromium-type engines");
+            }
+        }
+
         Ok(result)
     }
 
+    pub fn long_task_threshold(&self) -> Duration {
+        self.long_task_threshold
+            .map_or(DEFAULT_LONG_TASK_THRESHOLD, Duration::from_millis)
+    }
+
+    /// Returns `traceconv_jobs`, or by default, half the available parallelism, since traceconv is
+    /// memory-hungry.
+    pub fn traceconv_jobs(&self) -> usize {
+        self.traceconv_jobs
+            .unwrap_or_else(|| std::thread::available_parallelism().map_or(1, |n| n.get() / 2))
+            .max(1)
+    }
+
+    pub fn min_samples_for_report(&self) -> usize {
+        self.min_samples_for_report.unwrap_or(1)
+    }
+
+    pub fn selector_timeout(&self) -> Duration {
+        self.selector_timeout
+            .map_or(Duration::ZERO, Duration::from_secs)
+    }
+
+    /// Returns how long a run can take before we kill the browser and mark the sample as failed.
+    pub fn run_timeout(&self) -> Duration {
+        self.run_timeout
+            .map_or(Duration::from_secs(120), Duration::from_secs)
+    }
+
+    pub fn default_browser_open_time(&self) -> Duration {
+        self.default_browser_open_time
+            .map_or(Duration::from_secs(10), Duration::from_secs)
+    }
+
+    pub fn max_plausible_event_duration(&self) -> Option<Duration> {
+        self.max_plausible_event_duration.map(Duration::from_secs)
+    }
+
+    pub fn summary_options(&self) -> SummaryOptions {
+        SummaryOptions {
+            trim_outliers: self.trim_outliers,
+            representative: self.representative,
+            bootstrap_resamples: self
+                .bootstrap_resamples
+                .unwrap_or(DEFAULT_BOOTSTRAP_RESAMPLES),
+        }
+    }
+
+    pub fn metric_anchor(&self) -> MetricAnchor {
+        let default = MetricAnchor::default();
+        MetricAnchor {
+            name: self.metric_anchor.clone().unwrap_or(default.name),
+            required: !self.skip_metrics_without_anchor,
+            basis: self.metric_basis,
+        }
+    }
+
+    /// Returns the event names that the analysis should look for in the given engine’s traces,
+    /// that is, the given defaults for its engine type, with any overrides from the study.
+    pub fn category_names(&self, engine_key: &str, defaults: CategoryNames) -> CategoryNames {
+        match self.categories.get(engine_key) {
+            Some(overrides) => defaults.with_overrides(overrides),
+            None => defaults,
+        }
+    }
+
     pub fn cpu_configs(&self) -> impl Iterator<Item = KeyedCpuConfig> {
         self.cpu_configs
             .iter()
-            .map(|(key, cpu_config)| KeyedCpuConfig {
-                key,
-                cpus: &cpu_config.0,
+            .map(|(key, cpu_config)| match cpu_config {
+                CpuConfig::CpusOnly(cpus) => KeyedCpuConfig {
+                    key,
+                    cpus,
+                    memory_nodes: None,
+                },
+                CpuConfig::Full { cpus, memory_nodes } => KeyedCpuConfig {
+                    key,
+                    cpus,
+                    memory_nodes: memory_nodes.as_deref(),
+                },
             })
     }
 
     pub fn sites(&self) -> impl Iterator<Item = KeyedSite> {
-        self.sites.iter().map(|(key, site)| (&**key, site).into())
+        self.sites
+            .iter()
+            .map(|(key, site)| (&**key, site, self).into())
     }
 
     pub fn engines(&self) -> impl Iterator<Item = KeyedEngine> {
@@ -99,37 +451,51 @@ impl Study {
             .iter()
             .map(|(key, engine)| KeyedEngine { key, engine })
     }
+
+    /// Engines whose samples `analyse` summarises (see [KeyedEngine::has_summaries]).
+    pub fn analysed_engines(&self) -> impl Iterator<Item = KeyedEngine> {
+        self.engines().filter(|engine| engine.has_summaries())
+    }
 }
 
-impl<'study> From<(&'study str, &'study Site)> for KeyedSite<'study> {
-    fn from((key, site): (&'study str, &'study Site)) -> Self {
-        let default_browser_open_time = Duration::from_secs(10);
+impl<'study> From<(&'study str, &'study Site, &'study Study)> for KeyedSite<'study> {
+    fn from((key, site, study): (&'study str, &'study Site, &'study Study)) -> Self {
+        let default_browser_open_time = study.default_browser_open_time();
 
         match site {
             Site::UrlOnly(url) => Self {
                 key,
                 url,
+                url_pattern: None,
                 browser_open_time: default_browser_open_time,
-                user_agent: None,
-                screen_size: None,
+                user_agent: study.user_agent.as_deref(),
+                screen_size: study.screen_size.as_deref(),
                 wait_for_selectors: None,
+                wait_for_network_idle: None,
+                all_frames: false,
                 extra_engine_arguments: None,
             },
-            Site::Full {
+            Site::Full(FullSite {
                 url,
+                url_pattern,
                 browser_open_time,
                 user_agent,
                 screen_size,
                 wait_for_selectors,
+                wait_for_network_idle,
+                all_frames,
                 extra_engine_arguments,
-            } => Self {
+            }) => Self {
                 key,
                 url,
+                url_pattern: url_pattern.as_deref(),
                 browser_open_time: browser_open_time
                     .map_or(default_browser_open_time, Duration::from_secs),
-                user_agent: user_agent.as_deref(),
-                screen_size: screen_size.as_deref(),
+                user_agent: user_agent.as_deref().or(study.user_agent.as_deref()),
+                screen_size: screen_size.as_deref().or(study.screen_size.as_deref()),
                 wait_for_selectors: wait_for_selectors.as_ref(),
+                wait_for_network_idle: wait_for_network_idle.map(Duration::from_millis),
+                all_frames: *all_frames,
                 extra_engine_arguments: extra_engine_arguments.as_ref(),
             },
         }
@@ -137,15 +503,14 @@ impl<'study> From<(&'study str, &'study Site)> for KeyedSite<'study> {
 }
 
 impl KeyedSite<'_> {
-    pub fn screen_size(&self) -> eyre::Result<Option<(usize, usize)>> {
-        self.screen_size
-            .map(|size| {
-                Ok(match size {
-                    [width, height] => (*width, *height),
-                    other => bail!("Bad screen_size: {other:?}"),
-                })
-            })
-            .transpose()
+    /// The URL or URL pattern to give to the `servo` or `chromium` analysis.
+    pub fn analysis_url(&self) -> &str {
+        self.url_pattern.unwrap_or(self.url)
+    }
+
+    /// The width, height, and device pixel ratio (if any) of the screen.
+    pub fn screen_size(&self) -> eyre::Result<Option<(usize, usize, Option<f64>)>> {
+        self.screen_size.map(parse_screen_size).transpose()
     }
 
     pub fn wait_for_selectors(&self) -> Box<dyn Iterator<Item = (&String, &usize)> + '_> {
@@ -163,22 +528,71 @@ impl KeyedSite<'_> {
     }
 }
 
+fn parse_screen_size(size: &[f64]) -> eyre::Result<(usize, usize, Option<f64>)> {
+    fn dimension(value: f64) -> eyre::Result<usize> {
+        if value.fract() != 0.0 || !(0.0..=usize::MAX as f64).contains(&value) {
+            bail!("Bad screen_size dimension: {value:?}");
+        }
+        Ok(value as usize)
+    }
+    Ok(match size {
+        [width, height] => (dimension(*width)?, dimension(*height)?, None),
+        [width, height, dpr] => {
+            if !dpr.is_finite() || *dpr <= 0.0 {
+                bail!("Bad screen_size device pixel ratio: {dpr:?}");
+            }
+            (dimension(*width)?, dimension(*height)?, Some(*dpr))
+        }
+        other => bail!("Bad screen_size: {other:?}"),
+    })
+}
+
 impl KeyedEngine<'_> {
     pub fn benchmark_runner_code(&self) -> &str {
         match self.engine {
             Engine::Servo { .. } => include_str!("../benchmark-servo.sh"),
             Engine::Chromium { .. } => include_str!("../benchmark-chromium.sh"),
+            Engine::WebKit { .. } => include_str!("../benchmark-webkit.sh"),
             Engine::ChromeDriver { .. } => {
                 panic!("BUG: Engine::ChromeDriver has no benchmark runner script")
             }
+            Engine::GeckoDriver { .. } => {
+                panic!("BUG: Engine::GeckoDriver has no benchmark runner script")
+            }
+            Engine::RemoteWebDriver { .. } => {
+                panic!("BUG: Engine::RemoteWebDriver has no benchmark runner script")
+            }
         }
     }
 
+    /// Whether this engine is driven over WebDriver, rather than by a benchmark runner script.
+    pub fn uses_webdriver(&self) -> bool {
+        matches!(
+            self.engine,
+            Engine::ChromeDriver { .. }
+                | Engine::GeckoDriver { .. }
+                | Engine::RemoteWebDriver { .. }
+        )
+    }
+
+    /// Whether `analyse` summarises this engine’s samples. GeckoDriver samples can be collected,
+    /// but we can’t analyse Gecko profiles yet, and RemoteWebDriver samples have no traces on this
+    /// machine, so they are skipped.
+    pub fn has_summaries(&self) -> bool {
+        !matches!(
+            self.engine,
+            Engine::GeckoDriver { .. } | Engine::RemoteWebDriver { .. }
+        )
+    }
+
     pub fn type_name(&self) -> &str {
         match self.engine {
             Engine::Servo { .. } => "Servo",
             Engine::Chromium { .. } => "Chromium",
+            Engine::WebKit { .. } => "WebKit",
             Engine::ChromeDriver { .. } => "ChromeDriver",
+            Engine::GeckoDriver { .. } => "GeckoDriver",
+            Engine::RemoteWebDriver { .. } => "RemoteWebDriver",
         }
     }
 
@@ -186,7 +600,11 @@ impl KeyedEngine<'_> {
         match self.engine {
             Engine::Servo { path, .. } => path,
             Engine::Chromium { path, .. } => path,
+            Engine::WebKit { path, .. } => path,
             Engine::ChromeDriver { path, .. } => path,
+            Engine::GeckoDriver { path, .. } => path,
+            // There is no local browser, so show where the WebDriver server is instead.
+            Engine::RemoteWebDriver { url, .. } => url,
         }
     }
 
@@ -194,7 +612,54 @@ impl KeyedEngine<'_> {
         match self.engine {
             Engine::Servo { description, .. } => description.as_deref(),
             Engine::Chromium { description, .. } => description.as_deref(),
+            Engine::WebKit { description, .. } => description.as_deref(),
             Engine::ChromeDriver { description, .. } => description.as_deref(),
+            Engine::GeckoDriver { description, .. } => description.as_deref(),
+            Engine::RemoteWebDriver { description, .. } => description.as_deref(),
+        }
+    }
+
+    /// Returns the CSS colour for this engine in report plots, either from the engine config or
+    /// derived from a hash of the engine key, so each engine has the same colour in every plot.
+    pub fn color(&self) -> String {
+        let color = match self.engine {
+            Engine::Servo { color, .. } => color,
+            Engine::Chromium { color, .. } => color,
+            Engine::WebKit { color, .. } => color,
+            Engine::ChromeDriver { color, .. } => color,
+            Engine::GeckoDriver { color, .. } => color,
+            Engine::RemoteWebDriver { color, .. } => color,
+        };
+        if let Some(color) = color {
+            return color.clone();
+        }
+        let mut hasher = Fnv1aHasher::default();
+        hasher.write(self.key.as_bytes());
+        let hash = hasher.finish();
+        // Keep the lightness moderate, so the points stand out against the light theme.
+        format!("hsl({}, 70%, 40%)", hash % 360)
+    }
+
+    pub fn version_command(&self) -> Option<&str> {
+        match self.engine {
+            Engine::Servo {
+                version_command, ..
+            } => version_command.as_deref(),
+            Engine::Chromium {
+                version_command, ..
+            } => version_command.as_deref(),
+            Engine::WebKit {
+                version_command, ..
+            } => version_command.as_deref(),
+            Engine::ChromeDriver {
+                version_command, ..
+            } => version_command.as_deref(),
+            Engine::GeckoDriver {
+                version_command, ..
+            } => version_command.as_deref(),
+            Engine::RemoteWebDriver {
+                version_command, ..
+            } => version_command.as_deref(),
         }
     }
 }
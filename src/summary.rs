@@ -1,20 +1,108 @@
 use std::{
     collections::BTreeMap,
-    fmt::{Display, Write},
+    fmt::{Debug, Display, Write},
+    str::FromStr,
     time::Duration,
 };
 
-use jane_eyre::eyre::{self, OptionExt};
+use jane_eyre::eyre::{self, bail, OptionExt};
 use perfetto_protos::debug_annotation::DebugAnnotation;
+use rand::{rngs::StdRng, Rng, SeedableRng};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use tracing::warn;
 
-pub static SYNTHETIC_NAMES: &'static str = "Renderer Parse Script Layout Rasterise FP FCP";
+pub static SYNTHETIC_NAMES: &'static str =
+    "Renderer Parse Script Layout Rasterise FP FCP";
+/// Synthetic phases that each cover part of the `Renderer` phase.
+static SYNTHETIC_PHASE_NAMES: &'static str = "Parse Script Layout Rasterise";
+/// How far the synthetic phases can add up to more than the `Renderer` phase, as a fraction of the
+/// `Renderer` phase, before we flag the summaries.
+static SYNTHETIC_PHASE_SUM_TOLERANCE: f64 = 0.05;
+/// Script spans longer than this block interactivity, per the Long Tasks API.
+/// <https://w3c.github.io/longtasks/>
+pub const DEFAULT_LONG_TASK_THRESHOLD: Duration = Duration::from_millis(50);
+
+/// Names of the real events (or categories) that the analysis of an engine’s traces looks for,
+/// each as a space-separated list.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CategoryNames {
+    /// Real events that make up the synthetic `Renderer` phase, each also summarised on its own.
+    pub renderer: String,
+    pub parse: String,
+    pub script: String,
+    pub layout: String,
+    pub rasterise: String,
+    /// Real events that have no URL (Servo) or frame (Chromium), so they are kept even though
+    /// they can’t be tied to the page. For Chromium, they are taken from the page’s renderer
+    /// process instead.
+    pub no_url: String,
+}
+
+/// Overrides for some or all of an engine’s [CategoryNames], such as from study.toml.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CategoryNamesOverrides {
+    pub renderer: Option<String>,
+    pub parse: Option<String>,
+    pub script: Option<String>,
+    pub layout: Option<String>,
+    pub rasterise: Option<String>,
+    pub no_url: Option<String>,
+}
+
+impl CategoryNames {
+    pub fn with_overrides(self, overrides: &CategoryNamesOverrides) -> Self {
+        let or = |names: &Option<String>, default: String| names.clone().unwrap_or(default);
+        Self {
+            renderer: or(&overrides.renderer, self.renderer),
+            parse: or(&overrides.parse, self.parse),
+            script: or(&overrides.script, self.script),
+            layout: or(&overrides.layout, self.layout),
+            rasterise: or(&overrides.rasterise, self.rasterise),
+            no_url: or(&overrides.no_url, self.no_url),
+        }
+    }
+
+    /// Returns the real event names that make up each synthetic phase, for display in reports.
+    pub fn synthetic_phase_definitions(&self) -> [(&'static str, &str); 5] {
+        [
+            ("Renderer", &self.renderer),
+            ("Parse", &self.parse),
+            ("Script", &self.script),
+            ("Layout", &self.layout),
+            ("Rasterise", &self.rasterise),
+        ]
+    }
+}
+
+/// What instantaneous metrics like FCP are measured from.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum MetricBasis {
+    /// Each engine’s own basis: the first `markAsMainFrame` event in Chromium, or the
+    /// `metric_anchor` event in Servo.
+    #[default]
+    Native,
+    /// The start of navigation: the `navigationStart` event in Chromium, or the first event of
+    /// the page in Servo, which has no `navigationStart` event.
+    Navigation,
+}
 
 pub trait Individual {
     fn path(&self) -> &str;
     fn real_events(&self) -> eyre::Result<Vec<Event>>;
     fn synthetic_events(&self) -> eyre::Result<Vec<Event>>;
+
+    /// Returns the fraction of this individual’s span that the `Renderer` phase was busy (see
+    /// [Event::utilization]).
+    fn utilization(&self) -> eyre::Result<Option<f64>> {
+        let renderer_events = self
+            .synthetic_events()?
+            .into_iter()
+            .filter(|e| e.name == "Renderer")
+            .collect::<Vec<_>>();
+        Ok(Event::utilization(&self.real_events()?, &renderer_events))
+    }
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -35,15 +123,87 @@ pub struct Summary<T> {
     pub n: usize,
     pub mean: T,
     pub stdev: T,
+    /// Coefficient of variation, `stdev / mean`, as a measure of noise that does not depend on
+    /// the scale of the values. Zero if the mean is zero.
+    pub cv: T,
     pub min: T,
     pub max: T,
+    pub median: T,
+    pub p90: T,
+    pub p99: T,
+    /// Number of outliers dropped before computing the other statistics (see
+    /// [SummaryOptions::trim_outliers]), which are not counted in `n`.
+    #[serde(default)]
+    pub trimmed: usize,
+    /// 95% bootstrap confidence interval for the median, if computed (see [SummaryOptions]).
+    #[serde(default)]
+    pub ci_low: Option<T>,
+    #[serde(default)]
+    pub ci_high: Option<T>,
+    /// True if the values seem to form two separate clusters (see [is_bimodal]).
+    #[serde(skip)]
+    pub bimodal: bool,
+    /// Which statistic represents the values (see [Summary::fmt_representative]).
+    #[serde(default)]
+    pub representative_statistic: RepresentativeStatistic,
 }
 
+/// Which statistic represents a metric in summaries and reports. Defaults to the minimum, which
+/// is the least affected by noise, but the median or mean may be more meaningful for workloads
+/// with inherent variation.
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RepresentativeStatistic {
+    #[default]
+    Min,
+    Median,
+    Mean,
+}
+
+impl RepresentativeStatistic {
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Min => "min",
+            Self::Median => "median",
+            Self::Mean => "mean",
+        }
+    }
+}
+
+/// Parses the same names as study.toml, for command-line options like `--statistic`.
+impl FromStr for RepresentativeStatistic {
+    type Err = serde::de::value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::deserialize(serde::de::value::StrDeserializer::new(s))
+    }
+}
+
+/// Version of the `summaries.json` format. Bump this whenever [JsonSummaries] changes in a way
+/// that would make older files unreadable or misleading.
+pub static SUMMARIES_SCHEMA_VERSION: usize = 9;
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct JsonSummaries {
+    pub schema_version: usize,
     pub real_events: Vec<JsonSummary>,
+    /// Self time of the same real events, excluding the time covered by events nested inside
+    /// them (see [EventNode::self_duration]), so that nested events are not counted twice.
+    #[serde(default)]
+    pub real_events_self_time: Vec<JsonSummary>,
     pub synthetic_and_interpreted_events: Vec<JsonSummary>,
+    /// Peak and mean of each counter track, such as memory usage, in the counter’s own units,
+    /// and other counts like `LongTaskCount`.
+    #[serde(default)]
+    pub counters: Vec<JsonSummary>,
+    /// Dimensionless fractions from 0 to 1, such as `Utilization`, rather than durations of events.
+    #[serde(default)]
+    pub fractions: Vec<JsonSummary>,
     pub raw_series: Vec<JsonRawSeries>,
+    pub individuals: Vec<JsonIndividual>,
+    /// Caveats about the summaries as a whole, such as inconsistencies between the phases.
+    #[serde(default)]
+    pub notes: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -52,6 +212,29 @@ pub struct JsonSummary {
     pub raw: Summary<f64>,
     pub full: String,
     pub representative: String,
+    /// True if the values seem to form two separate clusters, such as cache hits and cache
+    /// misses, in which case no single representative value tells the whole story.
+    pub bimodal: bool,
+}
+
+/// Per-individual values, for comparing metrics within the same run.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonIndividual {
+    pub path: String,
+    /// Total duration of each synthetic or interpreted event, in seconds. Events that did not
+    /// occur in this individual are omitted, rather than zero.
+    pub synthetic_and_interpreted_events: BTreeMap<String, f64>,
+    /// Every synthetic or interpreted event, in order, for drawing timelines.
+    pub events: Vec<JsonEvent>,
+}
+
+/// An event in an individual, with times in seconds since the start of the individual.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JsonEvent {
+    pub name: String,
+    pub start: f64,
+    /// Some if the event is a span, None if the event is instantaneous.
+    pub duration: Option<f64>,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -66,6 +249,11 @@ pub enum EventKind {
     SyntheticOrInterpreted,
     Servo,
     Chromium,
+    WebKit,
+    /// Values of a counter track, like memory usage, rather than durations of events.
+    Counter,
+    /// Fractions from 0 to 1, like `Utilization`, rather than durations of events.
+    Fraction,
 }
 
 impl Display for EventKind {
@@ -74,6 +262,35 @@ impl Display for EventKind {
     }
 }
 
+/// Drops any span longer than `max_duration`, since it is likely corrupt (e.g. due to a clock
+/// glitch) and would otherwise dominate every sum and plot.
+pub fn drop_implausible_events<T: Debug>(
+    events: Vec<T>,
+    duration: impl Fn(&T) -> Option<Duration>,
+    max_duration: Option<Duration>,
+    path: &str,
+) -> Vec<T> {
+    let Some(max_duration) = max_duration else {
+        return events;
+    };
+
+    events
+        .into_iter()
+        .filter(|event| {
+            if duration(event).is_some_and(|duration| duration > max_duration) {
+                warn!(
+                    path,
+                    ?event,
+                    ?max_duration,
+                    "Dropping implausibly long event"
+                );
+                return false;
+            }
+            true
+        })
+        .collect()
+}
+
 impl Event {
     pub fn end(&self) -> Duration {
         if let Some(duration) = self.duration {
@@ -83,9 +300,52 @@ impl Event {
         }
     }
 
+    /// Returns the track that this event is on, such as the thread it ran on, if known.
+    pub fn track(&self) -> Option<&str> {
+        self.metadata.get("track").map(|a| a.string_value())
+    }
+
+    /// Returns true if this is a merged `Script` span longer than `threshold`.
+    pub fn is_long_task(&self, threshold: Duration) -> bool {
+        self.name == "Script" && self.duration.is_some_and(|duration| duration > threshold)
+    }
+
+    /// Merges the given events into the union of their intervals, returning one event named
+    /// `merged_name` for each maximal span of time covered by at least one of them. Each merged
+    /// event has the metadata of all of the given events.
+    ///
+    /// This is how we interpret engine-specific events as the synthetic phases, such as Renderer,
+    /// Parse, Script, and Layout.
     pub fn generate_merged_events<'event>(
         events: impl Iterator<Item = &'event Event>,
         merged_name: &str,
+    ) -> eyre::Result<Vec<Event>> {
+        Self::generate_merged_events_with_gap(events, merged_name, Duration::ZERO)
+    }
+
+    /// Returns the fraction of the span from the first to the last of `events` that is covered by
+    /// the merged `renderer_events`, from 0 to 1, or None if there are no events.
+    pub fn utilization(events: &[Event], renderer_events: &[Event]) -> Option<f64> {
+        let start = events.iter().map(|e| e.start).min()?;
+        let end = events.iter().map(|e| e.end()).max()?;
+        let span = end.saturating_sub(start);
+        let busy = renderer_events
+            .iter()
+            .flat_map(|e| e.duration)
+            .sum::<Duration>();
+        if span.is_zero() {
+            return Some(0.0);
+        }
+
+        Some((busy.as_secs_f64() / span.as_secs_f64()).clamp(0.0, 1.0))
+    }
+
+    /// Like [Event::generate_merged_events], but also bridges gaps shorter than `max_gap` between
+    /// the merged events, such as to treat back-to-back script calls as one busy period.
+    pub fn generate_merged_events_with_gap<'event>(
+        events: impl Iterator<Item = &'event Event>,
+        merged_name: &str,
+        max_gap: Duration,
     ) -> eyre::Result<Vec<Event>> {
         enum Edge {
             Start,
@@ -100,7 +360,7 @@ impl Event {
             metadata.extend(event.metadata.clone());
         }
 
-        let mut result = vec![];
+        let mut result: Vec<Event> = vec![];
         let mut active_count = 0usize;
         let mut start_time = None;
         for (time, edges) in edges {
@@ -113,6 +373,13 @@ impl Event {
             }
             if active_count > 0 && new_active_count == 0 {
                 let start_time = start_time.ok_or_eyre("No start time")?;
+                if let Some(previous) = result.last_mut() {
+                    if start_time - previous.end() < max_gap {
+                        previous.duration = Some(time - previous.start);
+                        active_count = new_active_count;
+                        continue;
+                    }
+                }
                 let duration = time - start_time;
                 result.push(Event {
                     name: merged_name.to_owned(),
@@ -128,6 +395,218 @@ impl Event {
 
         Ok(result)
     }
+
+    /// Reconstructs the nesting of the given events from their intervals, returning the events
+    /// that are not inside any other event, each with the events inside it, in order of start.
+    ///
+    /// An event is inside another if the other event is a span on the same track (see
+    /// [Event::track]) that starts no later and ends no earlier. Events on different tracks, such
+    /// as a raster task during a script on the main thread, are never nested, and events that
+    /// overlap without nesting cleanly are treated as siblings.
+    pub fn build_tree<'event>(events: impl Iterator<Item = &'event Event>) -> Vec<EventNode> {
+        let mut events_by_track: BTreeMap<Option<&str>, Vec<&Event>> = BTreeMap::default();
+        for event in events {
+            events_by_track.entry(event.track()).or_default().push(event);
+        }
+        let mut result = events_by_track
+            .into_values()
+            .flat_map(Self::build_track_tree)
+            .collect::<Vec<_>>();
+        result.sort_by_key(|node| node.event.start);
+
+        result
+    }
+
+    /// Like [Event::build_tree], but for events that are all on the same track.
+    fn build_track_tree(mut events: Vec<&Event>) -> Vec<EventNode> {
+        events.sort_by(|p, q| p.start.cmp(&q.start).then(q.end().cmp(&p.end())));
+
+        // Each event on the stack is inside the event below it, and the top of the stack is the
+        // innermost event that might still have more children.
+        let mut result = vec![];
+        let mut stack: Vec<EventNode> = vec![];
+        let pop = |stack: &mut Vec<EventNode>, result: &mut Vec<EventNode>| {
+            let node = stack.pop().expect("Guaranteed by caller");
+            match stack.last_mut() {
+                Some(parent) => parent.children.push(node),
+                None => result.push(node),
+            }
+        };
+        for event in events {
+            while stack
+                .last()
+                .is_some_and(|top| top.event.duration.is_none() || top.event.end() < event.end())
+            {
+                pop(&mut stack, &mut result);
+            }
+            stack.push(EventNode {
+                event: event.clone(),
+                children: vec![],
+            });
+        }
+        while !stack.is_empty() {
+            pop(&mut stack, &mut result);
+        }
+
+        result
+    }
+
+    /// Returns the total self duration (see [EventNode::self_duration]) of the events with each
+    /// of the given names, or zero if there are no such events.
+    pub fn sum_self_durations<'name>(
+        events: &[Event],
+        names: impl Iterator<Item = &'name str>,
+    ) -> BTreeMap<String, Duration> {
+        fn visit(node: &EventNode, result: &mut BTreeMap<String, Duration>) {
+            if let Some(total) = result.get_mut(&node.event.name) {
+                *total += node.self_duration().unwrap_or_default();
+            }
+            for child in node.children.iter() {
+                visit(child, result);
+            }
+        }
+
+        let mut result = names
+            .map(|name| (name.to_owned(), Duration::ZERO))
+            .collect::<BTreeMap<_, _>>();
+        for root in Event::build_tree(events.iter()) {
+            visit(&root, &mut result);
+        }
+
+        result
+    }
+}
+
+/// An event with the events inside it (see [Event::build_tree]).
+#[derive(Clone, Debug, PartialEq)]
+pub struct EventNode {
+    pub event: Event,
+    /// The events directly inside this event, in order of start.
+    pub children: Vec<EventNode>,
+}
+
+impl EventNode {
+    /// Returns the duration of this event minus the time covered by its children, such as the time
+    /// spent in Layout itself rather than in scripts it called, or None if it is instantaneous.
+    pub fn self_duration(&self) -> Option<Duration> {
+        let duration = self.event.duration?;
+        let mut covered = Duration::ZERO;
+        let mut covered_until = self.event.start;
+        for child in self.children.iter() {
+            // Children are in order of start, but siblings may overlap.
+            let start = child.event.start.max(covered_until);
+            let end = child.event.end();
+            if end > start {
+                covered += end - start;
+                covered_until = end;
+            }
+        }
+
+        Some(duration.saturating_sub(covered))
+    }
+}
+
+#[test]
+fn test_build_tree() {
+    let event = |name: &str, start: u64, duration: Option<u64>| Event {
+        name: name.to_owned(),
+        start: Duration::from_secs(start),
+        duration: duration.map(Duration::from_secs),
+        metadata: BTreeMap::default(),
+    };
+    let node = |event: Event, children: Vec<EventNode>| EventNode { event, children };
+    let events = [
+        event("Layout", 0, Some(10)),
+        event("Script", 1, Some(3)),
+        event("Mark", 2, None),
+        event("Script", 3, Some(2)),
+        // Overlaps `Layout` without nesting, so it becomes a sibling of `Layout`.
+        event("Paint", 8, Some(4)),
+    ];
+    let result = Event::build_tree(events.iter());
+    assert_eq!(
+        result,
+        [
+            node(
+                event("Layout", 0, Some(10)),
+                vec![
+                    node(
+                        event("Script", 1, Some(3)),
+                        vec![node(event("Mark", 2, None), vec![])],
+                    ),
+                    // Ends after the first `Script`, so it becomes a sibling of that `Script`.
+                    node(event("Script", 3, Some(2)), vec![]),
+                ],
+            ),
+            node(event("Paint", 8, Some(4)), vec![]),
+        ],
+    );
+
+    // Overlapping children only count once.
+    assert_eq!(result[0].self_duration(), Some(Duration::from_secs(6)));
+    assert_eq!(result[0].children[0].self_duration(), Some(Duration::from_secs(3)));
+    assert_eq!(result[0].children[0].children[0].self_duration(), None);
+}
+
+#[test]
+fn test_build_tree_with_tracks() {
+    let event = |name: &str, start: u64, duration: u64, track: &str| {
+        let mut annotation = DebugAnnotation::new();
+        annotation.set_name("track".to_owned());
+        annotation.set_string_value(track.to_owned());
+        Event {
+            name: name.to_owned(),
+            start: Duration::from_secs(start),
+            duration: Some(Duration::from_secs(duration)),
+            metadata: BTreeMap::from([("track".to_owned(), annotation)]),
+        }
+    };
+    let events = [
+        event("FunctionCall", 0, 10, "main"),
+        event("Layout", 2, 3, "main"),
+        // Overlaps both of the above, but on another thread, so it is nested in neither.
+        event("RasterTask", 1, 5, "raster"),
+    ];
+    let result = Event::build_tree(events.iter());
+    assert_eq!(
+        result
+            .iter()
+            .map(|node| (&*node.event.name, node.children.len()))
+            .collect::<Vec<_>>(),
+        [("FunctionCall", 1), ("RasterTask", 0)],
+    );
+    assert_eq!(
+        Event::sum_self_durations(&events, "FunctionCall Layout RasterTask".split(" ")),
+        BTreeMap::from([
+            ("FunctionCall".to_owned(), Duration::from_secs(7)),
+            ("Layout".to_owned(), Duration::from_secs(3)),
+            ("RasterTask".to_owned(), Duration::from_secs(5)),
+        ])
+    );
+}
+
+#[test]
+fn test_sum_self_durations() {
+    let event = |name: &str, start: u64, duration: u64| Event {
+        name: name.to_owned(),
+        start: Duration::from_secs(start),
+        duration: Some(Duration::from_secs(duration)),
+        metadata: BTreeMap::default(),
+    };
+    let events = [
+        event("ScriptEvaluate", 0, 10),
+        event("LayoutPerform", 2, 3),
+        event("ScriptEvaluate", 20, 1),
+    ];
+    let result = Event::sum_self_durations(&events, "ScriptEvaluate LayoutPerform Paint".split(" "));
+    assert_eq!(
+        result,
+        BTreeMap::from([
+            ("LayoutPerform".to_owned(), Duration::from_secs(3)),
+            ("Paint".to_owned(), Duration::ZERO),
+            ("ScriptEvaluate".to_owned(), Duration::from_secs(8)),
+        ])
+    );
 }
 
 #[test]
@@ -190,125 +669,888 @@ fn test_generate_merged_events() -> eyre::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_generate_merged_events_with_gap() -> eyre::Result<()> {
+    let event = |start: u64, duration: u64| Event {
+        name: "".to_owned(),
+        start: Duration::from_millis(start),
+        duration: Some(Duration::from_millis(duration)),
+        metadata: BTreeMap::default(),
+    };
+    let events = [event(0, 10), event(15, 10), event(30, 10), event(100, 10)];
+    let spans = |events: Vec<Event>| {
+        events
+            .iter()
+            .map(|event| (event.start.as_millis(), event.end().as_millis()))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        spans(Event::generate_merged_events_with_gap(
+            events.iter(),
+            "",
+            Duration::ZERO
+        )?),
+        [(0, 10), (15, 25), (30, 40), (100, 110)]
+    );
+    assert_eq!(
+        spans(Event::generate_merged_events_with_gap(
+            events.iter(),
+            "",
+            Duration::from_millis(6)
+        )?),
+        [(0, 40), (100, 110)]
+    );
+    // Gaps must be shorter than `max_gap` to be bridged.
+    assert_eq!(
+        spans(Event::generate_merged_events_with_gap(
+            events.iter(),
+            "",
+            Duration::from_millis(5)
+        )?),
+        [(0, 10), (15, 25), (30, 40), (100, 110)]
+    );
+    Ok(())
+}
+
+/// Number of bootstrap resamples for the confidence interval of the median, unless overridden.
+pub static DEFAULT_BOOTSTRAP_RESAMPLES: usize = 1000;
+
+/// Options for [Analysis::summary_with_options]. The defaults are the same as [Analysis::summary].
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SummaryOptions {
+    /// Whether to drop outliers first, namely values outside [Q1 − 1.5 × IQR, Q3 + 1.5 × IQR], so
+    /// that one GC pause or scheduling hiccup does not skew the mean and standard deviation.
+    pub trim_outliers: bool,
+    /// Number of times to resample the values for the confidence interval of the median, or zero
+    /// for no confidence interval.
+    pub bootstrap_resamples: usize,
+    /// Which statistic represents the values.
+    pub representative: RepresentativeStatistic,
+}
+
 impl<IndividualType> Analysis<IndividualType> {
     pub fn summary<T: Into<Option<f64>>>(
         &self,
+        getter: impl FnMut(&IndividualType) -> T,
+    ) -> eyre::Result<Summary<f64>> {
+        self.summary_with_options(SummaryOptions::default(), getter)
+    }
+
+    pub fn summary_with_options<T: Into<Option<f64>>>(
+        &self,
+        options: SummaryOptions,
         mut getter: impl FnMut(&IndividualType) -> T,
     ) -> eyre::Result<Summary<f64>> {
-        let xs = self
+        let mut xs = self
             .individuals
             .iter()
             .filter_map(|x| getter(x).into())
             .collect::<Vec<f64>>();
-        let n = xs.len();
-        let mean = xs.iter().sum::<f64>() / (n as f64);
-        let stdev =
-            (xs.iter().map(|x| (x - mean).powf(2.0)).sum::<f64>() / ((n - 1) as f64)).sqrt();
-        let min = xs
-            .iter()
-            .cloned()
-            .min_by(|p, q| p.total_cmp(q))
-            .ok_or_eyre("No minimum")?;
-        let max = xs
-            .iter()
-            .cloned()
-            .max_by(|p, q| p.total_cmp(q))
-            .ok_or_eyre("No maximum")?;
-
-        Ok(Summary {
-            n: self.individuals.len(),
-            mean,
-            stdev,
-            min,
-            max,
-        })
+        let untrimmed_len = xs.len();
+        if options.trim_outliers {
+            xs.sort_by(|p, q| p.total_cmp(q));
+            let q1 = percentile(&xs, 0.25);
+            let q3 = percentile(&xs, 0.75);
+            let iqr = q3 - q1;
+            let (low, high) = (q1 - 1.5 * iqr, q3 + 1.5 * iqr);
+            xs.retain(|&x| (low..=high).contains(&x));
+        }
+        let mut result = summarise(&xs, untrimmed_len - xs.len())?;
+        result.representative_statistic = options.representative;
+        if options.bootstrap_resamples > 0 {
+            let (ci_low, ci_high) = bootstrap_median_ci(&xs, options.bootstrap_resamples);
+            result.ci_low = Some(ci_low);
+            result.ci_high = Some(ci_high);
+        }
+
+        Ok(result)
     }
 }
 
-fn value_unit(x: f64) -> (f64, &'static str) {
-    if x >= 1.0 {
-        (x, "s")
-    } else if x * 1000.0 >= 1.0 {
-        (x * 1000.0, "ms")
-    } else if x * 1000000.0 >= 1.0 {
-        (x * 1000000.0, "μs")
+/// Returns a 95% confidence interval for the median of the given values, by resampling them with
+/// replacement the given number of times. The RNG has a fixed seed, so the same values always
+/// yield the same interval, and reports can be diffed between runs.
+fn bootstrap_median_ci(xs: &[f64], resamples: usize) -> (f64, f64) {
+    let mut rng = StdRng::seed_from_u64(0);
+    let mut medians = (0..resamples)
+        .map(|_| {
+            let mut resample = (0..xs.len())
+                .map(|_| xs[rng.gen_range(0..xs.len())])
+                .collect::<Vec<_>>();
+            resample.sort_by(|p, q| p.total_cmp(q));
+            percentile(&resample, 0.5)
+        })
+        .collect::<Vec<_>>();
+    medians.sort_by(|p, q| p.total_cmp(q));
+
+    (percentile(&medians, 0.025), percentile(&medians, 0.975))
+}
+
+/// Computes a [Summary] of the given values, `trimmed` of which were dropped as outliers.
+fn summarise(xs: &[f64], trimmed: usize) -> eyre::Result<Summary<f64>> {
+    let n = xs.len();
+    if n == 0 {
+        bail!("No values to summarise");
+    }
+    let mean = xs.iter().sum::<f64>() / (n as f64);
+    // The sample standard deviation is undefined for one value, so avoid dividing by zero,
+    // which would make it NaN. [Summary::fmt_stdev] shows it as unknown.
+    let stdev = if n > 1 {
+        (xs.iter().map(|x| (x - mean).powf(2.0)).sum::<f64>() / ((n - 1) as f64)).sqrt()
     } else {
-        (x * 1000000000.0, "ns")
+        0.0
+    };
+    let cv = if mean != 0.0 { stdev / mean.abs() } else { 0.0 };
+    let min = xs
+        .iter()
+        .cloned()
+        .min_by(|p, q| p.total_cmp(q))
+        .ok_or_eyre("No minimum")?;
+    let max = xs
+        .iter()
+        .cloned()
+        .max_by(|p, q| p.total_cmp(q))
+        .ok_or_eyre("No maximum")?;
+    let mut sorted_xs = xs.to_owned();
+    sorted_xs.sort_by(|p, q| p.total_cmp(q));
+
+    Ok(Summary {
+        // Count only the individuals that had a value, not all of the individuals.
+        n,
+        mean,
+        stdev,
+        cv,
+        min,
+        max,
+        median: percentile(&sorted_xs, 0.5),
+        p90: percentile(&sorted_xs, 0.9),
+        p99: percentile(&sorted_xs, 0.99),
+        trimmed,
+        ci_low: None,
+        ci_high: None,
+        bimodal: is_bimodal(xs),
+        representative_statistic: RepresentativeStatistic::default(),
+    })
+}
+
+impl<IndividualType: Individual> Analysis<IndividualType> {
+    pub fn individuals_to_json(&self) -> Vec<JsonIndividual> {
+        let mut result = vec![];
+        for individual in self.individuals.iter() {
+            let events = match individual.synthetic_events() {
+                Ok(events) => events,
+                Err(error) => {
+                    warn!(?error, "Failed to get synthetic events");
+                    continue;
+                }
+            };
+            let mut synthetic_and_interpreted_events = BTreeMap::default();
+            for event in events.iter() {
+                if let Some(duration) = event.duration {
+                    *synthetic_and_interpreted_events
+                        .entry(event.name.clone())
+                        .or_default() += duration.as_secs_f64();
+                }
+            }
+            result.push(JsonIndividual {
+                path: individual.path().to_owned(),
+                synthetic_and_interpreted_events,
+                events: events
+                    .iter()
+                    .map(|event| JsonEvent {
+                        name: event.name.clone(),
+                        start: event.start.as_secs_f64(),
+                        duration: event.duration.map(|duration| duration.as_secs_f64()),
+                    })
+                    .collect(),
+            });
+        }
+
+        result
+    }
+
+    /// Summarise the long tasks in each individual, both as a total duration (`LongTasks`) and as
+    /// a count (`LongTaskCount`). Returns the duration summaries, the count summaries (which are
+    /// not in seconds, so they go with the counters), and the raw series of both.
+    pub fn long_task_summaries(
+        &self,
+        threshold: Duration,
+        summary_options: SummaryOptions,
+    ) -> eyre::Result<(Vec<JsonSummary>, Vec<JsonSummary>, Vec<JsonRawSeries>)> {
+        let mut durations = JsonRawSeries {
+            name: "LongTasks".to_owned(),
+            kind: EventKind::SyntheticOrInterpreted,
+            xs: vec![],
+        };
+        let mut counts = JsonRawSeries {
+            name: "LongTaskCount".to_owned(),
+            kind: EventKind::Counter,
+            xs: vec![],
+        };
+        for individual in self.individuals.iter() {
+            let events = match individual.synthetic_events() {
+                Ok(events) => events,
+                Err(error) => {
+                    warn!(?error, "Failed to get synthetic events");
+                    continue;
+                }
+            };
+            let long_tasks = events
+                .iter()
+                .filter(|e| e.is_long_task(threshold))
+                .collect::<Vec<_>>();
+            durations.xs.push(
+                long_tasks
+                    .iter()
+                    .flat_map(|e| e.duration)
+                    .sum::<Duration>()
+                    .as_secs_f64(),
+            );
+            counts.xs.push(long_tasks.len() as f64);
+        }
+
+        let mut duration_summaries = vec![];
+        let series = Analysis {
+            individuals: durations.xs.clone(),
+        };
+        if let Ok(summary) = series.summary_with_options(summary_options, |&x| x) {
+            duration_summaries.push(summary.to_json(&durations.name));
+        }
+        let mut count_summaries = vec![];
+        let series = Analysis {
+            individuals: counts.xs.clone(),
+        };
+        if let Ok(summary) = series.summary_with_options(summary_options, |&x| x) {
+            count_summaries.push(summary.to_count_json(&counts.name));
+        }
+
+        Ok((duration_summaries, count_summaries, vec![durations, counts]))
+    }
+
+    /// Summarise the `Utilization` of each individual (see [Individual::utilization]).
+    pub fn utilization_summaries(
+        &self,
+        summary_options: SummaryOptions,
+    ) -> (Vec<JsonSummary>, Vec<JsonRawSeries>) {
+        let mut series = JsonRawSeries {
+            name: "Utilization".to_owned(),
+            kind: EventKind::Fraction,
+            xs: vec![],
+        };
+        let getter = |individual: &IndividualType| {
+            let result = match individual.utilization() {
+                Ok(result) => result?,
+                Err(error) => {
+                    warn!(?error, "Failed to compute utilization");
+                    return None;
+                }
+            };
+            series.xs.push(result);
+            Some(result)
+        };
+        let mut summaries = vec![];
+        if let Ok(summary) = self.summary_with_options(summary_options, getter) {
+            summaries.push(summary.to_fraction_json("Utilization"));
+        }
+
+        (summaries, vec![series])
+    }
+
+    /// Summarise the self time (see [Event::sum_self_durations]) of the real events with each of
+    /// the given names in each individual.
+    pub fn self_time_summaries<'name>(
+        &self,
+        names: impl Iterator<Item = &'name str> + Clone,
+        summary_options: SummaryOptions,
+    ) -> Vec<JsonSummary> {
+        let mut self_durations = vec![];
+        for individual in self.individuals.iter() {
+            let events = match individual.real_events() {
+                Ok(events) => events,
+                Err(error) => {
+                    warn!(?error, "Failed to get real events");
+                    continue;
+                }
+            };
+            self_durations.push(Event::sum_self_durations(&events, names.clone()));
+        }
+
+        let analysis = Analysis {
+            individuals: self_durations,
+        };
+        let mut result = vec![];
+        for name in names {
+            let getter = |s: &BTreeMap<String, Duration>| s.get(name).map(|d| d.as_secs_f64());
+            if let Ok(summary) = analysis.summary_with_options(summary_options, getter) {
+                result.push(summary.to_json(name));
+            }
+        }
+
+        result
+    }
+}
+
+/// Returns the given percentile (0 to 1) of the given sorted values, interpolating linearly between
+/// the closest ranks, or NaN if there are no values.
+pub fn percentile(sorted_xs: &[f64], p: f64) -> f64 {
+    if sorted_xs.is_empty() {
+        return f64::NAN;
+    }
+    let rank = p * (sorted_xs.len() - 1) as f64;
+    let lower = rank.floor() as usize;
+    let upper = rank.ceil() as usize;
+
+    sorted_xs[lower] + (sorted_xs[upper] - sorted_xs[lower]) * (rank - lower as f64)
+}
+
+#[test]
+fn test_summary_trimmed() -> eyre::Result<()> {
+    let analysis = Analysis {
+        individuals: vec![1.0, 1.1, 1.2, 1.1, 1.0, 1.2, 1.1, 9.0],
+    };
+    let summary = analysis.summary(|&x| x)?;
+    assert_eq!((summary.n, summary.trimmed, summary.max), (8, 0, 9.0));
+    let options = SummaryOptions {
+        trim_outliers: true,
+        ..SummaryOptions::default()
+    };
+    let summary = analysis.summary_with_options(options, |&x| x)?;
+    assert_eq!((summary.n, summary.trimmed, summary.max), (7, 1, 1.2));
+    assert_eq!(summary.fmt_n(), "7 (1 trimmed)");
+    Ok(())
+}
+
+#[test]
+fn test_bootstrap_median_ci() -> eyre::Result<()> {
+    let analysis = Analysis {
+        individuals: (1..=30).map(|x| x as f64).collect::<Vec<_>>(),
+    };
+    let options = SummaryOptions {
+        bootstrap_resamples: 1000,
+        ..SummaryOptions::default()
+    };
+    let summary = analysis.summary_with_options(options, |&x| x)?;
+    let (low, high) = (summary.ci_low.unwrap(), summary.ci_high.unwrap());
+    assert!(low < summary.median && summary.median < high);
+    assert!(low >= 1.0 && high <= 30.0);
+    // The RNG is seeded, so the interval is the same every time.
+    let again = analysis.summary_with_options(options, |&x| x)?;
+    assert_eq!(
+        (again.ci_low, again.ci_high),
+        (summary.ci_low, summary.ci_high)
+    );
+    assert_eq!(analysis.summary(|&x| x)?.ci_low, None);
+    Ok(())
+}
+
+#[test]
+fn test_summary_of_one_or_zero_values() -> eyre::Result<()> {
+    let summary = Analysis {
+        individuals: vec![1.5],
+    }
+    .summary(|&x| x)?;
+    assert_eq!((summary.n, summary.mean, summary.stdev), (1, 1.5, 0.0));
+    assert_eq!(summary.fmt_stdev(Units::Auto), "—");
+    assert_eq!(summary.fmt_cv(), "—");
+    let error = Analysis {
+        individuals: vec![None::<f64>],
+    }
+    .summary(|&x| x)
+    .unwrap_err();
+    assert_eq!(error.to_string(), "No values to summarise");
+    Ok(())
+}
+
+#[test]
+fn test_percentile() {
+    assert!(percentile(&[], 0.5).is_nan());
+    assert_eq!(percentile(&[3.0], 0.9), 3.0);
+    assert_eq!(percentile(&[1.0, 2.0, 3.0], 0.5), 2.0);
+    assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0], 0.5), 2.5);
+    assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0, 5.0], 0.9), 4.6);
+    assert_eq!(percentile(&[1.0, 2.0, 3.0, 4.0, 5.0], 1.0), 5.0);
+}
+
+/// Returns the geometric mean of the given values, or None if there are no values. Values must be
+/// positive, since the geometric mean is computed via logarithms.
+pub fn geometric_mean(xs: &[f64]) -> Option<f64> {
+    if xs.is_empty() {
+        return None;
+    }
+
+    Some((xs.iter().map(|x| x.ln()).sum::<f64>() / xs.len() as f64).exp())
+}
+
+#[test]
+fn test_geometric_mean() {
+    assert_eq!(geometric_mean(&[]), None);
+    assert_eq!(geometric_mean(&[2.0]), Some(2.0));
+    let mean = geometric_mean(&[1.0, 4.0, 16.0]).unwrap();
+    assert!((mean - 4.0).abs() < 1e-9);
+}
+
+/// Returns true if the given values seem to form two separate clusters, using a simple heuristic:
+/// split the sorted values at the largest gap, then check that each side has at least a fifth of
+/// the values (and at least two), and that the gap is wider than either side’s range.
+pub fn is_bimodal(xs: &[f64]) -> bool {
+    let mut xs = xs.to_owned();
+    xs.sort_by(|p, q| p.total_cmp(q));
+    let min_cluster_len = (xs.len() / 5).max(2);
+    if xs.len() < 2 * min_cluster_len {
+        return false;
+    }
+    let Some((split, gap)) = xs
+        .windows(2)
+        .map(|pair| pair[1] - pair[0])
+        .enumerate()
+        .map(|(i, gap)| (i + 1, gap))
+        .filter(|&(split, _)| split >= min_cluster_len && xs.len() - split >= min_cluster_len)
+        .max_by(|p, q| p.1.total_cmp(&q.1))
+    else {
+        return false;
+    };
+    let (low, high) = xs.split_at(split);
+    let low_range = low[low.len() - 1] - low[0];
+    let high_range = high[high.len() - 1] - high[0];
+
+    gap > low_range && gap > high_range
+}
+
+#[test]
+fn test_is_bimodal() {
+    assert!(!is_bimodal(&[]));
+    assert!(!is_bimodal(&[1.0, 9.0]));
+    assert!(!is_bimodal(&[1.0, 1.1, 1.2, 1.3, 1.4, 1.5, 1.6, 1.7]));
+    assert!(is_bimodal(&[1.0, 5.1, 1.1, 5.0, 1.2, 5.2, 1.1, 5.1]));
+    // One outlier is not a second cluster.
+    assert!(!is_bimodal(&[1.0, 1.1, 1.2, 1.1, 1.0, 1.2, 1.1, 9.0]));
+}
+
+impl<IndividualType> Analysis<IndividualType> {
+    /// Returns the individual whose value for this metric is closest to the given statistic of
+    /// all of the values (so for the minimum, the individual with the minimum value), or None if
+    /// no individuals have a value. If more than one individual is closest, returns the first.
+    pub fn representative_individual<T: Into<Option<f64>>>(
+        &self,
+        statistic: RepresentativeStatistic,
+        mut getter: impl FnMut(&IndividualType) -> T,
+    ) -> Option<&IndividualType> {
+        let values = self
+            .individuals
+            .iter()
+            .filter_map(|individual| Some((individual, getter(individual).into()?)))
+            .collect::<Vec<_>>();
+        let mut xs = values.iter().map(|&(_, x)| x).collect::<Vec<_>>();
+        xs.sort_by(|p, q| p.total_cmp(q));
+        let target = match statistic {
+            RepresentativeStatistic::Min => *xs.first()?,
+            RepresentativeStatistic::Median => percentile(&xs, 0.5),
+            RepresentativeStatistic::Mean => xs.iter().sum::<f64>() / xs.len() as f64,
+        };
+
+        values
+            .into_iter()
+            .reduce(|p, q| {
+                if (q.1 - target).abs() < (p.1 - target).abs() {
+                    q
+                } else {
+                    p
+                }
+            })
+            .map(|(individual, _)| individual)
     }
 }
 
-fn dp(x: f64) -> usize {
-    let (value, _) = value_unit(x);
+#[test]
+fn test_representative_statistic_from_str() {
+    assert_eq!("min".parse(), Ok(RepresentativeStatistic::Min));
+    assert_eq!("median".parse(), Ok(RepresentativeStatistic::Median));
+    assert_eq!("mean".parse(), Ok(RepresentativeStatistic::Mean));
+    assert!("p90".parse::<RepresentativeStatistic>().is_err());
+}
+
+#[test]
+fn test_representative_individual() {
+    let analysis = Analysis {
+        individuals: vec![
+            ("median", Some(2.0)),
+            ("min", Some(1.0)),
+            ("none", None),
+            ("max", Some(3.0)),
+            ("tie", Some(1.0)),
+        ],
+    };
+    assert_eq!(
+        analysis.representative_individual(RepresentativeStatistic::Min, |&(_, x)| x),
+        Some(&("min", Some(1.0))),
+    );
+    // The values are 2, 1, 3, 1, so the median is 1.5 (equally close to 2 and 1, so the first
+    // wins) and the mean is 1.75.
+    assert_eq!(
+        analysis.representative_individual(RepresentativeStatistic::Median, |&(_, x)| x),
+        Some(&("median", Some(2.0))),
+    );
+    assert_eq!(
+        analysis.representative_individual(RepresentativeStatistic::Mean, |&(_, x)| x),
+        Some(&("median", Some(2.0))),
+    );
+    assert_eq!(
+        Analysis::<f64> {
+            individuals: vec![]
+        }
+        .representative_individual(RepresentativeStatistic::Min, |&x| x),
+        None,
+    );
+}
+
+/// Unit to format durations in.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Units {
+    /// Choose the largest unit where the value is at least 1, separately for each value.
+    #[default]
+    Auto,
+    Seconds,
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+fn value_unit(x: f64, units: Units) -> (f64, &'static str) {
+    match units {
+        Units::Auto if x >= 1.0 => (x, "s"),
+        Units::Auto if x * 1000.0 >= 1.0 => (x * 1000.0, "ms"),
+        Units::Auto if x * 1000000.0 >= 1.0 => (x * 1000000.0, "μs"),
+        Units::Auto => (x * 1000000000.0, "ns"),
+        Units::Seconds => (x, "s"),
+        Units::Milliseconds => (x * 1000.0, "ms"),
+        Units::Microseconds => (x * 1000000.0, "μs"),
+        Units::Nanoseconds => (x * 1000000000.0, "ns"),
+    }
+}
+
+/// Returns the number of decimal places for the given value in its unit, for four significant
+/// figures below 1000. Values below 1 only happen with fixed [Units], or for zero.
+fn dp(value: f64) -> usize {
     if value >= 1000.0 {
         0
     } else if value >= 100.0 {
         1
     } else if value >= 10.0 {
         2
-    } else {
+    } else if value >= 1.0 || value == 0.0 {
         3
+    } else {
+        // One more for each leading zero after the decimal point, up to nanosecond precision.
+        (3 + (-value.log10()).ceil() as usize).min(9)
+    }
+}
+
+#[test]
+fn test_dp() {
+    assert_eq!(fmt_seconds_in(1.5, Units::Auto), "1.500s");
+    assert_eq!(fmt_seconds_in(0.0015, Units::Auto), "1.500ms");
+    assert_eq!(fmt_seconds_in(1.5, Units::Milliseconds), "1500ms");
+    assert_eq!(fmt_seconds_in(0.0015, Units::Milliseconds), "1.500ms");
+    assert_eq!(fmt_seconds_in(0.0005, Units::Milliseconds), "0.5000ms");
+    assert_eq!(fmt_seconds_in(0.0000012, Units::Milliseconds), "0.001200ms");
+    assert_eq!(fmt_seconds_in(0.0, Units::Milliseconds), "0.000ms");
+}
+
+pub fn fmt_seconds(x: f64) -> String {
+    fmt_seconds_in(x, Units::Auto)
+}
+
+/// Like [fmt_seconds], but in the given [Units].
+pub fn fmt_seconds_in(x: f64, units: Units) -> String {
+    let (value, unit) = value_unit(x, units);
+    format!("{:.*?}{}", dp(value), value, unit)
+}
+
+/// Like [fmt_seconds], but with a sign, for values that may be negative.
+pub fn fmt_seconds_offset(x: f64) -> String {
+    if x < 0.0 {
+        format!("−{}", fmt_seconds(-x))
+    } else {
+        format!("+{}", fmt_seconds(x))
     }
 }
 
 pub fn fmt_seconds_short(x: f64) -> String {
-    let (value, unit) = value_unit(x);
+    let (value, unit) = value_unit(x, Units::Auto);
     format!("{:.*?}{}", 0, value, unit)
 }
 
+/// Formats a fraction from 0 to 1 as a percentage.
+pub fn fmt_fraction(x: f64) -> String {
+    format!("{:.1}%", x * 100.0)
+}
+
+/// Formats a dimensionless count, without decimal places if it is a whole number.
+pub fn fmt_count(x: f64) -> String {
+    if x.fract() == 0.0 {
+        format!("{x:.0}")
+    } else {
+        format!("{x:.2}")
+    }
+}
+
 impl Summary<f64> {
-    pub fn fmt_representative(&self) -> String {
-        self.fmt_min()
+    /// Returns the value of the representative statistic.
+    pub fn representative(&self) -> f64 {
+        match self.representative_statistic {
+            RepresentativeStatistic::Min => self.min,
+            RepresentativeStatistic::Median => self.median,
+            RepresentativeStatistic::Mean => self.mean,
+        }
+    }
+
+    pub fn fmt_representative(&self, units: Units) -> String {
+        match self.representative_statistic {
+            RepresentativeStatistic::Min => self.fmt_min(units),
+            RepresentativeStatistic::Median => self.fmt_median(units),
+            RepresentativeStatistic::Mean => self.fmt_mean(units),
+        }
     }
 
-    pub fn fmt_full(&self) -> String {
+    pub fn fmt_full(&self, units: Units) -> String {
         format!(
-            "n={}, μ={}, s={}, min={}, max={}",
+            "n={}, μ={}, s={}, cv={}, min={}, median={}, p90={}, p99={}, max={}",
             self.fmt_n(),
-            self.fmt_mean(),
-            self.fmt_stdev(),
-            self.fmt_min(),
-            self.fmt_max(),
-        )
+            self.fmt_mean(units),
+            self.fmt_stdev(units),
+            self.fmt_cv(),
+            self.fmt_min(units),
+            self.fmt_median(units),
+            self.fmt_p90(units),
+            self.fmt_p99(units),
+            self.fmt_max(units),
+        ) + &self
+            .fmt_ci(units)
+            .map_or(String::default(), |ci| format!(", median 95% CI={ci}"))
     }
 
     pub fn fmt_n(&self) -> String {
-        format!("{}", self.n)
+        if self.trimmed > 0 {
+            format!("{} ({} trimmed)", self.n, self.trimmed)
+        } else {
+            format!("{}", self.n)
+        }
+    }
+
+    pub fn fmt_mean(&self, units: Units) -> String {
+        fmt_seconds_in(self.mean, units)
+    }
+
+    pub fn fmt_stdev(&self, units: Units) -> String {
+        if self.n <= 1 {
+            return "—".to_owned();
+        }
+        fmt_seconds_in(self.stdev, units)
+    }
+
+    pub fn fmt_cv(&self) -> String {
+        if self.n <= 1 {
+            return "—".to_owned();
+        }
+        format!("{:.1}%", self.cv * 100.0)
     }
 
-    pub fn fmt_mean(&self) -> String {
-        let (mean, mean_unit) = value_unit(self.mean);
-        format!("{:.*?}{}", dp(self.mean), mean, mean_unit)
+    pub fn fmt_min(&self, units: Units) -> String {
+        fmt_seconds_in(self.min, units)
     }
 
-    pub fn fmt_stdev(&self) -> String {
-        let (stdev, stdev_unit) = value_unit(self.stdev);
-        format!("{:.*?}{}", dp(self.stdev), stdev, stdev_unit)
+    pub fn fmt_max(&self, units: Units) -> String {
+        fmt_seconds_in(self.max, units)
     }
 
-    pub fn fmt_min(&self) -> String {
-        let (min, min_unit) = value_unit(self.min);
-        format!("{:.*?}{}", dp(self.min), min, min_unit)
+    /// Formats the confidence interval for the median, if any, as `[low, high]`.
+    pub fn fmt_ci(&self, units: Units) -> Option<String> {
+        let (low, high) = self.ci_low.zip(self.ci_high)?;
+        Some(format!(
+            "[{}, {}]",
+            fmt_seconds_in(low, units),
+            fmt_seconds_in(high, units)
+        ))
     }
 
-    pub fn fmt_max(&self) -> String {
-        let (max, max_unit) = value_unit(self.max);
-        format!("{:.*?}{}", dp(self.max), max, max_unit)
+    pub fn fmt_median(&self, units: Units) -> String {
+        fmt_seconds_in(self.median, units)
+    }
+
+    pub fn fmt_p90(&self, units: Units) -> String {
+        fmt_seconds_in(self.p90, units)
+    }
+
+    pub fn fmt_p99(&self, units: Units) -> String {
+        fmt_seconds_in(self.p99, units)
     }
 
     pub fn to_json(&self, name: &str) -> JsonSummary {
         JsonSummary {
             name: name.to_owned(),
             raw: self.clone(),
-            full: self.fmt_full(),
-            representative: self.fmt_representative(),
+            full: self.fmt_full(Units::Auto),
+            representative: self.fmt_representative(Units::Auto),
+            bimodal: self.bimodal,
         }
     }
+
+    /// Like [Summary::to_json], but for dimensionless counts rather than seconds.
+    pub fn to_count_json(&self, name: &str) -> JsonSummary {
+        JsonSummary {
+            name: name.to_owned(),
+            raw: self.clone(),
+            full: format!(
+                "n={}, μ={:.2}, s={:.2}, min={}, median={}, p90={}, p99={}, max={}",
+                self.n, self.mean, self.stdev, self.min, self.median, self.p90, self.p99, self.max,
+            ),
+            representative: fmt_count(self.representative()),
+            bimodal: self.bimodal,
+        }
+    }
+
+    /// Like [Summary::to_json], but for fractions from 0 to 1, shown as percentages.
+    pub fn to_fraction_json(&self, name: &str) -> JsonSummary {
+        JsonSummary {
+            name: name.to_owned(),
+            raw: self.clone(),
+            full: format!(
+                "n={}, μ={}, s={}, min={}, median={}, p90={}, p99={}, max={}",
+                self.n,
+                fmt_fraction(self.mean),
+                fmt_fraction(self.stdev),
+                fmt_fraction(self.min),
+                fmt_fraction(self.median),
+                fmt_fraction(self.p90),
+                fmt_fraction(self.p99),
+                fmt_fraction(self.max),
+            ),
+            representative: fmt_fraction(self.representative()),
+            bimodal: self.bimodal,
+        }
+    }
+
+    /// Compares the means of two summaries with Welch’s t-test, which does not assume equal
+    /// variances. Returns the t statistic and the two-tailed p-value, or None if either summary
+    /// has fewer than two values, or there is no variance at all.
+    pub fn welch_t_test(&self, other: &Summary<f64>) -> Option<(f64, f64)> {
+        if self.n < 2 || other.n < 2 {
+            return None;
+        }
+        let (n1, n2) = (self.n as f64, other.n as f64);
+        let (v1, v2) = (self.stdev.powi(2) / n1, other.stdev.powi(2) / n2);
+        let variance = v1 + v2;
+        if variance.is_nan() || variance <= 0.0 {
+            return None;
+        }
+        let t = (self.mean - other.mean) / variance.sqrt();
+        // Welch–Satterthwaite approximation of the degrees of freedom.
+        let df = variance.powi(2) / (v1.powi(2) / (n1 - 1.0) + v2.powi(2) / (n2 - 1.0));
+        let p = regularized_incomplete_beta(df / (df + t * t), df / 2.0, 0.5);
+
+        Some((t, p))
+    }
 }
 
 impl Display for Summary<f64> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} ({})", self.fmt_representative(), self.fmt_full())
+        write!(
+            f,
+            "{} ({})",
+            self.fmt_representative(Units::Auto),
+            self.fmt_full(Units::Auto)
+        )
+    }
+}
+
+/// Returns ln Γ(x) for x > 0, using the Lanczos approximation.
+fn ln_gamma(x: f64) -> f64 {
+    static COEFFICIENTS: [f64; 6] = [
+        76.18009172947146,
+        -86.50532032941678,
+        24.01409824083091,
+        -1.231739572450155,
+        0.001208650973866179,
+        -5.395239384953e-6,
+    ];
+    let tmp = x + 5.5;
+    let tmp = tmp - (x + 0.5) * tmp.ln();
+    let mut series = 1.000000000190015;
+    for (i, coefficient) in COEFFICIENTS.iter().enumerate() {
+        series += coefficient / (x + 1.0 + i as f64);
+    }
+
+    -tmp + (2.5066282746310007 * series / x).ln()
+}
+
+/// Returns the regularised incomplete beta function Iₓ(a, b), using the continued fraction
+/// from Numerical Recipes.
+fn regularized_incomplete_beta(x: f64, a: f64, b: f64) -> f64 {
+    if x <= 0.0 {
+        return 0.0;
+    }
+    if x >= 1.0 {
+        return 1.0;
+    }
+    let front =
+        (ln_gamma(a + b) - ln_gamma(a) - ln_gamma(b) + a * x.ln() + b * (1.0 - x).ln()).exp();
+    // The continued fraction converges quickly only for x < (a + 1) / (a + b + 2).
+    if x < (a + 1.0) / (a + b + 2.0) {
+        front * beta_continued_fraction(x, a, b) / a
+    } else {
+        1.0 - front * beta_continued_fraction(1.0 - x, b, a) / b
+    }
+}
+
+fn beta_continued_fraction(x: f64, a: f64, b: f64) -> f64 {
+    static MAX_ITERATIONS: usize = 200;
+    static EPSILON: f64 = 3e-14;
+    static TINY: f64 = 1e-300;
+
+    let mut c = 1.0;
+    let mut d = 1.0 - (a + b) * x / (a + 1.0);
+    if d.abs() < TINY {
+        d = TINY;
+    }
+    d = 1.0 / d;
+    let mut result = d;
+    for m in 1..=MAX_ITERATIONS {
+        let m = m as f64;
+        for numerator in [
+            m * (b - m) * x / ((a + 2.0 * m - 1.0) * (a + 2.0 * m)),
+            -(a + m) * (a + b + m) * x / ((a + 2.0 * m) * (a + 2.0 * m + 1.0)),
+        ] {
+            d = 1.0 + numerator * d;
+            if d.abs() < TINY {
+                d = TINY;
+            }
+            c = 1.0 + numerator / c;
+            if c.abs() < TINY {
+                c = TINY;
+            }
+            d = 1.0 / d;
+            result *= d * c;
+        }
+        if (d * c - 1.0).abs() < EPSILON {
+            break;
+        }
     }
+
+    result
+}
+
+#[test]
+fn test_welch_t_test() -> eyre::Result<()> {
+    let summary = |xs: Vec<f64>| Analysis { individuals: xs }.summary(|&x| x);
+    let p = summary(vec![1.0, 2.0, 3.0, 4.0, 5.0])?;
+    let q = summary(vec![3.0, 4.0, 5.0, 6.0, 7.0, 8.0, 9.0])?;
+    let (t, p_value) = p.welch_t_test(&q).unwrap();
+    // t = −2.777 with 9.97 degrees of freedom, so p is just under 0.02.
+    assert!((t - -2.7774602993176543).abs() < 1e-9);
+    assert!((p_value - 0.019594474530359014).abs() < 1e-6);
+    assert_eq!(p.welch_t_test(&summary(vec![1.0])?), None);
+    assert_eq!(p.welch_t_test(&p), Some((0.0, 1.0)));
+    Ok(())
 }
 
 impl JsonSummaries {
@@ -318,6 +1560,13 @@ impl JsonSummaries {
 
     pub fn text(&self) -> eyre::Result<String> {
         let mut result = String::default();
+        if !self.notes.is_empty() {
+            writeln!(result, ">>> Notes")?;
+            for note in self.notes.iter() {
+                writeln!(result, "{note}")?;
+            }
+            writeln!(result)?;
+        }
         writeln!(result, ">>> Real events")?;
         for summary in self.real_events.iter() {
             writeln!(
@@ -326,6 +1575,17 @@ impl JsonSummaries {
                 summary.name, summary.representative, summary.full
             )?;
         }
+        if !self.real_events_self_time.is_empty() {
+            writeln!(result)?;
+            writeln!(result, ">>> Real events (self time)")?;
+            for summary in self.real_events_self_time.iter() {
+                writeln!(
+                    result,
+                    "{}: {} ({})",
+                    summary.name, summary.representative, summary.full
+                )?;
+            }
+        }
         writeln!(result)?;
         writeln!(result, ">>> Synthetic and interpreted events")?;
         for summary in self.synthetic_and_interpreted_events.iter() {
@@ -335,7 +1595,181 @@ impl JsonSummaries {
                 summary.name, summary.representative, summary.full
             )?;
         }
+        if !self.counters.is_empty() {
+            writeln!(result)?;
+            writeln!(result, ">>> Counters")?;
+            for summary in self.counters.iter() {
+                writeln!(
+                    result,
+                    "{}: {} ({})",
+                    summary.name, summary.representative, summary.full
+                )?;
+            }
+        }
+
+        if !self.fractions.is_empty() {
+            writeln!(result)?;
+            writeln!(result, ">>> Fractions")?;
+            for summary in self.fractions.iter() {
+                writeln!(
+                    result,
+                    "{}: {} ({})",
+                    summary.name, summary.representative, summary.full
+                )?;
+            }
+        }
 
         Ok(result)
     }
+
+    /// Drops the summaries (including the self time summaries) of real events that occurred (with a
+    /// nonzero duration) in less than `min_coverage` of the individuals, since a summary of one or
+    /// two values out of many would be misleading. Returns the names of the dropped events.
+    pub fn drop_low_coverage_real_events(&mut self, min_coverage: f64) -> Vec<String> {
+        let mut dropped = vec![];
+        for series in self.raw_series.iter() {
+            if matches!(
+                series.kind,
+                EventKind::SyntheticOrInterpreted | EventKind::Counter | EventKind::Fraction
+            ) || series.xs.is_empty()
+            {
+                continue;
+            }
+            let coverage =
+                series.xs.iter().filter(|&&x| x > 0.0).count() as f64 / series.xs.len() as f64;
+            if coverage < min_coverage {
+                warn!(
+                    name = %series.name,
+                    coverage, min_coverage, "Dropping summary of event with low coverage"
+                );
+                dropped.push(series.name.clone());
+            }
+        }
+        self.real_events
+            .retain(|summary| !dropped.contains(&summary.name));
+        self.real_events_self_time
+            .retain(|summary| !dropped.contains(&summary.name));
+
+        dropped
+    }
+
+    /// Adds a note (and warns) if the representative values of the synthetic phases add up to
+    /// more than that of the `Renderer` phase, beyond [SYNTHETIC_PHASE_SUM_TOLERANCE]. Each phase
+    /// is merged separately, so phases that overlap in time are counted more than once in the sum.
+    /// The sum being less is expected, since some `Renderer` events are in no phase.
+    pub fn check_synthetic_phase_sum(&mut self) {
+        let representative = |name: &str| {
+            self.synthetic_and_interpreted_events
+                .iter()
+                .find(|summary| summary.name == name)
+                .map(|summary| summary.raw.representative())
+        };
+        let Some(renderer) = representative("Renderer") else {
+            return;
+        };
+        let phase_sum = SYNTHETIC_PHASE_NAMES
+            .split(" ")
+            .flat_map(representative)
+            .sum::<f64>();
+        if phase_sum > renderer * (1.0 + SYNTHETIC_PHASE_SUM_TOLERANCE) {
+            warn!(
+                phase_sum,
+                renderer, "Synthetic phases add up to more than Renderer; they overlap in time"
+            );
+            self.notes.push(format!(
+                "{SYNTHETIC_PHASE_NAMES} add up to {}, which is more than Renderer ({}), because they overlap in time",
+                fmt_seconds_short(phase_sum),
+                fmt_seconds_short(renderer),
+            ));
+        }
+    }
+}
+
+#[test]
+fn test_check_synthetic_phase_sum() {
+    let summary = |name: &str, x: f64| {
+        Analysis {
+            individuals: vec![x],
+        }
+        .summary(|&x| x)
+        .unwrap()
+        .to_json(name)
+    };
+    let summaries = |renderer: f64| JsonSummaries {
+        schema_version: SUMMARIES_SCHEMA_VERSION,
+        real_events: vec![],
+        real_events_self_time: vec![],
+        synthetic_and_interpreted_events: vec![
+            summary("Renderer", renderer),
+            summary("Parse", 0.25),
+            summary("Script", 0.5),
+            summary("Layout", 0.25),
+            summary("FCP", 2.0),
+        ],
+        counters: vec![],
+        fractions: vec![],
+        raw_series: vec![],
+        individuals: vec![],
+        notes: vec![],
+    };
+    let mut consistent = summaries(1.0);
+    consistent.check_synthetic_phase_sum();
+    assert!(consistent.notes.is_empty());
+    let mut overlapping = summaries(0.5);
+    overlapping.check_synthetic_phase_sum();
+    assert_eq!(overlapping.notes.len(), 1);
+}
+
+#[test]
+fn test_drop_low_coverage_real_events() {
+    let series = |name: &str, kind, xs: &[f64]| JsonRawSeries {
+        name: name.to_owned(),
+        kind,
+        xs: xs.to_owned(),
+    };
+    let summary = |name: &str| {
+        Analysis {
+            individuals: vec![1.0, 2.0],
+        }
+        .summary(|&x| x)
+        .unwrap()
+        .to_json(name)
+    };
+    let mut summaries = JsonSummaries {
+        schema_version: SUMMARIES_SCHEMA_VERSION,
+        real_events: vec![summary("Layout"), summary("TimerFire")],
+        real_events_self_time: vec![summary("Layout"), summary("TimerFire")],
+        synthetic_and_interpreted_events: vec![summary("FCP")],
+        counters: vec![],
+        fractions: vec![],
+        raw_series: vec![
+            series("Layout", EventKind::Chromium, &[1.0, 2.0, 3.0, 4.0]),
+            series("TimerFire", EventKind::Chromium, &[0.0, 0.0, 0.0, 4.0]),
+            series(
+                "FCP",
+                EventKind::SyntheticOrInterpreted,
+                &[0.0, 0.0, 0.0, 4.0],
+            ),
+        ],
+        individuals: vec![],
+        notes: vec![],
+    };
+    assert_eq!(summaries.drop_low_coverage_real_events(0.5), ["TimerFire"]);
+    assert_eq!(
+        summaries
+            .real_events
+            .iter()
+            .map(|summary| &*summary.name)
+            .collect::<Vec<_>>(),
+        ["Layout"],
+    );
+    assert_eq!(
+        summaries
+            .real_events_self_time
+            .iter()
+            .map(|summary| &*summary.name)
+            .collect::<Vec<_>>(),
+        ["Layout"],
+    );
+    assert_eq!(summaries.synthetic_and_interpreted_events.len(), 1);
 }
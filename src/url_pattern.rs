@@ -0,0 +1,104 @@
+use tracing::warn;
+
+/// Returns true if `url` matches `pattern`, where each `*` in the pattern matches any sequence of
+/// characters (including none), and all other characters match themselves.
+pub fn url_matches(pattern: &str, url: &str) -> bool {
+    let Some((prefix, rest)) = pattern.split_once('*') else {
+        return pattern == url;
+    };
+    let Some(mut url) = url.strip_prefix(prefix) else {
+        return false;
+    };
+    let mut parts = rest.split('*').collect::<Vec<_>>();
+    let suffix = parts.pop().expect("Guaranteed by split");
+    for part in parts {
+        let Some(index) = url.find(part) else {
+            return false;
+        };
+        url = &url[index + part.len()..];
+    }
+
+    url.ends_with(suffix)
+}
+
+#[test]
+fn test_url_matches() {
+    assert!(url_matches("https://servo.org/", "https://servo.org/"));
+    assert!(!url_matches(
+        "https://servo.org/",
+        "https://servo.org/blog/"
+    ));
+    assert!(url_matches("https://servo.org/*", "https://servo.org/"));
+    assert!(url_matches(
+        "https://servo.org/?q=*",
+        "https://servo.org/?q=123"
+    ));
+    assert!(url_matches("https://*.org/*/", "https://servo.org/blog/"));
+    assert!(!url_matches("https://*.org/*/", "https://servo.org/blog"));
+    assert!(!url_matches("https://*.org/*.org", "https://servo.org"));
+}
+
+/// Resolves `pattern` to the first of the given URLs that it matches. Every event with that
+/// exact URL will be analysed, so the same frame is chosen consistently.
+///
+/// A pattern that is too broad may match other frames too, so we warn if it matches more than
+/// one distinct URL.
+pub fn resolve_url_pattern<'url>(
+    pattern: &str,
+    urls: impl Iterator<Item = &'url str>,
+) -> Option<&'url str> {
+    let mut matching_urls = urls.filter(|url| url_matches(pattern, url));
+    let result = matching_urls.next()?;
+    let others = matching_urls
+        .filter(|url| *url != result)
+        .collect::<std::collections::BTreeSet<_>>();
+    if !others.is_empty() {
+        warn!(
+            pattern,
+            chosen = result,
+            ?others,
+            "URL pattern matches more than one distinct URL; using the first"
+        );
+    }
+
+    Some(result)
+}
+
+/// Resolves `pattern` to every distinct URL of the given URLs that it matches, in order of first
+/// appearance, such as to analyse each matching frame separately.
+pub fn resolve_url_pattern_to_all<'url>(
+    pattern: &str,
+    urls: impl Iterator<Item = &'url str>,
+) -> Vec<&'url str> {
+    let mut result = vec![];
+    for url in urls.filter(|url| url_matches(pattern, url)) {
+        if !result.contains(&url) {
+            result.push(url);
+        }
+    }
+
+    result
+}
+
+#[test]
+fn test_resolve_url_pattern_to_all() {
+    let urls = [
+        "https://servo.org/",
+        "https://ads.example/1",
+        "https://servo.org/",
+        "https://ads.example/2",
+    ];
+    assert_eq!(
+        resolve_url_pattern_to_all("*", urls.into_iter()),
+        [
+            "https://servo.org/",
+            "https://ads.example/1",
+            "https://ads.example/2",
+        ]
+    );
+    assert_eq!(
+        resolve_url_pattern_to_all("https://ads.example/*", urls.into_iter()),
+        ["https://ads.example/1", "https://ads.example/2"]
+    );
+    assert!(resolve_url_pattern_to_all("https://example.org/", urls.into_iter()).is_empty());
+}
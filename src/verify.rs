@@ -0,0 +1,119 @@
+use std::path::Path;
+
+use jane_eyre::eyre::{self, bail};
+use toml::Value;
+use tracing::{info, warn};
+
+use crate::study::Study;
+
+pub fn main(args: Vec<String>) -> eyre::Result<()> {
+    let study_dir = Path::new(args.iter().nth(0).expect("Usage: verify <studies/example>"));
+    let study = Study::load(study_dir.join("study.toml"))?;
+
+    // Round-trip the study through serialization, then look for keys in the source that did not
+    // survive, such as typos that serde silently ignored.
+    let source = toml::from_str::<Value>(&study.source_toml)?;
+    let parsed = Value::try_from(&study)?;
+    let mut dropped_keys = vec![];
+    find_dropped_keys("", &source, &parsed, &mut dropped_keys);
+    for key in dropped_keys.iter() {
+        warn!(key, "Key in study.toml was ignored");
+    }
+    if !dropped_keys.is_empty() {
+        bail!(
+            "Found {} keys in study.toml that were ignored (see above)",
+            dropped_keys.len()
+        );
+    }
+    info!("All keys in study.toml were understood");
+
+    Ok(())
+}
+
+/// Finds keys that are in `source` but not in `parsed`, ignoring ordering and formatting
+/// differences, and pushes their dotted paths to `result`.
+fn find_dropped_keys(prefix: &str, source: &Value, parsed: &Value, result: &mut Vec<String>) {
+    match (source, parsed) {
+        (Value::Table(source), Value::Table(parsed)) => {
+            for (key, source_value) in source {
+                let path = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{prefix}.{key}")
+                };
+                match parsed.get(key) {
+                    Some(parsed_value) => {
+                        find_dropped_keys(&path, source_value, parsed_value, result)
+                    }
+                    None => result.push(path),
+                }
+            }
+        }
+        (Value::Array(source), Value::Array(parsed)) => {
+            for (i, (source_value, parsed_value)) in source.iter().zip(parsed).enumerate() {
+                find_dropped_keys(
+                    &format!("{prefix}[{i}]"),
+                    source_value,
+                    parsed_value,
+                    result,
+                );
+            }
+        }
+        // An array of tables with a `key` each, like `[[sites]]`, is parsed into a table by key.
+        (Value::Array(source), Value::Table(parsed)) => {
+            for (i, source_value) in source.iter().enumerate() {
+                let Some(mut source_value) = source_value.as_table().cloned() else {
+                    continue;
+                };
+                let Some(Value::String(key)) = source_value.remove("key") else {
+                    continue;
+                };
+                if let Some(parsed_value) = parsed.get(&key) {
+                    find_dropped_keys(
+                        &format!("{prefix}[{i}]"),
+                        &Value::Table(source_value),
+                        parsed_value,
+                        result,
+                    );
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+#[test]
+fn test_find_dropped_keys() -> eyre::Result<()> {
+    let source = toml::from_str::<Value>(
+        r#"
+        sample_size = 30
+        smaple_size = 30
+        [sites."example.com"]
+        url = "http://example.com/"
+        browser_open_tiem = 20
+        [[engines]]
+        key = "servo"
+        pth = "servo"
+        "#,
+    )?;
+    let parsed = toml::from_str::<Value>(
+        r#"
+        sample_size = 30
+        [sites."example.com"]
+        url = "http://example.com/"
+        [engines.servo]
+        "#,
+    )?;
+    let mut result = vec![];
+    find_dropped_keys("", &source, &parsed, &mut result);
+    assert_eq!(
+        result,
+        [
+            "engines[0].pth",
+            r#"sites.example.com.browser_open_tiem"#,
+            "smaple_size",
+        ]
+    );
+
+    Ok(())
+}
@@ -0,0 +1,463 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    io::Read,
+    time::Duration,
+};
+
+use jane_eyre::eyre::{self, OptionExt};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::{debug, error_span, info, trace, warn};
+
+use crate::{
+    analyse::{dedupe_input_paths, expand_input_globs, open_trace, record_failed_path},
+    summary::{
+        drop_implausible_events, Analysis, CategoryNames, Event, EventKind, Individual,
+        JsonRawSeries, JsonSummaries, SummaryOptions, DEFAULT_LONG_TASK_THRESHOLD,
+        SUMMARIES_SCHEMA_VERSION, SYNTHETIC_NAMES,
+    },
+};
+
+static RENDERER_NAMES: &'static str = "EvaluateScript FunctionCall TimerFire EventDispatch FireAnimationFrame ObserverCallback RecalculateStyles Layout Paint Composite";
+// WebKit timelines have no record type for HTML parsing.
+static PARSE_NAMES: &'static str = "";
+static SCRIPT_NAMES: &'static str =
+    "EvaluateScript FunctionCall TimerFire EventDispatch FireAnimationFrame ObserverCallback";
+static LAYOUT_NAMES: &'static str = "RecalculateStyles Layout Paint";
+static RASTERISE_NAMES: &'static str = "Composite";
+static METRICS: &'static [(&'static str, &'static str)] =
+    &[("FP", "first-paint"), ("FCP", "first-contentful-paint")];
+
+/// Returns the built-in [CategoryNames], for display in reports.
+pub fn default_category_names() -> CategoryNames {
+    CategoryNames {
+        renderer: RENDERER_NAMES.to_owned(),
+        parse: PARSE_NAMES.to_owned(),
+        script: SCRIPT_NAMES.to_owned(),
+        layout: LAYOUT_NAMES.to_owned(),
+        rasterise: RASTERISE_NAMES.to_owned(),
+        no_url: String::new(),
+    }
+}
+
+pub fn main(args: Vec<String>) -> eyre::Result<()> {
+    let summaries = compute_summaries(
+        expand_input_globs(args)?,
+        DEFAULT_LONG_TASK_THRESHOLD,
+        None,
+        SummaryOptions::default(),
+    )?;
+
+    println!("{}", summaries.json());
+    println!();
+    println!("{}", summaries.text()?);
+
+    Ok(())
+}
+
+#[tracing::instrument(level = "error")]
+pub fn compute_summaries(
+    args: Vec<String>,
+    long_task_threshold: Duration,
+    max_plausible_event_duration: Option<Duration>,
+    summary_options: SummaryOptions,
+) -> Result<JsonSummaries, eyre::Error> {
+    info!("Computing summaries");
+    let individuals = analyse_individuals(&args, max_plausible_event_duration)?;
+    let durations_keys = individuals
+        .iter()
+        .flat_map(|s| s.durations.keys())
+        .map(|name| name.to_owned())
+        .collect::<BTreeSet<_>>();
+    let mut raw_series = durations_keys
+        .iter()
+        .map(|name| JsonRawSeries {
+            name: name.to_owned(),
+            kind: EventKind::WebKit,
+            xs: individuals
+                .iter()
+                .flat_map(|i| i.durations.get(name))
+                .map(|x| x.as_secs_f64())
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+
+    let analysis = Analysis { individuals };
+    let real_events_self_time =
+        analysis.self_time_summaries(durations_keys.iter().map(|name| &**name), summary_options);
+
+    let mut real_events = vec![];
+    let mut synthetic_and_interpreted_events = vec![];
+
+    for name in durations_keys {
+        let getter = |s: &IndividualAnalysis| s.durations.get(&name).map(|d| d.as_secs_f64());
+        let summary = analysis.summary_with_options(summary_options, getter);
+        if let Ok(summary) = summary {
+            real_events.push(summary.to_json(&name));
+        };
+    }
+
+    for synthetic_name in SYNTHETIC_NAMES.split(" ") {
+        let mut series = JsonRawSeries {
+            name: synthetic_name.to_owned(),
+            kind: EventKind::SyntheticOrInterpreted,
+            xs: vec![],
+        };
+        let getter = |s: &IndividualAnalysis| {
+            let events = match s.synthetic_events() {
+                Ok(events) => events,
+                Err(error) => {
+                    warn!(?error, "Failed to get synthetic events");
+                    return None;
+                }
+            };
+            let result = events
+                .iter()
+                .filter(|e| e.name == synthetic_name)
+                .flat_map(|e| e.duration.map(|d| d.as_secs_f64()))
+                .sum::<f64>();
+            series.xs.push(result);
+            Some(result)
+        };
+        // Trim outliers from the summary only, so the raw series still has every value.
+        let summary = analysis.summary_with_options(summary_options, getter);
+        if let Ok(summary) = summary {
+            synthetic_and_interpreted_events.push(summary.to_json(synthetic_name));
+        }
+        raw_series.push(series);
+    }
+
+    let (long_task_summaries, long_task_counts, long_task_series) =
+        analysis.long_task_summaries(long_task_threshold, summary_options)?;
+    synthetic_and_interpreted_events.extend(long_task_summaries);
+    raw_series.extend(long_task_series);
+    let (fractions, fraction_series) = analysis.utilization_summaries(summary_options);
+    raw_series.extend(fraction_series);
+
+    let mut summaries = JsonSummaries {
+        schema_version: SUMMARIES_SCHEMA_VERSION,
+        real_events,
+        real_events_self_time,
+        synthetic_and_interpreted_events,
+        counters: long_task_counts,
+        fractions,
+        raw_series,
+        individuals: analysis.individuals_to_json(),
+        notes: vec![],
+    };
+    summaries.check_synthetic_phase_sum();
+
+    Ok(summaries)
+}
+
+pub fn analyse_individuals(
+    args: &[String],
+    max_plausible_event_duration: Option<Duration>,
+) -> eyre::Result<Vec<IndividualAnalysis>> {
+    // WebKit timeline records have no URLs to filter by, but each timeline only covers one page
+    // load anyway (see benchmark-webkit.sh), so we take the URL for consistency only.
+    let url = args.iter().nth(0).unwrap().to_owned();
+    debug!(url, "Ignoring URL for WebKit timelines");
+    let paths = dedupe_input_paths(&args[1..])?;
+
+    let mut individuals = vec![];
+    for (path, result) in paths
+        .iter()
+        .map(|path| {
+            (
+                path.to_owned(),
+                analyse_individual(path, max_plausible_event_duration),
+            )
+        })
+        .collect::<Vec<_>>()
+    {
+        let span = error_span!("analyse", path = path);
+        let _enter = span.enter();
+        match result {
+            Ok(result) => individuals.push(result),
+            Err(error) => {
+                warn!("Failed to analyse file: {error}");
+                record_failed_path(&path);
+            }
+        }
+    }
+
+    Ok(individuals)
+}
+
+#[tracing::instrument(level = "error")]
+fn analyse_individual(
+    path: &str,
+    max_plausible_event_duration: Option<Duration>,
+) -> eyre::Result<IndividualAnalysis> {
+    info!("Analysing individual");
+
+    let mut json = String::default();
+    open_trace(path)?.read_to_string(&mut json)?;
+    let timeline = serde_json::from_str::<WebKitTimeline>(&json)?;
+
+    // Records nest, e.g. Layout within FunctionCall, so flatten them like the spans in our
+    // other traces.
+    let mut all_events = vec![];
+    let mut stack = timeline.records.iter().collect::<Vec<_>>();
+    while let Some(record) = stack.pop() {
+        all_events.push(Event::try_from(record)?);
+        stack.extend(record.children.iter());
+    }
+    all_events.sort_by(|p, q| p.start.cmp(&q.start).then(p.duration.cmp(&q.duration)));
+
+    let mut result = vec![];
+    let start_timestamp = all_events.first().ok_or_eyre("No records")?.start;
+    for event in all_events {
+        let new_timestamp = event.start - start_timestamp;
+        if let Some(dur) = event.duration {
+            debug!("{:?} +{:?} {}", new_timestamp, dur, event.name);
+        } else {
+            debug!("{:?} {}", new_timestamp, event.name);
+        }
+        trace!("{:?}", event);
+        result.push(Event {
+            start: new_timestamp,
+            ..event
+        });
+    }
+
+    let result =
+        drop_implausible_events(result, |e| e.duration, max_plausible_event_duration, path);
+
+    let mut durations = BTreeMap::default();
+    for name in RENDERER_NAMES.split(" ") {
+        let duration = IndividualAnalysis::sum_duration(&result, name);
+        debug!("{name}: {:?}", duration);
+        durations.insert(name.to_owned(), duration);
+    }
+
+    let paint_timings = timeline
+        .paintTimings
+        .into_iter()
+        .map(|timing| (timing.name, timing.startTime))
+        .collect();
+
+    Ok(IndividualAnalysis {
+        path: path.to_owned(),
+        relevant_events: result,
+        durations,
+        paint_timings,
+    })
+}
+
+#[test]
+fn test_analyse_individual() -> eyre::Result<()> {
+    let timeline = serde_json::json!({
+        "records": [
+            {
+                "type": "FunctionCall",
+                "startTime": 1.0,
+                "endTime": 1.5,
+                "children": [{ "type": "Layout", "startTime": 1.25, "endTime": 1.375 }],
+            },
+            { "type": "Paint", "startTime": 2.0, "endTime": null },
+        ],
+        "paintTimings": [
+            { "name": "first-paint", "startTime": 125.0 },
+            { "name": "first-contentful-paint", "startTime": 250.0 },
+        ],
+    });
+    let dir = mktemp::Temp::new_dir()?;
+    let path = dir.join("webkit1.json");
+    std::fs::write(&path, serde_json::to_string(&timeline)?)?;
+
+    let individual = analyse_individual(path.to_str().ok_or_eyre("Unsupported path")?, None)?;
+    // Nested records are flattened, and times are relative to the first record.
+    let events = individual
+        .relevant_events
+        .iter()
+        .map(|e| (&*e.name, e.start, e.duration))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        events,
+        [
+            (
+                "FunctionCall",
+                Duration::ZERO,
+                Some(Duration::from_millis(500))
+            ),
+            (
+                "Layout",
+                Duration::from_millis(250),
+                Some(Duration::from_millis(125))
+            ),
+            ("Paint", Duration::from_secs(1), None),
+        ]
+    );
+    assert_eq!(individual.durations["Layout"], Duration::from_millis(125));
+    assert_eq!(individual.durations["Paint"], Duration::ZERO);
+
+    // Paint timings become FP and FCP, measured from the start of the timeline.
+    let paint_metrics = individual
+        .synthetic_events()?
+        .into_iter()
+        .filter(|e| e.name == "FP" || e.name == "FCP")
+        .map(|e| (e.name, e.start, e.duration))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        paint_metrics,
+        [
+            (
+                "FP".to_owned(),
+                Duration::ZERO,
+                Some(Duration::from_millis(125))
+            ),
+            (
+                "FCP".to_owned(),
+                Duration::ZERO,
+                Some(Duration::from_millis(250))
+            ),
+        ]
+    );
+
+    Ok(())
+}
+
+/// WebKit timeline, as written by benchmark-webkit.sh.
+#[derive(Clone, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct WebKitTimeline {
+    /// Records from `Timeline.eventRecorded` in the Web Inspector protocol.
+    records: Vec<TimelineRecord>,
+    /// Entries from `performance.getEntriesByType("paint")`.
+    #[serde(default)]
+    paintTimings: Vec<PaintTiming>,
+}
+
+/// Record in a WebKit timeline, with times in seconds.
+/// <https://github.com/WebKit/WebKit/blob/main/Source/JavaScriptCore/inspector/protocol/Timeline.json>
+#[derive(Clone, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct TimelineRecord {
+    r#type: String,
+    startTime: f64,
+    endTime: Option<f64>,
+    #[serde(default)]
+    children: Vec<TimelineRecord>,
+    #[serde(flatten)]
+    _rest: BTreeMap<String, Value>,
+}
+
+/// Paint timing entry, with its time in milliseconds since navigation start.
+/// <https://w3c.github.io/paint-timing/>
+#[derive(Clone, Debug, Deserialize)]
+#[allow(non_snake_case)]
+struct PaintTiming {
+    name: String,
+    startTime: f64,
+}
+
+impl TryFrom<&TimelineRecord> for Event {
+    type Error = eyre::Report;
+
+    fn try_from(record: &TimelineRecord) -> eyre::Result<Self> {
+        let start = Duration::try_from_secs_f64(record.startTime)?;
+        let duration = match record.endTime {
+            Some(end_time) => Some(Duration::try_from_secs_f64(end_time - record.startTime)?),
+            None => None,
+        };
+
+        Ok(Self {
+            name: record.r#type.clone(),
+            start,
+            duration,
+            metadata: BTreeMap::default(),
+        })
+    }
+}
+
+pub struct IndividualAnalysis {
+    path: String,
+    relevant_events: Vec<Event>,
+    durations: BTreeMap<String, Duration>,
+    /// Time of each paint timing entry, in milliseconds since navigation start.
+    paint_timings: BTreeMap<String, f64>,
+}
+
+impl Individual for IndividualAnalysis {
+    fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn real_events(&self) -> eyre::Result<Vec<Event>> {
+        Ok(self.relevant_events.clone())
+    }
+
+    fn synthetic_events(&self) -> eyre::Result<Vec<Event>> {
+        let real_events = self.real_events()?;
+
+        // Add some synthetic events with our interpretations.
+        let renderer_events = real_events.iter().filter(|e| {
+            RENDERER_NAMES
+                .split(" ")
+                .find(|&name| name == e.name)
+                .is_some()
+        });
+        let parse_events = real_events.iter().filter(|e| {
+            PARSE_NAMES
+                .split(" ")
+                .find(|&name| name == e.name)
+                .is_some()
+        });
+        let script_events = real_events.iter().filter(|e| {
+            SCRIPT_NAMES
+                .split(" ")
+                .find(|&name| name == e.name)
+                .is_some()
+        });
+        let layout_events = real_events.iter().filter(|e| {
+            LAYOUT_NAMES
+                .split(" ")
+                .find(|&name| name == e.name)
+                .is_some()
+        });
+        let rasterise_events = real_events.iter().filter(|e| {
+            RASTERISE_NAMES
+                .split(" ")
+                .find(|&name| name == e.name)
+                .is_some()
+        });
+        let renderer_events = Event::generate_merged_events(renderer_events, "Renderer")?;
+        let mut result = [
+            renderer_events,
+            Event::generate_merged_events(parse_events, "Parse")?,
+            Event::generate_merged_events(script_events, "Script")?,
+            Event::generate_merged_events(layout_events, "Layout")?,
+            Event::generate_merged_events(rasterise_events, "Rasterise")?,
+        ]
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+        // Paint timings are measured from navigation start, which is not on the timeline’s
+        // clock, so we start these events at the start of the timeline.
+        for (result_name, entry_name) in METRICS {
+            let Some(&start_time) = self.paint_timings.get(*entry_name) else {
+                continue;
+            };
+            result.push(Event {
+                name: (*result_name).to_owned(),
+                start: Duration::ZERO,
+                duration: Some(Duration::try_from_secs_f64(start_time / 1000.0)?),
+                metadata: BTreeMap::default(),
+            });
+        }
+
+        Ok(result)
+    }
+}
+
+impl IndividualAnalysis {
+    fn sum_duration(relevant_events: &[Event], name: &str) -> Duration {
+        relevant_events
+            .iter()
+            .filter(|e| e.name == name)
+            .filter_map(|e| e.duration)
+            .sum()
+    }
+}
@@ -1,6 +1,6 @@
 #!/usr/bin/env zsh
 # Based on: <https://testbit.eu/2023/cgroup-cpuset>
-# To enable: isolate-cpu-for-shell.sh <pid> <cpu ids ...>
+# To enable: isolate-cpu-for-shell.sh [--mems=<numa node ids>] <pid> <cpu ids ...>
 #       e.g. isolate-cpu-for-shell.sh $$ 10 {13..15}
 # To disable: undo-cpu-isolation.sh
 set -euo pipefail
@@ -24,6 +24,9 @@ done
 # Make the shield cgroup no longer a partition root.
 echo member > /sys/fs/cgroup/shield/cpuset.cpus.partition || :
 
+# Allow the shield cgroup to use memory from all NUMA nodes again.
+echo > /sys/fs/cgroup/shield/cpuset.mems || :
+
 # Move all cgroups to all CPUs.
 all_cpus=$(all_cpu_ids | tr \\n ,)
 all_cpus=${all_cpus%,}